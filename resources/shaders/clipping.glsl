@@ -0,0 +1,35 @@
+// Clipping slab and depth fog, shared by the fragment shaders of the scene geometry (see
+// `shaders.rs`).
+
+// View matrix of the camera the slab is defined for, the light view of the shadow pass being
+// another one.
+uniform mat4 clip_view;
+uniform float clip_near;
+uniform float clip_far;
+uniform int fog_mode;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
+uniform vec3 fog_color;
+
+// Distance of a point from the camera, along its view direction.
+float view_depth(vec3 position) {
+    return -(clip_view * vec4(position, 1.0)).z;
+}
+
+// Whether a point lies outside of the slab between the near and far clipping planes.
+bool is_clipped(vec3 position) {
+    float depth = view_depth(position);
+    return depth < clip_near || depth > clip_far;
+}
+
+// Blend the color toward the fog color with the depth of the point, from `fog_start` (no fog) to
+// `fog_end`, linearly (mode 1) or exponentially (mode 2).
+vec3 apply_fog(vec3 color, vec3 position) {
+    if (fog_mode == 0) {
+        return color;
+    }
+    float depth = clamp((view_depth(position) - fog_start) / max(fog_end - fog_start, 1e-4), 0.0, 1.0);
+    float fog = fog_mode == 1 ? depth * fog_density : 1.0 - exp(-3.0 * fog_density * depth);
+    return mix(color, fog_color, clamp(fog, 0.0, 1.0));
+}
@@ -9,6 +9,7 @@ layout(location = 3) in vec3 instance_end_pos;
 layout(location = 4) in vec4 instance_color_first_half;
 layout(location = 5) in vec4 instance_color_second_half;
 layout(location = 6) in float instance_radius;
+layout(location = 7) in float instance_dash_length;
 
 out vec2 v_uv_coordinates;
 out vec3 v_world_pos;
@@ -17,10 +18,12 @@ out vec3 v_end;
 out vec4 v_color_second_half;
 out vec4 v_color_first_half;
 out float v_radius;
+out float v_dash_length;
 
 uniform mat4 view;
 uniform mat4 projection;
 uniform mat4 model;
+uniform bool parallel_rays;
 
 void main() {
     vec3 transformed_start_pos = (model * vec4(instance_start_pos, 1.0)).xyz;
@@ -31,6 +34,10 @@ void main() {
 
     vec3 camera_pos = vec3(inverse(view)[3]);
     vec3 camera_dir = normalize(camera_pos - transformed_start_pos);
+    // Parallel rays all go along the view direction.
+    if (parallel_rays) {
+        camera_dir = vec3(view[0][2], view[1][2], view[2][2]);
+    }
 
     // Create basis for billboard
     vec3 right = normalize(cross(cylinder_dir, camera_dir));
@@ -55,5 +62,6 @@ void main() {
     v_color_first_half = instance_color_first_half;
     v_color_second_half = instance_color_second_half;
     v_radius = scaled_radius;
+    v_dash_length = instance_dash_length * scale_x;
     gl_Position = projection * view * vec4(world_pos, 1.0);
 }
@@ -0,0 +1,18 @@
+#version 410 core
+
+layout(location = 0) in vec3 anchor;
+layout(location = 1) in vec2 offset;
+layout(location = 2) in vec4 color;
+
+out vec4 v_color;
+
+uniform mat4 view;
+uniform mat4 projection;
+uniform mat4 model;
+
+void main() {
+    v_color = color;
+    // The offset is applied in view space, so that the label faces the camera.
+    vec4 view_anchor = view * model * vec4(anchor, 1.0);
+    gl_Position = projection * (view_anchor + vec4(offset, 0.0, 0.0));
+}
@@ -0,0 +1,20 @@
+#version 410 core
+
+in vec3 v_world_pos;
+in vec4 v_color;
+
+layout(location = 0) out vec4 frag_color;
+// View space normal, mapped to [0, 1], read by the ambient occlusion pass.
+layout(location = 1) out vec4 frag_normal;
+
+uniform mat4 view;
+
+void main() {
+    if (is_clipped(v_world_pos)) {
+        discard;
+    }
+
+    frag_color = vec4(apply_fog(v_color.rgb, v_world_pos), v_color.a);
+    // Lines have no surface, they face the camera.
+    frag_normal = vec4(0.5, 0.5, 1.0, v_color.a);
+}
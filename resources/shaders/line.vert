@@ -0,0 +1,18 @@
+#version 410 core
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+
+out vec3 v_world_pos;
+out vec4 v_color;
+
+uniform mat4 view;
+uniform mat4 projection;
+uniform mat4 model;
+
+void main() {
+    vec4 world_pos = model * vec4(position, 1.0);
+    v_world_pos = world_pos.xyz;
+    v_color = color;
+    gl_Position = projection * view * world_pos;
+}
@@ -0,0 +1,66 @@
+#version 410 core
+
+in vec3 v_world_pos;
+in vec3 v_normal;
+in vec4 v_color;
+
+layout(location = 0) out vec4 frag_color;
+// View space normal, mapped to [0, 1], read by the ambient occlusion pass.
+layout(location = 1) out vec4 frag_normal;
+
+uniform vec3 light_position;
+uniform vec3 camera_position;
+uniform mat4 view;
+uniform bool u_show_silhouette;
+uniform bool parallel_rays;
+
+void main() {
+    if (is_clipped(v_world_pos)) {
+        discard;
+    }
+
+    vec3 view_dir = normalize(camera_position - v_world_pos);
+    // Orthographic views look along the view direction from everywhere.
+    if (parallel_rays) {
+        view_dir = vec3(view[0][2], view[1][2], view[2][2]);
+    }
+
+    // Meshes are not closed everywhere (arrow heads), back faces are lit as front faces.
+    vec3 normal = normalize(v_normal);
+    if (dot(normal, view_dir) < 0.0) {
+        normal = -normal;
+    }
+
+    vec3 light_dir = normalize(light_position - v_world_pos);
+    float distance_to_light = distance(light_position, v_world_pos);
+
+    // Attenuation based on distance
+    float attenuation = 1.0 / (1.0 + 0.09 * distance_to_light + 0.032 * distance_to_light * distance_to_light);
+
+    float visibility = light_visibility(v_world_pos, normal);
+    float diffuse = max(dot(normal, light_dir), 0.0) * attenuation * visibility;
+
+    vec3 reflect_dir = reflect(-light_dir, normal);
+    float shininess = 16.0;
+    float specular = pow(max(dot(view_dir, reflect_dir), 0.0), shininess) * attenuation * visibility;
+
+    vec3 ambient_light = vec3(0.3, 0.3, 0.4); // Slightly bluish ambient light
+    vec3 ambient = ambient_light * 0.7;
+
+    vec3 final_color = v_color.rgb * (ambient + diffuse) + specular;
+
+    const float SILHOUETTE_THRESHOLD = 0.4;
+    const vec3 SILHOUETTE_COLOR = vec3(0.0, 0.0, 0.0);
+
+    float ndotl = dot(normal, view_dir);
+    float silhouette_factor = smoothstep(SILHOUETTE_THRESHOLD, SILHOUETTE_THRESHOLD + 0.1, abs(ndotl));
+
+    if (u_show_silhouette && silhouette_factor < 1.0) {
+        final_color = mix(SILHOUETTE_COLOR, final_color, silhouette_factor);
+    }
+
+    final_color = apply_fog(final_color, v_world_pos);
+
+    frag_color = vec4(final_color, v_color.a);
+    frag_normal = vec4(normalize(mat3(view) * normal) * 0.5 + 0.5, v_color.a);
+}
@@ -0,0 +1,24 @@
+#version 410 core
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec4 color;
+
+out vec3 v_world_pos;
+out vec3 v_normal;
+out vec4 v_color;
+
+uniform mat4 view;
+uniform mat4 projection;
+uniform mat4 model;
+
+void main() {
+    vec4 world_pos = model * vec4(position, 1.0);
+
+    // The model matrix is a rotation and an uniform scaling, so it also transforms the normals.
+    v_world_pos = world_pos.xyz;
+    v_normal = normalize(mat3(model) * normal);
+    v_color = color;
+
+    gl_Position = projection * view * world_pos;
+}
@@ -0,0 +1,11 @@
+#version 410 core
+
+layout(location = 0) in vec2 position;
+
+out vec2 v_uv_coordinates;
+
+void main() {
+    // The quad covers the whole viewport, from -1 to 1 in normalized device coordinates.
+    v_uv_coordinates = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
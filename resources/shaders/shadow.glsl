@@ -0,0 +1,28 @@
+// Shadow map lookup, shared by the fragment shaders of the lit geometry (see `shaders.rs`).
+
+uniform bool shadows;
+uniform sampler2DShadow shadow_map;
+uniform mat4 light_space;
+uniform float shadow_bias;
+
+// Fraction of the light reaching the point, averaged over the 3x3 shadow map texels around it
+// (percentage closer filtering) to soften the shadow edges.
+float light_visibility(vec3 position, vec3 normal) {
+    if (!shadows) {
+        return 1.0;
+    }
+    // The point is moved along its normal so that surfaces don't shadow themselves.
+    vec4 light_clip = light_space * vec4(position + normal * shadow_bias, 1.0);
+    vec3 coordinates = light_clip.xyz / light_clip.w * 0.5 + 0.5;
+    if (any(lessThan(coordinates, vec3(0.0))) || any(greaterThan(coordinates, vec3(1.0)))) {
+        return 1.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+    float visibility = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            visibility += texture(shadow_map, vec3(coordinates.xy + vec2(x, y) * texel, coordinates.z));
+        }
+    }
+    return visibility / 9.0;
+}
@@ -7,7 +7,9 @@ in vec4 v_color;
 in float v_radius;
 in float v_depth;
 
-out vec4 frag_color;
+layout(location = 0) out vec4 frag_color;
+// View space normal, mapped to [0, 1], read by the ambient occlusion pass.
+layout(location = 1) out vec4 frag_normal;
 
 uniform vec3 light_position;
 uniform vec3 camera_position;
@@ -15,6 +17,8 @@ uniform bool debug_billboard;
 uniform mat4 projection;
 uniform mat4 view;
 uniform bool u_show_silhouette;
+uniform bool parallel_rays;
+uniform bool depth_only;
 
 void main() {
     // Convert texture coordinates from [0,1] to [-1,1]
@@ -22,6 +26,12 @@ void main() {
 
     vec3 ray_origin = camera_position;
     vec3 ray_dir = normalize(v_world_pos - camera_position);
+    // Orthographic views (such as the light view of the shadow pass) cast parallel rays along the
+    // view direction, starting from the billboard.
+    if (parallel_rays) {
+        ray_origin = v_world_pos;
+        ray_dir = -vec3(view[0][2], view[1][2], view[2][2]);
+    }
     vec3 sphere_center = v_center;
 
     // Ray-sphere intersection
@@ -48,7 +58,50 @@ void main() {
     float t = (-b - sqrt(discriminant)) / (2.0 * a);
     vec3 intersection = ray_origin + t * ray_dir;
 
+    // The clipping planes cut the imposter open, its inside is seen through the cut instead.
+    // When the slab is thinner than the imposter, the ray crosses both planes inside it and the
+    // cut is capped on the near plane.
+    bool is_inside = false;
+    bool is_cap = false;
+    if (is_clipped(intersection)) {
+        vec3 front = intersection;
+        t = (-b + sqrt(discriminant)) / (2.0 * a);
+        intersection = ray_origin + t * ray_dir;
+        if (is_clipped(intersection)) {
+            float front_depth = view_depth(front);
+            float back_depth = view_depth(intersection);
+            if (front_depth >= clip_near || back_depth <= clip_far) {
+                discard;
+            }
+            intersection = mix(front, intersection, (clip_near - front_depth) / (back_depth - front_depth));
+            is_cap = true;
+        } else {
+            is_inside = true;
+        }
+    }
+
+    // Depth calculation
+    vec4 clip_space = projection * view * vec4(intersection, 1.0);
+    float ndc_depth = clip_space.z / clip_space.w;
+    float window_depth = (ndc_depth * 0.5) + 0.5; // Map from [-1,1] to [0,1]
+
+    // Apply a small depth bias to prevent z-fighting
+    float depth_bias = 0.0001;
+    gl_FragDepth = window_depth + depth_bias;
+
+    // The shadow pass only needs the depth.
+    if (depth_only) {
+        return;
+    }
+
     vec3 normal = normalize(intersection - sphere_center);
+    if (is_inside) {
+        normal = -normal;
+    }
+    // The cap faces the camera.
+    if (is_cap) {
+        normal = vec3(view[0][2], view[1][2], view[2][2]);
+    }
 
     vec3 light_dir = normalize(light_position - intersection);
     float distance_to_light = distance(light_position, intersection);
@@ -56,12 +109,13 @@ void main() {
     // Attenuation based on distance
     float attenuation = 1.0 / (1.0 + 0.09 * distance_to_light + 0.032 * distance_to_light * distance_to_light);
 
-    float diffuse = max(dot(normal, light_dir), 0.0) * attenuation;
+    float visibility = light_visibility(intersection, normal);
+    float diffuse = max(dot(normal, light_dir), 0.0) * attenuation * visibility;
 
-    vec3 view_dir = normalize(camera_position - intersection);
+    vec3 view_dir = -ray_dir;
     vec3 reflect_dir = reflect(-light_dir, normal);
     float shininess = 16.0; // Lower for broader highlights
-    float specular = pow(max(dot(view_dir, reflect_dir), 0.0), shininess) * attenuation;
+    float specular = pow(max(dot(view_dir, reflect_dir), 0.0), shininess) * attenuation * visibility;
 
     vec3 ambient_light = vec3(0.3, 0.3, 0.4); // Slightly bluish ambient light
     vec3 ambient = ambient_light * 0.7;
@@ -78,14 +132,8 @@ void main() {
         final_color = mix(SILHOUETTE_COLOR, final_color, silhouette_factor);
     }
 
-    // Depth calculation
-    vec4 clip_space = projection * view * vec4(intersection, 1.0);
-    float ndc_depth = clip_space.z / clip_space.w;
-    float window_depth = (ndc_depth * 0.5) + 0.5; // Map from [-1,1] to [0,1]
-
-    // Apply a small depth bias to prevent z-fighting
-    float depth_bias = 0.0001;
-    gl_FragDepth = window_depth + depth_bias;
+    final_color = apply_fog(final_color, intersection);
 
     frag_color = vec4(final_color, v_color.a);
+    frag_normal = vec4(normalize(mat3(view) * normal) * 0.5 + 0.5, v_color.a);
 }
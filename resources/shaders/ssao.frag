@@ -0,0 +1,74 @@
+#version 410 core
+
+in vec2 v_uv_coordinates;
+
+out vec4 frag_color;
+
+uniform sampler2D depth_texture;
+uniform sampler2D normal_texture;
+uniform mat4 projection;
+uniform mat4 inverse_projection;
+uniform float radius;
+uniform int samples;
+
+const float GOLDEN_ANGLE = 2.39996323;
+const float TAU = 6.28318531;
+
+float hash(vec2 seed) {
+    return fract(sin(dot(seed, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// View space position of the geometry at the given texture coordinates.
+vec3 view_position(vec2 uv) {
+    float depth = texture(depth_texture, uv).r;
+    vec4 position = inverse_projection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    // Nothing to occlude on the background.
+    if (texture(depth_texture, v_uv_coordinates).r >= 1.0) {
+        frag_color = vec4(1.0);
+        return;
+    }
+
+    vec3 position = view_position(v_uv_coordinates);
+    vec3 normal = normalize(texture(normal_texture, v_uv_coordinates).xyz * 2.0 - 1.0);
+
+    // The hemisphere around the normal is rotated by a random angle for each pixel, trading the
+    // banding of a fixed kernel for noise, which is blurred by the composite pass.
+    float angle = hash(gl_FragCoord.xy) * TAU;
+    vec3 random = vec3(cos(angle), sin(angle), 0.0);
+    vec3 tangent = random - normal * dot(random, normal);
+    if (length(tangent) < 1e-3) {
+        tangent = vec3(0.0, 0.0, 1.0) - normal * normal.z;
+    }
+    tangent = normalize(tangent);
+    mat3 tangent_space = mat3(tangent, cross(normal, tangent), normal);
+
+    float bias = 0.02 * radius;
+    float occlusion = 0.0;
+    for (int i = 0; i < samples; i++) {
+        // Fibonacci spiral over the hemisphere, the samples getting denser close to the point.
+        float elevation = 1.0 - (float(i) + 0.5) / float(samples);
+        float spread = sqrt(1.0 - elevation * elevation);
+        float azimuth = float(i) * GOLDEN_ANGLE;
+        vec3 direction = vec3(spread * cos(azimuth), spread * sin(azimuth), elevation);
+        float scale = mix(0.1, 1.0, pow(hash(gl_FragCoord.xy + float(i)), 2.0));
+        vec3 sample_position = position + tangent_space * direction * radius * scale;
+
+        vec4 clip_space = projection * vec4(sample_position, 1.0);
+        vec2 sample_uv = clip_space.xy / clip_space.w * 0.5 + 0.5;
+        if (any(lessThan(sample_uv, vec2(0.0))) || any(greaterThan(sample_uv, vec2(1.0)))) {
+            continue;
+        }
+
+        // The sample is occluded when the geometry seen there is in front of it, geometry far
+        // in front (another part of the molecule) doesn't count.
+        float geometry_depth = view_position(sample_uv).z;
+        float range = smoothstep(0.0, 1.0, radius / abs(position.z - geometry_depth));
+        occlusion += (geometry_depth >= sample_position.z + bias ? 1.0 : 0.0) * range;
+    }
+
+    frag_color = vec4(vec3(1.0 - occlusion / float(samples)), 1.0);
+}
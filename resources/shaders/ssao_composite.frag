@@ -0,0 +1,24 @@
+#version 410 core
+
+in vec2 v_uv_coordinates;
+
+out vec4 frag_color;
+
+uniform sampler2D color_texture;
+uniform sampler2D occlusion_texture;
+uniform float strength;
+
+void main() {
+    // 4x4 box blur, removing the noise of the randomly rotated samples.
+    vec2 texel = 1.0 / vec2(textureSize(occlusion_texture, 0));
+    float occlusion = 0.0;
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            occlusion += texture(occlusion_texture, v_uv_coordinates + (vec2(x, y) + 0.5) * texel).r;
+        }
+    }
+    occlusion /= 16.0;
+
+    vec4 color = texture(color_texture, v_uv_coordinates);
+    frag_color = vec4(color.rgb * mix(1.0, occlusion, strength), color.a);
+}
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+/// Playback state of a sequence of frames (models of an ensemble, trajectory frames).
+/// This struct only keeps track of the current frame, it is up to the caller to display it when
+/// `update` or one of the controls report a frame change.
+pub struct Playback {
+    frame_count: usize,
+    current: usize,
+    playing: bool,
+    looping: bool,
+    frame_duration: Duration,
+    last_advance: Instant,
+}
+
+impl Playback {
+    /// Create a playback over `frame_count` frames, advancing at `frame_rate` frames per second.
+    /// The playback starts as soon as there is more than one frame.
+    pub fn new(frame_count: usize, frame_rate: f32) -> Self {
+        Self {
+            frame_count,
+            current: 0,
+            playing: frame_count > 1,
+            looping: true,
+            frame_duration: Duration::from_secs_f32(1.0 / frame_rate.max(f32::EPSILON)),
+            last_advance: Instant::now(),
+        }
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.current
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn toggle_play(&mut self) {
+        if self.frame_count <= 1 {
+            return;
+        }
+        // Restart from the beginning when playing again a finished, non looping, playback.
+        if !self.playing && !self.looping && self.current + 1 == self.frame_count {
+            self.current = 0;
+        }
+        self.playing = !self.playing;
+        self.last_advance = Instant::now();
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn toggle_loop(&mut self) {
+        self.looping = !self.looping;
+    }
+
+    /// Move `delta` frames forward (or backward when negative) and pause the playback.
+    /// Return `true` if the current frame has changed.
+    pub fn step(&mut self, delta: isize) -> bool {
+        self.playing = false;
+        self.advance(delta)
+    }
+
+    /// Advance the playback according to the elapsed time, should be called once per frame.
+    /// Return `true` if the current frame has changed.
+    pub fn update(&mut self) -> bool {
+        if !self.playing || self.last_advance.elapsed() < self.frame_duration {
+            return false;
+        }
+        self.last_advance = Instant::now();
+
+        if !self.looping && self.current + 1 >= self.frame_count {
+            self.playing = false;
+            return false;
+        }
+        self.advance(1)
+    }
+
+    fn advance(&mut self, delta: isize) -> bool {
+        if self.frame_count == 0 {
+            return false;
+        }
+        let count = self.frame_count as isize;
+        let target = self.current as isize + delta;
+        let next = if self.looping {
+            target.rem_euclid(count)
+        } else {
+            target.clamp(0, count - 1)
+        } as usize;
+
+        let changed = next != self.current;
+        self.current = next;
+        changed
+    }
+}
@@ -1,4 +1,23 @@
-use clap::Parser;
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::{ArgGroup, Parser};
+
+use crate::{
+    bonding::{BondOrderSource, BondSource},
+    clipping::FogMode,
+    coloring::AtomColoring,
+    hydrogen_bonds::HydrogenBondDisplay,
+    io::{
+        mirror::{PdbId, MIRROR_ENV},
+        trajectory::FrameRange,
+        RecordSelector,
+    },
+    molecule::ModelDisplay,
+    representation::RepresentationAssignment,
+    secondary_structure::SecondaryStructureSource,
+    selection::Selection,
+    surface::SurfaceKind,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -13,10 +32,187 @@ use clap::Parser;
     {usage-heading} {usage}
     {all-args}{after-help}"
 )]
+#[command(group(ArgGroup::new("structure").required(true).args(["file", "id"])))]
 pub struct Args {
+    /// Path of the structure file (PDB, mmCIF/PDBx, XYZ, SDF/MOL or MOL2, optionally gzip
+    /// compressed).
     #[arg(short, long)]
-    pub file: String,
+    pub file: Option<String>,
+
+    /// PDB ID of an entry to load from the local PDB mirror, the mmCIF file is preferred over the
+    /// PDB one.
+    #[arg(long)]
+    pub id: Option<PdbId>,
+
+    /// Root of a local PDB mirror using the divided layout (`xy/1xyz.cif.gz`,
+    /// `xy/pdb1xyz.ent.gz`).
+    #[arg(long, env = MIRROR_ENV)]
+    pub mirror: Option<PathBuf>,
+
+    /// Record to display from a multi-record file (SDF), either its index starting at 1 or its
+    /// name. The first record is displayed by default.
+    #[arg(long)]
+    pub record: Option<RecordSelector>,
 
     #[arg(long, default_value = "false")]
     pub fps: bool,
+
+    /// How the models of a multi-model file are displayed.
+    #[arg(long, value_enum, default_value_t = ModelDisplay::Animate)]
+    pub models: ModelDisplay,
+
+    /// DCD or XTC trajectory played on top of the structure file, which is used as topology. Its
+    /// atoms must match the atoms of the first model of the structure.
+    #[arg(short, long)]
+    pub trajectory: Option<String>,
+
+    /// Only play one trajectory frame out of `stride`.
+    #[arg(long, default_value = "1")]
+    pub stride: NonZeroUsize,
+
+    /// Range of trajectory frames to play, `start:end` with `end` excluded, both bounds being
+    /// optional (`100:`, `:500`, `100:500`).
+    #[arg(long)]
+    pub frames: Option<FrameRange>,
+
+    /// Number of animation frames displayed per second.
+    #[arg(long, default_value_t = 10.0)]
+    pub frame_rate: f32,
+
+    /// Property driving the atoms color, press `K` to cycle through the colorings at runtime.
+    #[arg(long, value_enum, default_value_t = AtomColoring::Element)]
+    pub color_by: AtomColoring,
+
+    /// Where the bonds are taken from: `CONECT` records, distance based perception, standard
+    /// residue templates, or the records merged with the templates.
+    #[arg(long, value_enum, default_value_t = BondSource::Merged)]
+    pub bonds: BondSource,
+
+    /// Tolerance (in Å) added to the sum of the covalent radii when inferring bonds.
+    #[arg(long, default_value_t = 0.45)]
+    pub bond_tolerance: f32,
+
+    /// Where the bond orders are taken from: the file, perceived from the geometry when the file
+    /// has none (auto), always perceived, or ignored (every bond drawn as single).
+    #[arg(long, value_enum, default_value_t = BondOrderSource::Auto)]
+    pub bond_orders: BondOrderSource,
+
+    /// Hydrogen bonds displayed at startup: none, all of them, or only the ones involving the
+    /// `--hbond-selection` atoms. Press `H` to cycle through the modes.
+    #[arg(long, value_enum, default_value_t = HydrogenBondDisplay::Off)]
+    pub hbonds: HydrogenBondDisplay,
+
+    /// Maximum donor to acceptor distance (in Å) of a hydrogen bond.
+    #[arg(long, default_value_t = 3.5)]
+    pub hbond_distance: f32,
+
+    /// Minimum donor, hydrogen, acceptor angle (in degrees) of a hydrogen bond.
+    #[arg(long, default_value_t = 120.0)]
+    pub hbond_angle: f32,
+
+    /// Atoms of the hydrogen bond selection, a selection expression such as `ligand` (residues
+    /// which are neither standard residues nor water) or `chain A and resname HIS`, see
+    /// `Selection`.
+    #[arg(long, default_value = "ligand")]
+    pub hbond_selection: Selection,
+
+    /// Representation of a selection, written `representation[:selection]` (`licorice:ligand`),
+    /// the selection being every atom when omitted. Can be repeated, later assignments override
+    /// the previous ones, unassigned atoms use ball-and-stick. Press `R` to cycle the
+    /// representation of the last assignment at runtime.
+    #[arg(long = "representation", value_name = "REPRESENTATION")]
+    pub representations: Vec<RepresentationAssignment>,
+
+    /// Draw the cartoon of the proteins and nucleic acids along with the atoms and bonds. Press
+    /// `C` to toggle it at runtime.
+    #[arg(long, default_value = "false")]
+    pub cartoon: bool,
+
+    /// Molecular surface drawn at startup: solvent accessible (sas) or solvent excluded (ses).
+    /// Press `S` to cycle through the surfaces at runtime.
+    #[arg(long, value_enum)]
+    pub surface: Option<SurfaceKind>,
+
+    /// Radius (in Å) of the solvent probe the surfaces are built with, 1.4 Å being water.
+    #[arg(long, default_value_t = 1.4)]
+    pub probe_radius: f32,
+
+    /// Spacing (in Å) of the grid the surfaces are extracted from, smaller is finer but slower.
+    #[arg(long, default_value_t = 0.5)]
+    pub surface_resolution: f32,
+
+    /// Opacity of the surfaces, from 0 (invisible) to 1 (opaque).
+    #[arg(long, default_value_t = 1.0)]
+    pub surface_opacity: f32,
+
+    /// Atoms wrapped by the surfaces, a selection expression, see `--hbond-selection`.
+    #[arg(long, default_value = "not water")]
+    pub surface_selection: Selection,
+
+    /// Use an orthographic projection rather than a perspective one, the size of the atoms on
+    /// screen not depending on their depth. Press `P` to toggle it at runtime.
+    #[arg(long, default_value = "false")]
+    pub orthographic: bool,
+
+    /// Darken the creases and pockets with screen space ambient occlusion. Press `O` to toggle it
+    /// at runtime.
+    #[arg(long, default_value = "false")]
+    pub ssao: bool,
+
+    /// Strength of the ambient occlusion, from 0 (none) to 1 (fully occluded pixels are black).
+    #[arg(long, default_value_t = 0.8)]
+    pub ssao_strength: f32,
+
+    /// Radius (in Å) of the neighbourhood occluding each point.
+    #[arg(long, default_value_t = 2.0)]
+    pub ssao_radius: f32,
+
+    /// Number of samples per pixel of the ambient occlusion, more is smoother but slower.
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..=256))]
+    pub ssao_samples: u32,
+
+    /// Cast the shadows of a directional light coming from the light position. Press `D` to
+    /// toggle them at runtime.
+    #[arg(long, default_value = "false")]
+    pub shadows: bool,
+
+    /// Width and height (in texels) of the shadow map, larger maps give sharper shadows.
+    #[arg(long, default_value_t = 2048, value_parser = clap::value_parser!(u32).range(64..=8192))]
+    pub shadow_resolution: u32,
+
+    /// Distance (in Å) of the near clipping plane from the molecule center, negative towards the
+    /// camera. Hold `Ctrl` while scrolling to move it at runtime.
+    #[arg(long, allow_hyphen_values = true)]
+    pub clip_near: Option<f32>,
+
+    /// Distance (in Å) of the far clipping plane from the molecule center, negative towards the
+    /// camera. Hold `Alt` while scrolling to move it at runtime.
+    #[arg(long, allow_hyphen_values = true)]
+    pub clip_far: Option<f32>,
+
+    /// Fog blending the atoms toward the background with their depth between the clipping planes.
+    /// Press `F` to cycle through the modes at runtime.
+    #[arg(long, value_enum, default_value_t = FogMode::Off)]
+    pub fog: FogMode,
+
+    /// Density of the fog, the fraction of the background color blended at the far plane for the
+    /// linear fog.
+    #[arg(long, default_value_t = 0.8)]
+    pub fog_density: f32,
+
+    /// Where the secondary structure is taken from: the `HELIX` and `SHEET` records, assigned
+    /// from the backbone hydrogen bonds (DSSP) when the file has none (auto), only the records, or
+    /// always DSSP.
+    #[arg(long, value_enum, default_value_t = SecondaryStructureSource::Auto)]
+    pub secondary_structure: SecondaryStructureSource,
+
+    /// Write the secondary structure of every amino acid to this file at startup, as tab
+    /// separated text (`-` for the standard output).
+    #[arg(long)]
+    pub export_secondary_structure: Option<PathBuf>,
+
+    /// Write the measurements as CSV to this file when pressing `E`, to the standard output when
+    /// not given.
+    #[arg(long)]
+    pub export_measurements: Option<PathBuf>,
 }
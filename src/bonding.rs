@@ -0,0 +1,548 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+
+use clap::ValueEnum;
+use nalgebra::{Point3, Vector3};
+use pdbtbx::Element;
+
+use crate::spatial_grid::SpatialGrid;
+
+/// Covalent radius used for atoms without a known element, roughly the one of a carbon.
+const FALLBACK_COVALENT_RADIUS: f32 = 0.75;
+
+/// Two atoms closer than this distance (in Å) are considered overlapping (alternate locations,
+/// duplicated atoms) rather than bonded.
+const MIN_BOND_DISTANCE: f32 = 0.4;
+
+/// Maximum distance (in Å) of an atom to the plane of its 3 neighbours for it to be considered
+/// trigonal planar (sp2).
+const PLANARITY_TOLERANCE: f32 = 0.2;
+
+/// Minimum angle (in degrees) between the 2 neighbours of an atom for it to be considered linear
+/// (sp).
+const LINEAR_ANGLE: f32 = 160.0;
+
+/// Maximum distance (in Å) of the atoms of an aromatic ring to the mean plane of the ring.
+const RING_PLANARITY_TOLERANCE: f32 = 0.25;
+
+/// Where the bonds of the molecule are coming from.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondSource {
+    /// Only use the bonds explicitly written in the file (`CONECT`, `SSBOND` and `LINK` records).
+    Conect,
+    /// Only use the bonds inferred from the atoms distances.
+    Inferred,
+    /// Use the standard residue templates, and the atoms distances for the other residues.
+    Templates,
+    /// Use the explicit bonds and the templates, explicit bonds take precedence. SDF and MOL2
+    /// files list every bond and only use their explicit bonds.
+    Merged,
+}
+
+/// Where the bond orders are coming from.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondOrderSource {
+    /// Orders written in the file (SDF, MOL2, duplicated `CONECT` entries), or perceived from the
+    /// geometry when the file has none.
+    Auto,
+    /// Always perceive the orders from the geometry.
+    Perceive,
+    /// Draw every bond as a single bond.
+    Ignore,
+}
+
+/// Order of a covalent bond, as written in the formats carrying it (SDF, MOL2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BondOrder {
+    #[default]
+    Single,
+    Double,
+    Triple,
+    Aromatic,
+}
+
+/// A bond between two atoms, referenced by their index in the molecule atom list.
+/// The indices are stored in increasing order so that the same bond always compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bond {
+    pub first: usize,
+    pub second: usize,
+    pub order: BondOrder,
+}
+
+impl BondOrder {
+    /// Order of a bond written `multiplicity` times in the `CONECT` records.
+    pub fn from_multiplicity(multiplicity: usize) -> Self {
+        match multiplicity {
+            0 | 1 => Self::Single,
+            2 => Self::Double,
+            _ => Self::Triple,
+        }
+    }
+}
+
+impl Bond {
+    pub fn new(a: usize, b: usize) -> Self {
+        Self::with_order(a, b, BondOrder::Single)
+    }
+
+    pub fn with_order(a: usize, b: usize, order: BondOrder) -> Self {
+        Self {
+            first: a.min(b),
+            second: a.max(b),
+            order,
+        }
+    }
+}
+
+/// Single bond covalent radius of an element in Å.
+pub fn covalent_radius(element: Option<&Element>) -> f32 {
+    element.map_or(FALLBACK_COVALENT_RADIUS, |element| {
+        element.atomic_radius().covalent_single as f32
+    })
+}
+
+/// Infer the bonds of a set of atoms from their coordinates.
+/// Two atoms are bonded when their distance is lower than the sum of their covalent radii plus
+/// `tolerance` (in Å). Candidates are found through a `SpatialGrid` so the perception scale
+/// linearly with the atom count.
+pub fn perceive_bonds(
+    positions: &[Point3<f32>],
+    elements: &[Option<&Element>],
+    tolerance: f32,
+) -> Vec<Bond> {
+    let radii = elements
+        .iter()
+        .map(|element| covalent_radius(*element))
+        .collect::<Vec<_>>();
+    let max_radius = radii.iter().copied().fold(0.0, f32::max);
+    let max_bond_length = 2.0 * max_radius + tolerance;
+
+    let grid = SpatialGrid::new(positions, max_bond_length);
+    let mut bonds = Vec::new();
+
+    for (index, position) in positions.iter().enumerate() {
+        for neighbour in grid.within(position, max_bond_length) {
+            if neighbour <= index {
+                continue;
+            }
+            let distance = (positions[neighbour] - position).norm();
+            if distance > MIN_BOND_DISTANCE
+                && distance <= radii[index] + radii[neighbour] + tolerance
+            {
+                bonds.push(Bond::new(index, neighbour));
+            }
+        }
+    }
+    bonds
+}
+
+/// Merge the explicit bonds with the inferred ones, dropping the duplicates.
+pub fn merge_bonds(explicit: &[Bond], inferred: &[Bond]) -> Vec<Bond> {
+    let mut seen = HashSet::new();
+    explicit
+        .iter()
+        .chain(inferred)
+        .filter(|bond| seen.insert((bond.first, bond.second)))
+        .copied()
+        .collect()
+}
+
+/// Neighbours of every atom, `atom_count` being the size of the atom list the bonds refer to.
+pub fn neighbours(atom_count: usize, bonds: &[Bond]) -> Vec<Vec<(usize, BondOrder)>> {
+    let mut neighbours = vec![vec![]; atom_count];
+    for bond in bonds {
+        neighbours[bond.first].push((bond.second, bond.order));
+        neighbours[bond.second].push((bond.first, bond.order));
+    }
+    neighbours
+}
+
+/// Hybridization of an atom, deduced from the geometry of its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hybridization {
+    Sp,
+    Sp2,
+    Sp3,
+}
+
+/// Perceive the bond orders from the geometry, the given orders are discarded.
+/// This is a heuristic in the spirit of what molecular viewers do for files without orders:
+/// - the hybridization of each atom is deduced from the angles and planarity of its neighbours,
+/// - terminal atoms (carbonyl oxygens, nitriles) get a multiple bond when their bond is short,
+/// - planar 5 and 6 atoms rings made of sp2 atoms without exocyclic double bond are aromatic,
+/// - the remaining multiple bonds are assigned from the shortest to the longest, comparing the
+///   bond length to the sum of the single, double and triple covalent radii and never exceeding
+///   the valence of the atoms.
+pub fn perceive_bond_orders(
+    positions: &[Point3<f32>],
+    elements: &[Option<&Element>],
+    bonds: &[Bond],
+) -> Vec<Bond> {
+    let mut bonds = bonds
+        .iter()
+        .map(|bond| Bond::new(bond.first, bond.second))
+        .collect::<Vec<_>>();
+    let neighbours = neighbours(positions.len(), &bonds)
+        .into_iter()
+        .map(|neighbours| neighbours.into_iter().map(|(atom, _)| atom).collect())
+        .collect::<Vec<Vec<_>>>();
+    let hybridizations = (0..positions.len())
+        .map(|atom| hybridization(atom, positions, &neighbours[atom]))
+        .collect::<Vec<_>>();
+    let mut free_valences = (0..positions.len())
+        .map(|atom| max_valence(elements[atom]).saturating_sub(neighbours[atom].len()))
+        .collect::<Vec<_>>();
+
+    let can_be_unsaturated = |atom: usize| match elements[atom] {
+        Some(Element::C | Element::N | Element::B) => hybridizations[atom] != Hybridization::Sp3,
+        Some(Element::O) => neighbours[atom].len() == 1,
+        Some(Element::S | Element::P | Element::Se) => true,
+        _ => false,
+    };
+
+    // Multiple bond candidates, with how much shorter than a single bond they are.
+    let mut candidates = bonds
+        .iter()
+        .enumerate()
+        .filter(|(_, bond)| can_be_unsaturated(bond.first) && can_be_unsaturated(bond.second))
+        .filter_map(|(index, bond)| {
+            let distance = (positions[bond.second] - positions[bond.first]).norm();
+            let (order, shortening) =
+                length_order(elements[bond.first], elements[bond.second], distance)?;
+            let is_terminal =
+                neighbours[bond.first].len() == 1 || neighbours[bond.second].len() == 1;
+            Some((index, order, shortening, is_terminal))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let assign = |bonds: &mut [Bond], free_valences: &mut [usize], index: usize, order| {
+        let bond = bonds[index];
+        let needed = match order {
+            BondOrder::Triple => 2,
+            _ => 1,
+        };
+        let available = free_valences[bond.first].min(free_valences[bond.second]);
+        let order = match (order, available) {
+            (_, 0) => return,
+            (BondOrder::Triple, 1) => BondOrder::Double,
+            _ => order,
+        };
+        let used = needed.min(available);
+        free_valences[bond.first] -= used;
+        free_valences[bond.second] -= used;
+        bonds[index].order = order;
+    };
+
+    // Terminal atoms first, they carry the least ambiguous multiple bonds (C=O, C≡N).
+    for &(index, order, _, _) in candidates.iter().filter(|candidate| candidate.3) {
+        assign(&mut bonds, &mut free_valences, index, order);
+    }
+
+    let bond_indices = bonds
+        .iter()
+        .enumerate()
+        .map(|(index, bond)| ((bond.first, bond.second), index))
+        .collect::<HashMap<_, _>>();
+    let bond_index = |a: usize, b: usize| bond_indices[&(a.min(b), a.max(b))];
+    // Aromatic bonds do not count, fused rings share them.
+    let has_double_bond = |bonds: &[Bond], atom: usize| {
+        neighbours[atom].iter().any(|&neighbour| {
+            matches!(
+                bonds[bond_index(atom, neighbour)].order,
+                BondOrder::Double | BondOrder::Triple
+            )
+        })
+    };
+
+    for ring in find_rings(&neighbours, elements) {
+        let is_aromatic = ring.iter().all(|&atom| {
+            let ring_element = matches!(
+                elements[atom],
+                Some(Element::C | Element::N | Element::O | Element::S)
+            );
+            let heteroatom_allowed =
+                ring.len() == 5 || !matches!(elements[atom], Some(Element::O | Element::S));
+            ring_element
+                && heteroatom_allowed
+                && hybridizations[atom] == Hybridization::Sp2
+                && !has_double_bond(&bonds, atom)
+        }) && is_planar(&ring, positions);
+
+        if is_aromatic {
+            for (position, &atom) in ring.iter().enumerate() {
+                let next = ring[(position + 1) % ring.len()];
+                bonds[bond_index(atom, next)].order = BondOrder::Aromatic;
+            }
+            for &atom in &ring {
+                free_valences[atom] = 0;
+            }
+        }
+    }
+
+    for &(index, order, _, _) in candidates.iter().filter(|candidate| !candidate.3) {
+        if bonds[index].order == BondOrder::Single {
+            assign(&mut bonds, &mut free_valences, index, order);
+        }
+    }
+    bonds
+}
+
+/// Usual maximum number of bonds (counting the multiple bonds) of an element, 0 for the elements
+/// never involved in multiple bonds.
+fn max_valence(element: Option<&Element>) -> usize {
+    match element {
+        Some(Element::C) => 4,
+        Some(Element::N | Element::B) => 3,
+        Some(Element::O | Element::Se) => 2,
+        Some(Element::P) => 5,
+        Some(Element::S) => 6,
+        _ => 0,
+    }
+}
+
+fn hybridization(atom: usize, positions: &[Point3<f32>], neighbours: &[usize]) -> Hybridization {
+    let center = positions[atom];
+    match neighbours {
+        // Terminal atoms are decided from their bond length only.
+        [_] => Hybridization::Sp2,
+        [a, b] => {
+            let angle = (positions[*a] - center)
+                .angle(&(positions[*b] - center))
+                .to_degrees();
+            if angle > LINEAR_ANGLE {
+                Hybridization::Sp
+            } else {
+                Hybridization::Sp2
+            }
+        }
+        [a, b, c] => {
+            let normal = (positions[*b] - positions[*a]).cross(&(positions[*c] - positions[*a]));
+            let distance = (center - positions[*a]).dot(&normal.normalize()).abs();
+            if normal.norm() > f32::EPSILON && distance < PLANARITY_TOLERANCE {
+                Hybridization::Sp2
+            } else {
+                Hybridization::Sp3
+            }
+        }
+        _ => Hybridization::Sp3,
+    }
+}
+
+/// Order whose covalent radii sum is the closest to the bond length, with how much shorter than a
+/// single bond the bond is. `None` when the closest order is a single bond.
+fn length_order(
+    first: Option<&Element>,
+    second: Option<&Element>,
+    distance: f32,
+) -> Option<(BondOrder, f32)> {
+    let radii = |element: Option<&Element>| element.map(|element| element.atomic_radius());
+    let (first, second) = (radii(first)?, radii(second)?);
+
+    let single = (first.covalent_single + second.covalent_single) as f32;
+    let double = first
+        .covalent_double
+        .zip(second.covalent_double)
+        .map(|(a, b)| (a + b) as f32);
+    let triple = first
+        .covalent_triple
+        .zip(second.covalent_triple)
+        .map(|(a, b)| (a + b) as f32);
+
+    let order = [
+        (BondOrder::Single, Some(single)),
+        (BondOrder::Double, double),
+        (BondOrder::Triple, triple),
+    ]
+    .into_iter()
+    .filter_map(|(order, length)| Some((order, (length? - distance).abs())))
+    .min_by(|a, b| a.1.total_cmp(&b.1))?
+    .0;
+
+    (order != BondOrder::Single).then_some((order, single - distance))
+}
+
+/// Find the smallest ring (of at most 6 atoms) going through each bond between heavy atoms.
+/// The rings are returned as ordered atom cycles, without duplicates.
+fn find_rings(neighbours: &[Vec<usize>], elements: &[Option<&Element>]) -> Vec<Vec<usize>> {
+    const MAX_RING_SIZE: usize = 6;
+
+    let is_heavy = |atom: usize| !matches!(elements[atom], Some(Element::H) | None);
+    let mut seen = HashSet::new();
+    let mut rings = vec![];
+
+    for (start, start_neighbours) in neighbours.iter().enumerate() {
+        for &end in start_neighbours {
+            if end <= start || !is_heavy(start) || !is_heavy(end) {
+                continue;
+            }
+
+            // Shortest path from `start` to `end` which does not use the bond itself.
+            let mut parents = HashMap::from([(start, start)]);
+            let mut queue = VecDeque::from([(start, 1)]);
+            while let Some((atom, length)) = queue.pop_front() {
+                if atom == end || length >= MAX_RING_SIZE {
+                    continue;
+                }
+                for &next in &neighbours[atom] {
+                    if (atom == start && next == end) || !is_heavy(next) {
+                        continue;
+                    }
+                    if let Entry::Vacant(entry) = parents.entry(next) {
+                        entry.insert(atom);
+                        queue.push_back((next, length + 1));
+                    }
+                }
+            }
+
+            if !parents.contains_key(&end) {
+                continue;
+            }
+            let mut ring = vec![end];
+            let mut atom = end;
+            while atom != start {
+                atom = parents[&atom];
+                ring.push(atom);
+            }
+
+            let mut key = ring.clone();
+            key.sort_unstable();
+            if ring.len() >= 5 && seen.insert(key) {
+                rings.push(ring);
+            }
+        }
+    }
+    rings
+}
+
+/// Whether every atom of the ring is close to the mean plane of the ring.
+fn is_planar(ring: &[usize], positions: &[Point3<f32>]) -> bool {
+    let center = ring
+        .iter()
+        .fold(Vector3::zeros(), |sum, &atom| sum + positions[atom].coords)
+        / ring.len() as f32;
+    let normal = (0..ring.len())
+        .map(|index| {
+            let a = positions[ring[index]].coords - center;
+            let b = positions[ring[(index + 1) % ring.len()]].coords - center;
+            a.cross(&b)
+        })
+        .sum::<Vector3<f32>>();
+    if normal.norm() <= f32::EPSILON {
+        return false;
+    }
+    let normal = normal.normalize();
+
+    ring.iter().all(|&atom| {
+        (positions[atom].coords - center).dot(&normal).abs() < RING_PLANARITY_TOLERANCE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orders(positions: &[Point3<f32>], elements: &[Element], bonds: &[Bond]) -> Vec<BondOrder> {
+        let elements = elements.iter().map(Some).collect::<Vec<_>>();
+        perceive_bond_orders(positions, &elements, bonds)
+            .into_iter()
+            .map(|bond| bond.order)
+            .collect()
+    }
+
+    #[test]
+    fn perceive_bonds_from_distances() {
+        let positions = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.74, 0.0, 0.0),
+            // Overlapping the first atom.
+            Point3::new(0.0, 0.2, 0.0),
+            Point3::new(5.0, 0.0, 0.0),
+        ];
+        let elements = [Some(&Element::H); 4];
+        assert_eq!(
+            perceive_bonds(&positions, &elements, 0.45),
+            [Bond::new(0, 1), Bond::new(1, 2)]
+        );
+        assert!(perceive_bonds(&positions[..1], &elements[..1], 0.45).is_empty());
+    }
+
+    #[test]
+    fn merge_explicit_first() {
+        let explicit = [Bond::with_order(0, 1, BondOrder::Double)];
+        let inferred = [Bond::new(1, 0), Bond::new(1, 2)];
+        assert_eq!(
+            merge_bonds(&explicit, &inferred),
+            [Bond::with_order(0, 1, BondOrder::Double), Bond::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn multiplicity() {
+        assert_eq!(BondOrder::from_multiplicity(1), BondOrder::Single);
+        assert_eq!(BondOrder::from_multiplicity(2), BondOrder::Double);
+        assert_eq!(BondOrder::from_multiplicity(3), BondOrder::Triple);
+        assert_eq!(BondOrder::from_multiplicity(4), BondOrder::Triple);
+    }
+
+    #[test]
+    fn terminal_multiple_bonds() {
+        // Formaldehyde, the carbonyl is a double bond.
+        let positions = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.21, 0.0, 0.0),
+            Point3::new(-0.55, 0.94, 0.0),
+            Point3::new(-0.55, -0.94, 0.0),
+        ];
+        let bonds = [Bond::new(0, 1), Bond::new(0, 2), Bond::new(0, 3)];
+        assert_eq!(
+            orders(
+                &positions,
+                &[Element::C, Element::O, Element::H, Element::H],
+                &bonds
+            ),
+            [BondOrder::Double, BondOrder::Single, BondOrder::Single]
+        );
+
+        // Hydrogen cyanide, the nitrile is a triple bond.
+        let positions = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.16, 0.0, 0.0),
+            Point3::new(-1.07, 0.0, 0.0),
+        ];
+        let bonds = [Bond::new(0, 1), Bond::new(0, 2)];
+        assert_eq!(
+            orders(&positions, &[Element::C, Element::N, Element::H], &bonds),
+            [BondOrder::Triple, BondOrder::Single]
+        );
+    }
+
+    #[test]
+    fn saturated_bonds() {
+        // Ethane, sp3 carbons never get a multiple bond, however short their bond.
+        let positions = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.35, 0.0, 0.0),
+            Point3::new(-0.36, 1.03, 0.0),
+            Point3::new(-0.36, -0.51, 0.89),
+            Point3::new(-0.36, -0.51, -0.89),
+            Point3::new(1.71, -1.03, 0.0),
+            Point3::new(1.71, 0.51, 0.89),
+            Point3::new(1.71, 0.51, -0.89),
+        ];
+        let bonds = [
+            Bond::new(0, 1),
+            Bond::new(0, 2),
+            Bond::new(0, 3),
+            Bond::new(0, 4),
+            Bond::new(1, 5),
+            Bond::new(1, 6),
+            Bond::new(1, 7),
+        ];
+        let elements = [[Element::C; 2].as_slice(), &[Element::H; 6]].concat();
+        assert_eq!(
+            orders(&positions, &elements, &bonds),
+            [BondOrder::Single; 7]
+        );
+    }
+}
@@ -6,6 +6,10 @@ pub trait Camera {
     fn zoom(&mut self, zoom_amount: f32);
     fn get_view_matrix(&self) -> Matrix4<f32>;
     fn get_projection_matrix(&self, aspect_ratio: f32) -> Matrix4<f32>;
+    fn get_position(&self) -> Point3<f32>;
+    /// Whether the camera rays are parallel (orthographic projection) rather than starting from
+    /// the camera position, the imposters need to know it to cast their rays.
+    fn is_orthographic(&self) -> bool;
 }
 
 /// Camera marker for type-state pattern
@@ -138,15 +142,222 @@ impl Camera for PerspectiveCamera<Ready> {
     fn get_projection_matrix(&self, aspect_ratio: f32) -> Matrix4<f32> {
         Matrix4::new_perspective(aspect_ratio, self.fov, self.znear, self.zfar)
     }
+
+    fn get_position(&self) -> Point3<f32> {
+        self.state.pos
+    }
+
+    fn is_orthographic(&self) -> bool {
+        false
+    }
 }
 
 impl PerspectiveCamera<Ready> {
-    pub fn get_position(&self) -> Point3<f32> {
+    /// Set the field of view showing the target plane as the orthographic camera shows it, so
+    /// that switching between the projections keeps the framing.
+    pub fn match_orthographic(&mut self, camera: &OrthographicCamera<Ready>) {
+        let distance = (self.state.target - self.state.pos).norm();
+        self.fov =
+            (2.0 * (camera.height / (2.0 * distance)).atan()).clamp(self.fov_min, self.fov_max);
+    }
+}
+
+/// Camera that use an orthographic projection, the size of the molecule on screen doesn't depend
+/// on its depth and the camera rays are parallel.
+/// `height` is the height of the view volume (in world space units), its width following the
+/// aspect ratio, the zoom scales it.
+/// `height_min` and `height_max` hold the minimum and maximum acceptable value for the height of
+/// the view volume, this is used to clamp the camera zoom.
+/// `state` is the type-state marker for the camera state, it also hold necessary informations.
+pub struct OrthographicCamera<S: CameraState> {
+    pub height: f32,
+    pub height_min: f32,
+    pub height_max: f32,
+    pub zoom_sensitivity: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub state: S,
+}
+
+impl Default for OrthographicCamera<Virtual> {
+    fn default() -> Self {
+        Self {
+            height: 8.0,
+            height_min: 0.5,
+            height_max: 64.0,
+            zoom_sensitivity: 0.02,
+            znear: 0.1,
+            zfar: 1024.0,
+            state: Virtual {},
+        }
+    }
+}
+
+impl OrthographicCamera<Virtual> {
+    pub fn place(self, pos: Point3<f32>) -> OrthographicCamera<Placed> {
+        OrthographicCamera::<Placed> {
+            state: Placed { pos },
+            height: self.height,
+            height_min: self.height_min,
+            height_max: self.height_max,
+            zoom_sensitivity: self.zoom_sensitivity,
+            znear: self.znear,
+            zfar: self.zfar,
+        }
+    }
+
+    pub fn point(self, target: Point3<f32>, up: Vector3<f32>) -> OrthographicCamera<Pointed> {
+        OrthographicCamera::<Pointed> {
+            state: Pointed { target, up },
+            height: self.height,
+            height_min: self.height_min,
+            height_max: self.height_max,
+            zoom_sensitivity: self.zoom_sensitivity,
+            znear: self.znear,
+            zfar: self.zfar,
+        }
+    }
+}
+
+impl OrthographicCamera<Placed> {
+    pub fn point(self, target: Point3<f32>, up: Vector3<f32>) -> OrthographicCamera<Ready> {
+        OrthographicCamera::<Ready> {
+            state: Ready {
+                target,
+                up,
+                pos: self.state.pos,
+            },
+            height: self.height,
+            height_min: self.height_min,
+            height_max: self.height_max,
+            zoom_sensitivity: self.zoom_sensitivity,
+            znear: self.znear,
+            zfar: self.zfar,
+        }
+    }
+}
+
+impl OrthographicCamera<Pointed> {
+    pub fn place(self, pos: Point3<f32>) -> OrthographicCamera<Ready> {
+        OrthographicCamera::<Ready> {
+            state: Ready {
+                pos,
+                target: self.state.target,
+                up: self.state.up,
+            },
+            height: self.height,
+            height_min: self.height_min,
+            height_max: self.height_max,
+            zoom_sensitivity: self.zoom_sensitivity,
+            znear: self.znear,
+            zfar: self.zfar,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera<Ready> {
+    /// Zooming in shrinks the view volume, by `zoom_sensitivity` of its size per unit of
+    /// `zoom_amount`.
+    fn zoom(&mut self, zoom_amount: f32) {
+        self.height = (self.height * (1.0 - zoom_amount * self.zoom_sensitivity))
+            .clamp(self.height_min, self.height_max);
+    }
+
+    fn get_view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(&self.state.pos, &self.state.target, &self.state.up)
+    }
+
+    fn get_projection_matrix(&self, aspect_ratio: f32) -> Matrix4<f32> {
+        let half_height = self.height / 2.0;
+        let half_width = half_height * aspect_ratio;
+        Matrix4::new_orthographic(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            self.znear,
+            self.zfar,
+        )
+    }
+
+    fn get_position(&self) -> Point3<f32> {
         self.state.pos
     }
+
+    fn is_orthographic(&self) -> bool {
+        true
+    }
+}
+
+impl OrthographicCamera<Ready> {
+    /// Set the height of the view volume to the height of the target plane seen by the
+    /// perspective camera, so that switching between the projections keeps the framing.
+    pub fn match_perspective(&mut self, camera: &PerspectiveCamera<Ready>) {
+        let distance = (self.state.target - self.state.pos).norm();
+        self.height =
+            (2.0 * distance * (camera.fov / 2.0).tan()).clamp(self.height_min, self.height_max);
+    }
 }
 
 impl CameraState for Virtual {}
 impl CameraState for Placed {}
 impl CameraState for Pointed {}
 impl CameraState for Ready {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cameras() -> (PerspectiveCamera<Ready>, OrthographicCamera<Ready>) {
+        let (position, target) = (Point3::new(0.0, 0.0, 4.0), Point3::origin());
+        let perspective = PerspectiveCamera::default()
+            .place(position)
+            .point(target, Vector3::y());
+        let orthographic = OrthographicCamera::default()
+            .place(position)
+            .point(target, Vector3::y());
+        (perspective, orthographic)
+    }
+
+    #[test]
+    fn projection_switch_keeps_the_framing() {
+        let (mut perspective, mut orthographic) = cameras();
+        perspective.fov = 60.0_f32.to_radians();
+        orthographic.match_perspective(&perspective);
+        // The target plane, 4 units away, is seen 2 * 4 * tan(30°) high.
+        let height = 8.0 * 30.0_f32.to_radians().tan();
+        assert!((orthographic.height - height).abs() < 1e-5);
+        perspective.fov = 90.0_f32.to_radians();
+        perspective.match_orthographic(&orthographic);
+        assert!((perspective.fov.to_degrees() - 60.0).abs() < 1e-3);
+
+        // The top of the target plane is on the top of the screen with both projections.
+        let top = |camera: &dyn Camera| {
+            let point = Point3::new(0.0, height / 2.0, 0.0).to_homogeneous();
+            let clip = camera.get_projection_matrix(1.5) * camera.get_view_matrix() * point;
+            clip.y / clip.w
+        };
+        assert!((top(&perspective) - 1.0).abs() < 1e-5);
+        assert!((top(&orthographic) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zoom_limits() {
+        let (mut perspective, mut orthographic) = cameras();
+        orthographic.zoom(10.0);
+        assert!((orthographic.height - 8.0 * 0.8).abs() < 1e-5);
+        orthographic.zoom(1000.0);
+        assert_eq!(orthographic.height, orthographic.height_min);
+        orthographic.zoom(-10000.0);
+        assert_eq!(orthographic.height, orthographic.height_max);
+
+        perspective.zoom(1000.0);
+        assert_eq!(perspective.fov, perspective.fov_min);
+        perspective.zoom(-1000.0);
+        assert_eq!(perspective.fov, perspective.fov_max);
+        // A view volume out of its limits is clamped when matching the other projection.
+        orthographic.height_max = 10.0;
+        orthographic.match_perspective(&perspective);
+        assert_eq!(orthographic.height, 10.0);
+    }
+}
@@ -0,0 +1,583 @@
+use std::ops::Range;
+
+use nalgebra::{Point3, Point4, Vector3};
+
+use crate::{
+    mesh_batch::{Mesh, MeshVertex},
+    secondary_structure::SecondaryStructure,
+    structure::Structure,
+};
+
+/// Number of spline samples between two consecutive residues.
+const SUBDIVISIONS: usize = 8;
+
+/// Number of vertices of the cross section of the backbone.
+const PROFILE_POINTS: usize = 16;
+
+/// Maximum distance (in Å) between consecutive `CA`, longer ones are chain breaks.
+const MAX_CA_DISTANCE: f32 = 4.2;
+
+/// Maximum distance (in Å) between consecutive `P`, longer ones are chain breaks.
+const MAX_P_DISTANCE: f32 = 8.0;
+
+/// Half width and half thickness (in Å) of the nucleotide planks.
+const PLANK_HALF_WIDTH: f32 = 0.4;
+const PLANK_HALF_THICKNESS: f32 = 0.15;
+
+/// Cross section of the backbone, a superellipse of equation
+/// `|x / half_width|^exponent + |y / half_thickness|^exponent = 1`: an ellipse for an exponent of
+/// 2, closer to a rectangle as the exponent grows. The width is along the residue side vector.
+#[derive(Debug, Clone, Copy)]
+struct Profile {
+    half_width: f32,
+    half_thickness: f32,
+    exponent: f32,
+}
+
+const COIL: Profile = Profile {
+    half_width: 0.25,
+    half_thickness: 0.25,
+    exponent: 2.0,
+};
+const HELIX: Profile = Profile {
+    half_width: 0.9,
+    half_thickness: 0.15,
+    exponent: 4.0,
+};
+const STRAND: Profile = Profile {
+    half_width: 0.8,
+    half_thickness: 0.2,
+    exponent: 6.0,
+};
+const ARROW_HEAD: Profile = Profile {
+    half_width: 1.3,
+    half_thickness: 0.2,
+    exponent: 6.0,
+};
+const NUCLEIC_ACID_BACKBONE: Profile = Profile {
+    half_width: 0.4,
+    half_thickness: 0.4,
+    exponent: 2.0,
+};
+
+impl Profile {
+    fn of(secondary_structure: SecondaryStructure) -> Self {
+        match secondary_structure {
+            SecondaryStructure::Helix => HELIX,
+            SecondaryStructure::Strand => STRAND,
+            SecondaryStructure::Coil => COIL,
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            half_width: lerp(self.half_width, other.half_width),
+            half_thickness: lerp(self.half_thickness, other.half_thickness),
+            exponent: lerp(self.exponent, other.exponent),
+        }
+    }
+
+    /// Point of the cross section at `angle` and its normal, in the (width, thickness) plane.
+    fn point(&self, angle: f32) -> ((f32, f32), (f32, f32)) {
+        let (sin, cos) = angle.sin_cos();
+        let power = |value: f32, exponent: f32| value.signum() * value.abs().powf(exponent);
+        let point = (
+            self.half_width * power(cos, 2.0 / self.exponent),
+            self.half_thickness * power(sin, 2.0 / self.exponent),
+        );
+        let normal_exponent = 2.0 - 2.0 / self.exponent;
+        let normal = (
+            power(cos, normal_exponent) / self.half_width,
+            power(sin, normal_exponent) / self.half_thickness,
+        );
+        (point, normal)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolymerKind {
+    Protein,
+    NucleicAcid,
+}
+
+impl PolymerKind {
+    /// Maximum distance (in Å) between the guide atoms of consecutive residues, longer ones are
+    /// chain breaks.
+    pub fn max_guide_distance(&self) -> f32 {
+        match self {
+            Self::Protein => MAX_CA_DISTANCE,
+            Self::NucleicAcid => MAX_P_DISTANCE,
+        }
+    }
+}
+
+/// Residue of a polymer segment, the backbone spline goes through its guide atom (`CA` of the
+/// amino acids, `P` of the nucleotides). `side` is the direction of the ribbon width (the carbonyl
+/// of the amino acids), and `base` the tip and the plane normal of the nucleotide base.
+struct Guide {
+    position: Point3<f32>,
+    side: Option<Vector3<f32>>,
+    color: Point4<f32>,
+    secondary_structure: SecondaryStructure,
+    base: Option<(Point3<f32>, Option<Vector3<f32>>)>,
+}
+
+/// A sample of the backbone spline, with the frame and the cross section of the backbone there.
+struct Ring {
+    center: Point3<f32>,
+    tangent: Vector3<f32>,
+    side: Vector3<f32>,
+    profile: Profile,
+    color: Point4<f32>,
+}
+
+/// Build the cartoon mesh of the polymers of `atoms` (a model, or every model when they are
+/// superimposed). `positions` and `colors` are indexed by atom.
+///
+/// Proteins are a spline smoothed tube through their `CA`, flattened into a ribbon along the
+/// helices and into an arrow along the strands. Nucleic acids are a tube through their `P` with
+/// a plank from the backbone to the tip of each base (`N1` of the purines, `N3` of the
+/// pyrimidines).
+pub fn build_cartoon(
+    structure: &Structure,
+    positions: &[Point3<f32>],
+    atoms: Range<usize>,
+    colors: &[Point4<f32>],
+) -> Mesh {
+    let mut mesh = Mesh::default();
+
+    for chain in structure
+        .chains
+        .iter()
+        .filter(|chain| atoms.start <= chain.atoms.start && chain.atoms.end <= atoms.end)
+    {
+        let mut segment: Vec<Guide> = vec![];
+        let mut segment_kind = None;
+        for residue in chain.residues.clone() {
+            let guide = guide(structure, positions, colors, residue);
+            let is_continuous = match (&guide, segment_kind, segment.last()) {
+                (Some((kind, guide)), Some(segment_kind), Some(last)) => {
+                    *kind == segment_kind
+                        && (guide.position - last.position).norm() <= kind.max_guide_distance()
+                }
+                _ => false,
+            };
+            if !is_continuous {
+                if let Some(kind) = segment_kind {
+                    add_segment(&mut mesh, &segment, kind);
+                }
+                segment.clear();
+                segment_kind = None;
+            }
+            if let Some((kind, guide)) = guide {
+                segment.push(guide);
+                segment_kind = Some(kind);
+            }
+        }
+        if let Some(kind) = segment_kind {
+            add_segment(&mut mesh, &segment, kind);
+        }
+    }
+    mesh
+}
+
+/// Atom of a residue the backbone goes through, `None` for the residues which are neither an
+/// amino acid (`CA`, with `N` and `C` atoms) nor a nucleotide (`P`, or `O5'` for the 5' end without
+/// phosphate, with a `C1'` atom).
+pub fn guide_atom(structure: &Structure, residue: usize) -> Option<(PolymerKind, usize)> {
+    let find = |name: &str| structure.find_atom(residue, name);
+
+    if let (Some(ca), Some(_), Some(_)) = (find("CA"), find("N"), find("C")) {
+        return Some((PolymerKind::Protein, ca));
+    }
+    let (Some(_), Some(p)) = (find("C1'"), find("P").or_else(|| find("O5'"))) else {
+        return None;
+    };
+    Some((PolymerKind::NucleicAcid, p))
+}
+
+/// Guide of a residue, see `guide_atom`.
+fn guide(
+    structure: &Structure,
+    positions: &[Point3<f32>],
+    colors: &[Point4<f32>],
+    residue: usize,
+) -> Option<(PolymerKind, Guide)> {
+    let find = |name: &str| structure.find_atom(residue, name);
+
+    let (kind, atom) = guide_atom(structure, residue)?;
+    if kind == PolymerKind::Protein {
+        let ca = atom;
+        let guide = Guide {
+            position: positions[ca],
+            side: find("O").map(|o| positions[o] - positions[ca]),
+            color: colors[ca],
+            secondary_structure: structure.residues[residue].secondary_structure,
+            base: None,
+        };
+        return Some((PolymerKind::Protein, guide));
+    }
+
+    let p = atom;
+    let tip = if find("N9").is_some() {
+        find("N1")
+    } else {
+        find("N3")
+    };
+    let normal = match (find("C2"), find("C4"), find("C6")) {
+        (Some(c2), Some(c4), Some(c6)) => {
+            Some((positions[c4] - positions[c2]).cross(&(positions[c6] - positions[c2])))
+        }
+        _ => None,
+    };
+    let guide = Guide {
+        position: positions[p],
+        side: None,
+        color: colors[p],
+        secondary_structure: SecondaryStructure::Coil,
+        base: tip.map(|tip| (positions[tip], normal)),
+    };
+    Some((PolymerKind::NucleicAcid, guide))
+}
+
+/// Any unit vector orthogonal to `vector`.
+fn any_orthogonal(vector: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if vector.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    vector.cross(&axis).normalize()
+}
+
+/// Point and derivative of the uniform Catmull-Rom spline going through `p1` and `p2`.
+fn catmull_rom([p0, p1, p2, p3]: [Vector3<f32>; 4], t: f32) -> (Point3<f32>, Vector3<f32>) {
+    let a = p1 * 2.0;
+    let b = p2 - p0;
+    let c = p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3;
+    let d = -p0 + p1 * 3.0 - p2 * 3.0 + p3;
+    let point = (a + b * t + c * t * t + d * t * t * t) * 0.5;
+    let derivative = (b + c * 2.0 * t + d * 3.0 * t * t) * 0.5;
+    (Point3::from(point), derivative)
+}
+
+/// Add the backbone (and the nucleotide planks) of a continuous polymer segment.
+fn add_segment(mesh: &mut Mesh, guides: &[Guide], kind: PolymerKind) {
+    if guides.len() < 2 {
+        return;
+    }
+    let count = guides.len();
+
+    // The control points are extended past the segment ends by mirroring.
+    let control = |index: isize| -> Vector3<f32> {
+        match index {
+            -1 => guides[0].position.coords * 2.0 - guides[1].position.coords,
+            index if index as usize >= count => {
+                guides[count - 1].position.coords * 2.0 - guides[count - 2].position.coords
+            }
+            index => guides[index as usize].position.coords,
+        }
+    };
+
+    // The carbonyl flips from one residue to the next along a strand, the side vectors are made
+    // consistent so that the ribbon does not twist.
+    let mut sides: Vec<Option<Vector3<f32>>> = vec![];
+    for (index, guide) in guides.iter().enumerate() {
+        let side = guide.side.or_else(|| {
+            let previous = control(index as isize - 1) - guide.position.coords;
+            let next = control(index as isize + 1) - guide.position.coords;
+            Some(previous + next).filter(|side| side.norm() > 1e-3)
+        });
+        let side = match (side, sides.last().copied().flatten()) {
+            (Some(side), Some(previous)) if side.dot(&previous) < 0.0 => Some(-side),
+            (side, _) => side,
+        };
+        sides.push(
+            side.map(|side| side.normalize())
+                .or(sides.last().copied().flatten()),
+        );
+    }
+
+    let profile = |index: usize| match kind {
+        PolymerKind::Protein => Profile::of(guides[index].secondary_structure),
+        PolymerKind::NucleicAcid => NUCLEIC_ACID_BACKBONE,
+    };
+
+    let mut rings = vec![];
+    let mut previous_side = None;
+    for index in 0..count - 1 {
+        let points = [-1, 0, 1, 2].map(|offset| control(index as isize + offset));
+        let is_arrow_head = kind == PolymerKind::Protein
+            && guides[index].secondary_structure == SecondaryStructure::Strand
+            && guides[index + 1].secondary_structure != SecondaryStructure::Strand;
+        let samples = if index == count - 2 {
+            SUBDIVISIONS + 1
+        } else {
+            SUBDIVISIONS
+        };
+
+        for sample in 0..samples {
+            let t = sample as f32 / SUBDIVISIONS as f32;
+            let (center, derivative) = catmull_rom(points, t);
+            let tangent = if derivative.norm() > 1e-6 {
+                derivative.normalize()
+            } else {
+                (points[2] - points[1]).normalize()
+            };
+
+            let side = match (sides[index], sides[index + 1]) {
+                (Some(first), Some(second)) => Some(first.lerp(&second, t)),
+                (side, None) | (None, side) => side,
+            }
+            .or(previous_side)
+            .map(|side| side - tangent * side.dot(&tangent))
+            .filter(|side| side.norm() > 1e-3)
+            .map(|side| side.normalize())
+            .unwrap_or_else(|| any_orthogonal(&tangent));
+            previous_side = Some(side);
+
+            let color = if t < 0.5 {
+                guides[index].color
+            } else {
+                guides[index + 1].color
+            };
+            let mut ring = |profile: Profile| {
+                rings.push(Ring {
+                    center,
+                    tangent,
+                    side,
+                    profile,
+                    color,
+                })
+            };
+
+            if is_arrow_head {
+                // The base of the arrow head is a flat step from the strand width.
+                if sample == 0 {
+                    ring(STRAND);
+                }
+                ring(ARROW_HEAD.lerp(&profile(index + 1), t));
+            } else {
+                let smooth_t = t * t * (3.0 - 2.0 * t);
+                ring(profile(index).lerp(&profile(index + 1), smooth_t));
+            }
+        }
+    }
+    add_tube(mesh, &rings);
+
+    if kind == PolymerKind::NucleicAcid {
+        for guide in guides {
+            if let Some((tip, normal)) = guide.base {
+                add_plank(mesh, guide.position, tip, normal, guide.color);
+            }
+        }
+    }
+}
+
+/// Add the tube joining the cross sections of consecutive rings, closed by a cap at both ends.
+fn add_tube(mesh: &mut Mesh, rings: &[Ring]) {
+    let (Some(first), Some(last)) = (rings.first(), rings.last()) else {
+        return;
+    };
+
+    let start = mesh.vertices.len() as u32;
+    for ring in rings {
+        let up = ring.tangent.cross(&ring.side);
+        for point in 0..PROFILE_POINTS {
+            let angle = point as f32 / PROFILE_POINTS as f32 * std::f32::consts::TAU;
+            let ((x, y), (normal_x, normal_y)) = ring.profile.point(angle);
+            mesh.push_vertex(MeshVertex::new(
+                ring.center + ring.side * x + up * y,
+                (ring.side * normal_x + up * normal_y).normalize(),
+                ring.color,
+            ));
+        }
+    }
+
+    let points = PROFILE_POINTS as u32;
+    for ring in 0..rings.len() as u32 - 1 {
+        for point in 0..points {
+            let next_point = (point + 1) % points;
+            let current = start + ring * points;
+            let next = current + points;
+            mesh.push_triangle(current + point, current + next_point, next + point);
+            mesh.push_triangle(current + next_point, next + next_point, next + point);
+        }
+    }
+
+    add_cap(mesh, first, -first.tangent);
+    add_cap(mesh, last, last.tangent);
+}
+
+/// Close the cross section of `ring` with a flat cap facing `normal`.
+fn add_cap(mesh: &mut Mesh, ring: &Ring, normal: Vector3<f32>) {
+    let up = ring.tangent.cross(&ring.side);
+    let center = mesh.push_vertex(MeshVertex::new(ring.center, normal, ring.color));
+    for point in 0..PROFILE_POINTS {
+        let angle = point as f32 / PROFILE_POINTS as f32 * std::f32::consts::TAU;
+        let ((x, y), _) = ring.profile.point(angle);
+        mesh.push_vertex(MeshVertex::new(
+            ring.center + ring.side * x + up * y,
+            normal,
+            ring.color,
+        ));
+    }
+    let points = PROFILE_POINTS as u32;
+    for point in 0..points {
+        mesh.push_triangle(
+            center,
+            center + 1 + point,
+            center + 1 + (point + 1) % points,
+        );
+    }
+}
+
+/// Add a flat box from the backbone to the tip of a nucleotide base, lying in the base plane.
+fn add_plank(
+    mesh: &mut Mesh,
+    start: Point3<f32>,
+    end: Point3<f32>,
+    base_normal: Option<Vector3<f32>>,
+    color: Point4<f32>,
+) {
+    let axis = end - start;
+    if axis.norm() < 1e-3 {
+        return;
+    }
+    let width = base_normal
+        .map(|normal| normal.cross(&axis))
+        .filter(|width| width.norm() > 1e-3)
+        .map(|width| width.normalize())
+        .unwrap_or_else(|| any_orthogonal(&axis));
+    let thickness = axis.cross(&width).normalize();
+    let width = width * PLANK_HALF_WIDTH;
+    let thickness = thickness * PLANK_HALF_THICKNESS;
+
+    let corner =
+        |along: f32, across: f32, up: f32| start + axis * along + width * across + thickness * up;
+    // Each face is a quad with its own normal, given by its corners (along, across, up).
+    let faces = [
+        (
+            [(0., -1., 1.), (1., -1., 1.), (1., 1., 1.), (0., 1., 1.)],
+            thickness,
+        ),
+        (
+            [(0., 1., -1.), (1., 1., -1.), (1., -1., -1.), (0., -1., -1.)],
+            -thickness,
+        ),
+        (
+            [(0., 1., 1.), (1., 1., 1.), (1., 1., -1.), (0., 1., -1.)],
+            width,
+        ),
+        (
+            [(0., -1., -1.), (1., -1., -1.), (1., -1., 1.), (0., -1., 1.)],
+            -width,
+        ),
+        (
+            [(1., -1., -1.), (1., 1., -1.), (1., 1., 1.), (1., -1., 1.)],
+            axis,
+        ),
+        (
+            [(0., -1., 1.), (0., 1., 1.), (0., 1., -1.), (0., -1., -1.)],
+            -axis,
+        ),
+    ];
+    for (corners, normal) in faces {
+        let normal = normal.normalize();
+        let [a, b, c, d] = corners.map(|(along, across, up)| {
+            mesh.push_vertex(MeshVertex::new(corner(along, across, up), normal, color))
+        });
+        mesh.push_triangle(a, b, c);
+        mesh.push_triangle(a, c, d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{io::LoadOptions, secondary_structure::SecondaryStructureSource};
+
+    fn cartoon(structure: &Structure) -> Mesh {
+        let positions: Vec<_> = structure.positions().collect();
+        let colors = vec![Point4::new(1.0, 1.0, 1.0, 1.0); structure.atom_count()];
+        build_cartoon(structure, &positions, 0..structure.atom_count(), &colors)
+    }
+
+    fn load(path: &str) -> Structure {
+        let mut structure = Structure::load(Path::new(path), &LoadOptions::default()).unwrap();
+        structure.assign_secondary_structure(SecondaryStructureSource::Auto);
+        structure
+    }
+
+    /// Vertices of a tube made of `rings` cross sections, with its two caps.
+    fn tube_vertices(rings: usize) -> usize {
+        rings * PROFILE_POINTS + 2 * (PROFILE_POINTS + 1)
+    }
+
+    fn ring_width(mesh: &Mesh, first_vertex: usize, ring: usize) -> f32 {
+        let vertex = |point: usize| {
+            Point3::from(mesh.vertices[first_vertex + ring * PROFILE_POINTS + point].position)
+        };
+        (vertex(0) - vertex(PROFILE_POINTS / 2)).norm()
+    }
+
+    #[test]
+    fn protein() {
+        // A helix of 10 residues and two strands of 6 residues ("CEEECC"), each strand ending
+        // with an arrow head one ring longer than the rest of the tube.
+        let mut structure = load("resources/pdb/secondary_structure.pdb");
+        let mesh = cartoon(&structure);
+        assert!(!mesh.is_empty());
+        let helix = tube_vertices(9 * SUBDIVISIONS + 1);
+        let strand = tube_vertices(5 * SUBDIVISIONS + 2);
+        assert_eq!(mesh.vertices.len(), helix + 2 * strand);
+        assert_eq!(mesh.indices.len() % 3, 0);
+
+        // The arrow head starts after the third strand residue: a step from the strand width to
+        // the arrow head width, narrowing down to the coil of the next residue.
+        let arrow = 3 * SUBDIVISIONS;
+        for first_vertex in [helix, helix + strand] {
+            let width = |ring| ring_width(&mesh, first_vertex, ring);
+            assert!((width(arrow) - 2.0 * STRAND.half_width).abs() < 1e-2);
+            assert!((width(arrow + 1) - 2.0 * ARROW_HEAD.half_width).abs() < 1e-2);
+            assert!((width(arrow + 1 + SUBDIVISIONS) - 2.0 * COIL.half_width).abs() < 1e-2);
+        }
+
+        // A strand running to the end of its chain has no arrow head.
+        for residue in structure.residues.iter_mut().skip(10) {
+            residue.secondary_structure = SecondaryStructure::Strand;
+        }
+        let strand = tube_vertices(5 * SUBDIVISIONS + 1);
+        assert_eq!(cartoon(&structure).vertices.len(), helix + 2 * strand);
+    }
+
+    #[test]
+    fn nucleic_acid() {
+        // Three pyrimidine nucleotides, a plank of 6 quads for each base.
+        let structure = load("resources/pdb/trinucleotide.pdb");
+        for residue in 0..3 {
+            let (kind, atom) = guide_atom(&structure, residue).unwrap();
+            assert_eq!(kind, PolymerKind::NucleicAcid);
+            assert_eq!(structure.atoms[atom].name, "P");
+        }
+        let mesh = cartoon(&structure);
+        assert_eq!(
+            mesh.vertices.len(),
+            tube_vertices(2 * SUBDIVISIONS + 1) + 3 * 6 * 4
+        );
+
+        // Moving the last phosphate away breaks the chain, the lone last nucleotide is not drawn.
+        let mut positions: Vec<_> = structure.positions().collect();
+        let p = structure.find_atom(2, "P").unwrap();
+        positions[p].z += MAX_P_DISTANCE;
+        let colors = vec![Point4::new(1.0, 1.0, 1.0, 1.0); structure.atom_count()];
+        let mesh = build_cartoon(&structure, &positions, 0..structure.atom_count(), &colors);
+        assert_eq!(
+            mesh.vertices.len(),
+            tube_vertices(SUBDIVISIONS + 1) + 2 * 6 * 4
+        );
+    }
+}
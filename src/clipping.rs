@@ -0,0 +1,163 @@
+use clap::ValueEnum;
+
+/// Thinnest slab (in Å) the planes can be moved to.
+const MIN_SLAB_THICKNESS: f32 = 0.5;
+
+/// Near and far clipping planes, perpendicular to the view direction. Both are signed distances
+/// (in Å) from the molecule center along the view direction, negative towards the camera.
+/// `radius` is the radius (in Å) of the molecule, the planes are kept within it, and the slab
+/// spans the whole molecule when nothing is clipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClippingSlab {
+    pub near: f32,
+    pub far: f32,
+    pub radius: f32,
+}
+
+impl ClippingSlab {
+    /// Slab of a molecule of the given radius, with its planes at the given distances from the
+    /// molecule center, or on the molecule bounds when not given. The planes are kept within the
+    /// molecule, a near plane behind the far one is an error.
+    pub fn new(radius: f32, near: Option<f32>, far: Option<f32>) -> anyhow::Result<Self> {
+        if let (Some(near), Some(far)) = (near, far) {
+            if near >= far {
+                return Err(anyhow::format_err!(
+                    "The near clipping plane (--clip-near {near}) must be in front of the far one \
+                     (--clip-far {far})"
+                ));
+            }
+        }
+        let mut slab = Self {
+            near: -radius,
+            far: radius,
+            radius,
+        };
+        slab.move_far(far.unwrap_or(radius) - radius);
+        slab.move_near(near.unwrap_or(-radius) + radius);
+        Ok(slab)
+    }
+
+    /// Move the near plane away from the camera by `delta` Å (towards it when negative).
+    pub fn move_near(&mut self, delta: f32) {
+        self.near = (self.near + delta).clamp(-self.radius, self.far - MIN_SLAB_THICKNESS);
+    }
+
+    /// Move the far plane away from the camera by `delta` Å (towards it when negative).
+    pub fn move_far(&mut self, delta: f32) {
+        self.far = (self.far + delta).clamp(self.near + MIN_SLAB_THICKNESS, self.radius);
+    }
+
+    /// Depths (distances along the view direction, in world space units) of the near and far
+    /// planes, for a camera at `camera_distance` from the molecule center and a molecule scaled by
+    /// `scale`.
+    pub fn view_depths(&self, camera_distance: f32, scale: f32) -> (f32, f32) {
+        (
+            camera_distance + self.near * scale,
+            camera_distance + self.far * scale,
+        )
+    }
+}
+
+/// Fog blending the atoms toward the background color with their depth in the slab, making the
+/// back of the molecule recede (depth cueing).
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FogMode {
+    #[default]
+    Off,
+    /// Fog growing linearly from the front to the back of the slab.
+    Linear,
+    /// Fog growing quickly behind the front of the slab, then leveling off.
+    Exponential,
+}
+
+impl FogMode {
+    /// Next fog mode, used to cycle through the modes at runtime.
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Off => Self::Linear,
+            Self::Linear => Self::Exponential,
+            Self::Exponential => Self::Off,
+        }
+    }
+
+    /// Value of the `fog_mode` uniform of the shaders.
+    pub fn shader_mode(&self) -> i32 {
+        match self {
+            Self::Off => 0,
+            Self::Linear => 1,
+            Self::Exponential => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_planes() {
+        let slab = ClippingSlab::new(10.0, None, None).unwrap();
+        assert_eq!((slab.near, slab.far), (-10.0, 10.0));
+        let slab = ClippingSlab::new(10.0, Some(-2.0), Some(3.0)).unwrap();
+        assert_eq!((slab.near, slab.far), (-2.0, 3.0));
+        // Kept within the molecule.
+        let slab = ClippingSlab::new(10.0, Some(-20.0), Some(15.0)).unwrap();
+        assert_eq!((slab.near, slab.far), (-10.0, 10.0));
+        let slab = ClippingSlab::new(10.0, Some(12.0), None).unwrap();
+        assert_eq!((slab.near, slab.far), (10.0 - MIN_SLAB_THICKNESS, 10.0));
+
+        let error = ClippingSlab::new(10.0, Some(2.0), Some(-1.0)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The near clipping plane (--clip-near 2) must be in front of the far one \
+             (--clip-far -1)"
+        );
+        assert!(ClippingSlab::new(10.0, Some(1.0), Some(1.0)).is_err());
+    }
+
+    #[test]
+    fn moved_planes() {
+        let mut slab = ClippingSlab::new(10.0, None, None).unwrap();
+        slab.move_near(4.0);
+        slab.move_far(-3.0);
+        assert_eq!((slab.near, slab.far), (-6.0, 7.0));
+        // The planes stop at the molecule bounds and keep the slab from getting thinner.
+        slab.move_near(-8.0);
+        assert_eq!(slab.near, -10.0);
+        slab.move_near(100.0);
+        assert_eq!(slab.near, 7.0 - MIN_SLAB_THICKNESS);
+        slab.move_far(-100.0);
+        assert_eq!(slab.far, 7.0);
+        slab.move_far(100.0);
+        assert_eq!(slab.far, 10.0);
+        slab.move_near(-100.0);
+        slab.move_far(-100.0);
+        assert_eq!((slab.near, slab.far), (-10.0, -10.0 + MIN_SLAB_THICKNESS));
+    }
+
+    #[test]
+    fn view_depths() {
+        let slab = ClippingSlab::new(10.0, Some(-2.0), Some(4.0)).unwrap();
+        assert_eq!(slab.view_depths(20.0, 1.0), (18.0, 24.0));
+        assert_eq!(slab.view_depths(20.0, 0.5), (19.0, 22.0));
+    }
+
+    #[test]
+    fn fog_modes() {
+        let mut mode = FogMode::default();
+        let mut modes = vec![];
+        for _ in 0..3 {
+            modes.push((mode, mode.shader_mode()));
+            mode = mode.next();
+        }
+        assert_eq!(mode, FogMode::Off);
+        assert_eq!(
+            modes,
+            [
+                (FogMode::Off, 0),
+                (FogMode::Linear, 1),
+                (FogMode::Exponential, 2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use nalgebra::Point4;
+use pdbtbx::Element;
+
+use crate::{
+    secondary_structure::SecondaryStructure,
+    structure::{Atom, Structure},
+    templates::{self, ResidueKind},
+};
+
+/// Distinct colors given to the chains, in order of appearance.
+const CHAIN_PALETTE: [Point4<f32>; 10] = [
+    Point4::new(0.302, 0.686, 0.290, 1.0), // green
+    Point4::new(0.216, 0.494, 0.722, 1.0), // blue
+    Point4::new(0.894, 0.102, 0.110, 1.0), // red
+    Point4::new(1.0, 0.498, 0.0, 1.0),     // orange
+    Point4::new(0.596, 0.306, 0.639, 1.0), // purple
+    Point4::new(1.0, 1.0, 0.2, 1.0),       // yellow
+    Point4::new(0.651, 0.337, 0.157, 1.0), // brown
+    Point4::new(0.969, 0.506, 0.749, 1.0), // pink
+    Point4::new(0.0, 0.8, 0.8, 1.0),       // cyan
+    Point4::new(0.6, 0.6, 0.6, 1.0),       // grey
+];
+
+const HELIX_COLOR: Point4<f32> = Point4::new(1.0, 0.0, 0.5, 1.0);
+const STRAND_COLOR: Point4<f32> = Point4::new(1.0, 0.784, 0.0, 1.0);
+const COIL_COLOR: Point4<f32> = Point4::new(0.9, 0.9, 0.9, 1.0);
+
+/// Property driving the color of the atoms.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomColoring {
+    /// CPK coloring of the atom element.
+    Element,
+    /// Red (negative) to blue (positive) gradient over the partial charge, or the formal charge
+    /// when the file has no partial charges.
+    Charge,
+    /// CPK coloring shaded by the force field atom type (MOL2 Sybyl types).
+    AtomType,
+    /// A distinct color per chain identifier.
+    Chain,
+    /// Shapely colors of the amino acids and nucleotides, other residues get a color from their
+    /// name.
+    ResidueName,
+    /// Helices, strands and coils of the amino acids, see `secondary_structure`.
+    SecondaryStructure,
+    /// Blue to red along the sequence of each chain.
+    Rainbow,
+    /// Blue (low) to white to red (high) gradient over the B-factors of the structure.
+    BFactor,
+    /// Blue (empty) to white to red (full) gradient over the occupancy.
+    Occupancy,
+    /// Blue (hydrophilic) to white to red (hydrophobic) gradient over the Kyte-Doolittle scale of
+    /// the amino acids.
+    Hydrophobicity,
+}
+
+impl AtomColoring {
+    /// Next coloring, used to cycle through the colorings at runtime.
+    pub fn next(&self) -> Self {
+        let colorings = Self::value_variants();
+        let index = colorings
+            .iter()
+            .position(|coloring| coloring == self)
+            .unwrap_or_default();
+        colorings[(index + 1) % colorings.len()]
+    }
+}
+
+/// Return the normalized RGBA color of every atom of the structure according to the given
+/// coloring. Residue based colorings fall back to the CPK coloring for the residues they don't
+/// apply to (the ligands with `secondary-structure` or `hydrophobicity`).
+pub fn atom_colors(structure: &Structure, coloring: AtomColoring) -> Vec<Point4<f32>> {
+    let atoms = &structure.atoms;
+    match coloring {
+        AtomColoring::Element => atoms
+            .iter()
+            .map(|atom| element_color(atom.element))
+            .collect(),
+        AtomColoring::Charge => atoms
+            .iter()
+            .map(|atom| charge_color(atom.partial_charge.unwrap_or(atom.formal_charge as f32)))
+            .collect(),
+        AtomColoring::AtomType => atoms
+            .iter()
+            .map(|atom| match atom.atom_type.as_deref() {
+                Some(atom_type) => atom_type_color(atom, atom_type),
+                None => element_color(atom.element),
+            })
+            .collect(),
+        AtomColoring::Chain => {
+            let mut chain_colors = HashMap::new();
+            atoms
+                .iter()
+                .map(|atom| {
+                    let id = &structure.chains[structure.residues[atom.residue].chain].id;
+                    let next = chain_colors.len();
+                    let index = *chain_colors.entry(id).or_insert(next);
+                    CHAIN_PALETTE[index % CHAIN_PALETTE.len()]
+                })
+                .collect()
+        }
+        AtomColoring::ResidueName => atoms
+            .iter()
+            .map(|atom| residue_name_color(&structure.residues[atom.residue].name))
+            .collect(),
+        AtomColoring::SecondaryStructure => atoms
+            .iter()
+            .map(|atom| {
+                let residue = &structure.residues[atom.residue];
+                match residue_kind(&residue.name) {
+                    Some(ResidueKind::AminoAcid) => match residue.secondary_structure {
+                        SecondaryStructure::Helix => HELIX_COLOR,
+                        SecondaryStructure::Strand => STRAND_COLOR,
+                        SecondaryStructure::Coil => COIL_COLOR,
+                    },
+                    _ => element_color(atom.element),
+                }
+            })
+            .collect(),
+        AtomColoring::Rainbow => atoms
+            .iter()
+            .map(|atom| {
+                let chain = &structure.chains[structure.residues[atom.residue].chain];
+                let position = atom.residue - chain.residues.start;
+                let length = chain.residues.len().saturating_sub(1).max(1);
+                rainbow_color(position as f32 / length as f32)
+            })
+            .collect(),
+        AtomColoring::BFactor => {
+            let (min, max) = atoms.iter().fold((f32::MAX, f32::MIN), |(min, max), atom| {
+                (min.min(atom.b_factor), max.max(atom.b_factor))
+            });
+            atoms
+                .iter()
+                .map(|atom| gradient_color(normalize(atom.b_factor, min, max)))
+                .collect()
+        }
+        AtomColoring::Occupancy => atoms
+            .iter()
+            .map(|atom| gradient_color(atom.occupancy.clamp(0.0, 1.0)))
+            .collect(),
+        AtomColoring::Hydrophobicity => atoms
+            .iter()
+            .map(
+                |atom| match hydrophobicity(&structure.residues[atom.residue].name) {
+                    Some(value) => gradient_color(normalize(value, -4.5, 4.5)),
+                    None => element_color(atom.element),
+                },
+            )
+            .collect(),
+    }
+}
+
+fn residue_kind(residue_name: &str) -> Option<ResidueKind> {
+    templates::template(residue_name).map(|template| template.kind)
+}
+
+/// Map `value` from `min..max` to `0..1`, a degenerate range maps to the middle.
+fn normalize(value: f32, min: f32, max: f32) -> f32 {
+    if max - min <= f32::EPSILON {
+        return 0.5;
+    }
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// Blue to white to red gradient, `t` going from 0 to 1.
+fn gradient_color(t: f32) -> Point4<f32> {
+    let t = t * 2.0 - 1.0;
+    if t < 0.0 {
+        Point4::new(1.0 + t, 1.0 + t, 1.0, 1.0)
+    } else {
+        Point4::new(1.0, 1.0 - t, 1.0 - t, 1.0)
+    }
+}
+
+/// Blue, cyan, green, yellow to red hue gradient, `t` going from 0 to 1.
+fn rainbow_color(t: f32) -> Point4<f32> {
+    // Hue from 240° (blue) down to 0° (red), at full saturation and value.
+    let hue = (1.0 - t.clamp(0.0, 1.0)) * 4.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        _ => (0.0, x, 1.0),
+    };
+    Point4::new(r, g, b, 1.0)
+}
+
+/// Shapely color of a standard residue (RasMol), other residues get a color of `CHAIN_PALETTE`
+/// from their name so that each ligand keeps the same color.
+fn residue_name_color(residue_name: &str) -> Point4<f32> {
+    let rgb = |r: u8, g: u8, b: u8| {
+        Point4::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+    };
+    let name = residue_name.trim().to_ascii_uppercase();
+    match name.as_str() {
+        "ALA" => rgb(140, 255, 140),
+        "ARG" | "ARN" => rgb(0, 0, 124),
+        "ASN" => rgb(255, 124, 112),
+        "ASP" | "ASH" => rgb(160, 0, 66),
+        "CYS" | "CYX" | "CYM" => rgb(255, 255, 112),
+        "GLN" => rgb(255, 76, 76),
+        "GLU" | "GLH" => rgb(102, 0, 0),
+        "GLY" => rgb(255, 255, 255),
+        "HIS" | "HID" | "HIE" | "HIP" | "HSD" | "HSE" | "HSP" => rgb(112, 112, 255),
+        "ILE" => rgb(0, 76, 0),
+        "LEU" => rgb(69, 94, 69),
+        "LYS" | "LYN" => rgb(71, 71, 184),
+        "MET" => rgb(184, 160, 66),
+        "PHE" => rgb(83, 76, 66),
+        "PRO" => rgb(82, 82, 82),
+        "SER" => rgb(255, 112, 66),
+        "THR" => rgb(184, 76, 0),
+        "TRP" => rgb(79, 70, 0),
+        "TYR" => rgb(140, 112, 76),
+        "VAL" => rgb(255, 140, 255),
+        "A" | "DA" | "RA" => rgb(160, 160, 255),
+        "C" | "DC" | "RC" => rgb(255, 140, 75),
+        "G" | "DG" | "RG" => rgb(255, 112, 112),
+        "T" | "DT" => rgb(160, 255, 160),
+        "U" | "DU" | "RU" => rgb(184, 184, 184),
+        "HOH" | "WAT" | "H2O" | "DOD" | "SOL" | "TIP3" => rgb(0, 255, 255),
+        _ => {
+            let hash = name.bytes().fold(0usize, |hash, byte| {
+                hash.wrapping_mul(31).wrapping_add(byte as usize)
+            });
+            CHAIN_PALETTE[hash % CHAIN_PALETTE.len()]
+        }
+    }
+}
+
+/// Kyte-Doolittle hydropathy index of an amino acid, `None` for the other residues.
+fn hydrophobicity(residue_name: &str) -> Option<f32> {
+    let value = match residue_name.trim().to_ascii_uppercase().as_str() {
+        "ILE" => 4.5,
+        "VAL" => 4.2,
+        "LEU" => 3.8,
+        "PHE" => 2.8,
+        "CYS" | "CYX" | "CYM" => 2.5,
+        "MET" => 1.9,
+        "ALA" => 1.8,
+        "GLY" => -0.4,
+        "THR" => -0.7,
+        "SER" => -0.8,
+        "TRP" => -0.9,
+        "TYR" => -1.3,
+        "PRO" => -1.6,
+        "HIS" | "HID" | "HIE" | "HIP" | "HSD" | "HSE" | "HSP" => -3.2,
+        "GLU" | "GLH" | "GLN" | "ASP" | "ASH" | "ASN" => -3.5,
+        "LYS" | "LYN" => -3.9,
+        "ARG" | "ARN" => -4.5,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Red to white to blue gradient, saturating at one elementary charge.
+fn charge_color(charge: f32) -> Point4<f32> {
+    let t = charge.clamp(-1.0, 1.0);
+    if t < 0.0 {
+        Point4::new(1.0, 1.0 + t, 1.0 + t, 1.0)
+    } else {
+        Point4::new(1.0 - t, 1.0 - t, 1.0, 1.0)
+    }
+}
+
+/// Shade the CPK color of the atom according to the hybridization or environment part of its
+/// Sybyl type (the part after the dot, `ar` in `C.ar`).
+fn atom_type_color(atom: &Atom, atom_type: &str) -> Point4<f32> {
+    let base = element_color(atom.element);
+    let mix =
+        |target: Point4<f32>, amount: f32| Point4::from(base.coords.lerp(&target.coords, amount));
+
+    match atom_type.split_once('.').map(|(_, suffix)| suffix) {
+        Some("ar") => mix(Point4::new(0.6, 0.2, 0.8, 1.0), 0.4), // aromatic, purple
+        Some("am") => mix(Point4::new(0.0, 0.7, 0.7, 1.0), 0.4), // amide, teal
+        Some("1") => mix(Point4::new(1.0, 1.0, 1.0, 1.0), 0.4),  // sp, lighter
+        Some("3") => mix(Point4::new(0.0, 0.0, 0.0, 1.0), 0.3),  // sp3, darker
+        _ => base,
+    }
+}
+
+/// Normalized RGBA color of an element according to the CPK coloring, atoms of unknown element
+/// are pink.
+pub fn element_color(element: Option<Element>) -> Point4<f32> {
+    let Some(element) = element else {
+        return Point4::new(1.0, 0.078, 0.576, 1.0);
+    };
+    match element {
+        Element::H => Point4::new(1.0, 1.0, 1.0, 1.0), // 255/255
+        Element::He => Point4::new(0.851, 1.0, 1.0, 1.0), // 217/255
+        Element::Li => Point4::new(0.8, 0.502, 1.0, 1.0), // 204/255, 128/255
+        Element::Be => Point4::new(0.761, 1.0, 0.0, 1.0), // 194/255
+        Element::B => Point4::new(1.0, 0.710, 0.710, 1.0), // 181/255
+        Element::C => Point4::new(0.565, 0.565, 0.565, 1.0), // 144/255
+        Element::N => Point4::new(0.188, 0.314, 0.973, 1.0), // 48/255, 80/255, 248/255
+        Element::O => Point4::new(1.0, 0.051, 0.051, 1.0), // 13/255
+        Element::F => Point4::new(0.565, 0.878, 0.314, 1.0), // 144/255, 224/255, 80/255
+        Element::Ne => Point4::new(0.702, 0.890, 0.961, 1.0), // 179/255, 227/255, 245/255
+        Element::Na => Point4::new(0.671, 0.361, 0.949, 1.0), // 171/255, 92/255, 242/255
+        Element::Mg => Point4::new(0.541, 1.0, 0.0, 1.0), // 138/255
+        Element::Al => Point4::new(0.749, 0.651, 0.651, 1.0), // 191/255, 166/255
+        Element::Si => Point4::new(0.941, 0.784, 0.627, 1.0), // 240/255, 200/255, 160/255
+        Element::P => Point4::new(1.0, 0.502, 0.0, 1.0), // 128/255
+        Element::S => Point4::new(1.0, 1.0, 0.188, 1.0), // 48/255
+        Element::Cl => Point4::new(0.122, 0.941, 0.122, 1.0), // 31/255, 240/255
+        Element::Ar => Point4::new(0.502, 0.820, 0.890, 1.0), // 128/255, 209/255, 227/255
+        Element::K => Point4::new(0.561, 0.251, 0.831, 1.0), // 143/255, 64/255, 212/255
+        Element::Ca => Point4::new(0.239, 1.0, 0.0, 1.0), // 61/255
+        Element::Fe => Point4::new(0.878, 0.400, 0.200, 1.0), // 224/255, 102/255, 51/255
+        Element::Cu => Point4::new(0.784, 0.502, 0.200, 1.0), // 200/255, 128/255, 51/255
+        Element::Zn => Point4::new(0.490, 0.502, 0.690, 1.0), // 125/255, 128/255, 176/255
+        Element::Br => Point4::new(0.651, 0.161, 0.161, 1.0), // 166/255, 41/255
+        Element::Ag => Point4::new(0.753, 0.753, 0.753, 1.0), // 192/255
+        Element::I => Point4::new(0.580, 0.0, 0.580, 1.0), // 148/255
+        Element::Au => Point4::new(1.0, 0.820, 0.137, 1.0), // 255/255, 209/255, 35/255
+        Element::Pb => Point4::new(0.341, 0.349, 0.380, 1.0), // 87/255, 89/255, 97/255
+        Element::U => Point4::new(0.0, 0.561, 1.0, 1.0), // 0, 143/255, 255/255
+        _ => Point4::new(1.0, 0.078, 0.576, 1.0),      // 255/255, 20/255, 147/255 (pink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::io::LoadOptions;
+
+    fn load(path: &str) -> Structure {
+        Structure::load(Path::new(path), &LoadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn element_colors() {
+        let ethylene = load("resources/pdb/ethylene.pdb");
+        let colors = atom_colors(&ethylene, AtomColoring::Element);
+        assert_eq!(colors.len(), 6);
+        assert_eq!(colors[0], element_color(Some(Element::C)));
+        assert_eq!(colors[5], Point4::new(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(element_color(None), element_color(Some(Element::Xe)));
+    }
+
+    #[test]
+    fn chain_colors() {
+        let complex = load("resources/pdb/complex.pdb");
+        let colors = atom_colors(&complex, AtomColoring::Chain);
+        for (index, chain) in complex.chains.iter().enumerate() {
+            assert!(chain
+                .atoms
+                .clone()
+                .all(|atom| colors[atom] == CHAIN_PALETTE[index]));
+        }
+    }
+
+    #[test]
+    fn rainbow_colors() {
+        let complex = load("resources/pdb/complex.pdb");
+        let colors = atom_colors(&complex, AtomColoring::Rainbow);
+        let chain = &complex.chains[2];
+        assert_eq!(colors[chain.atoms.start], Point4::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(colors[chain.atoms.end - 1], Point4::new(1.0, 0.0, 0.0, 1.0));
+        // A single residue chain is blue.
+        assert_eq!(colors[0], Point4::new(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn gradient_colors() {
+        // Every B-factor is the same, they map to the middle of the gradient.
+        let ethylene = load("resources/pdb/ethylene.pdb");
+        let colors = atom_colors(&ethylene, AtomColoring::BFactor);
+        assert!(colors
+            .iter()
+            .all(|color| *color == Point4::new(1.0, 1.0, 1.0, 1.0)));
+        let colors = atom_colors(&ethylene, AtomColoring::Occupancy);
+        assert!(colors
+            .iter()
+            .all(|color| *color == Point4::new(1.0, 0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn residue_colors() {
+        let complex = load("resources/pdb/complex.pdb");
+        let colors = atom_colors(&complex, AtomColoring::Hydrophobicity);
+        // The ligand has no hydrophobicity and keeps its CPK colors.
+        assert_eq!(colors[0], element_color(complex.atoms[0].element));
+        let leucine = complex
+            .atoms
+            .iter()
+            .position(|atom| complex.residues[atom.residue].name == "LEU")
+            .unwrap();
+        assert_eq!(colors[leucine], gradient_color(normalize(3.8, -4.5, 4.5)));
+
+        assert_eq!(residue_name_color("gly"), Point4::new(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(residue_name_color("MOL"), residue_name_color("MOL"));
+    }
+
+    #[test]
+    fn charge_colors() {
+        let ethanol = load("resources/molecules/ethanol.mol2");
+        let colors = atom_colors(&ethanol, AtomColoring::Charge);
+        // Negative partial charge of the oxygen, positive of its hydrogen.
+        assert_eq!(colors[2].x, 1.0);
+        assert!(colors[2].y < 1.0);
+        assert_eq!(colors[8].z, 1.0);
+        assert!(colors[8].x < 1.0);
+
+        let colors = atom_colors(&ethanol, AtomColoring::AtomType);
+        assert_ne!(colors[0], element_color(Some(Element::C)));
+        assert_eq!(colors[3], element_color(Some(Element::H)));
+    }
+
+    #[test]
+    fn cycle_colorings() {
+        let mut coloring = AtomColoring::Element;
+        for _ in 0..AtomColoring::value_variants().len() {
+            coloring = coloring.next();
+        }
+        assert_eq!(coloring, AtomColoring::Element);
+    }
+}
@@ -6,7 +6,10 @@ use glium::{
 };
 use nalgebra::{Point3, Point4};
 
-use crate::geometry::quad::{Quad, QuadVertex};
+use crate::{
+    geometry::quad::{Quad, QuadVertex},
+    shaders,
+};
 
 /// This struct hold a instancing imposter cylinder batch informations.
 /// `vertex_buffer` hold the quad geometry for the imposter, and `index_buffer` contains the
@@ -27,6 +30,9 @@ pub struct CylinderInstanceData {
     pub instance_color_first_half: [f32; 4],
     pub instance_color_second_half: [f32; 4],
     pub instance_radius: f32,
+    /// Length of the dashes (and of the gaps between them) of a dashed cylinder, 0 for a solid
+    /// cylinder.
+    pub instance_dash_length: f32,
 }
 
 implement_vertex!(
@@ -35,7 +41,8 @@ implement_vertex!(
     instance_end_pos,
     instance_color_first_half,
     instance_color_second_half,
-    instance_radius
+    instance_radius,
+    instance_dash_length
 );
 
 impl CylinderInstanceData {
@@ -52,6 +59,15 @@ impl CylinderInstanceData {
             instance_color_first_half: color_first_half.into(),
             instance_color_second_half: color_second_half.into(),
             instance_radius: radius,
+            instance_dash_length: 0.0,
+        }
+    }
+
+    /// Draw the cylinder as dashes of `dash_length`.
+    pub fn dashed(self, dash_length: f32) -> Self {
+        Self {
+            instance_dash_length: dash_length,
+            ..self
         }
     }
 }
@@ -89,7 +105,8 @@ impl CylinderBatch {
     /// Build the cylinder imposter GLSL Program and return it.
     pub fn build_program(display: &glium::Display<WindowSurface>) -> anyhow::Result<Program> {
         let vertex_shader = fs::read_to_string("./resources/shaders/cylinder_imposter.vert")?;
-        let fragment_shader = fs::read_to_string("./resources/shaders/cylinder_imposter.frag")?;
+        let fragment_shader =
+            shaders::read_shader("cylinder_imposter.frag", &["clipping.glsl", "shadow.glsl"])?;
 
         if vertex_shader.is_empty() || fragment_shader.is_empty() {
             return Err(anyhow::format_err!(
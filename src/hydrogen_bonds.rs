@@ -0,0 +1,298 @@
+use std::{collections::HashSet, ops::Range};
+
+use clap::ValueEnum;
+use nalgebra::Point3;
+use pdbtbx::Element;
+
+use crate::{
+    bonding::{self, BondOrder},
+    spatial_grid::SpatialGrid,
+    structure::Structure,
+};
+
+/// Minimum angle (in degrees) between a heavy neighbour of a donor without explicit hydrogen, the
+/// donor and the acceptor. Smaller angles would put the implicit hydrogen inside a bond.
+const MIN_IMPLICIT_HYDROGEN_ANGLE: f32 = 90.0;
+
+/// Which hydrogen bonds are displayed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HydrogenBondDisplay {
+    /// No hydrogen bond.
+    Off,
+    /// Every hydrogen bond of the structure.
+    All,
+    /// Only the hydrogen bonds involving an atom of the selection.
+    Selection,
+}
+
+impl HydrogenBondDisplay {
+    /// Next display mode, used to cycle through the modes at runtime.
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Off => Self::All,
+            Self::All => Self::Selection,
+            Self::Selection => Self::Off,
+        }
+    }
+}
+
+/// Geometric criteria of a hydrogen bond.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HydrogenBondCriteria {
+    /// Maximum donor to acceptor distance, in Å.
+    pub max_distance: f32,
+    /// Minimum donor, hydrogen, acceptor angle, in degrees.
+    pub min_angle: f32,
+}
+
+impl Default for HydrogenBondCriteria {
+    fn default() -> Self {
+        Self {
+            max_distance: 3.5,
+            min_angle: 120.0,
+        }
+    }
+}
+
+/// A hydrogen bond between two atoms of a structure, `hydrogen` is the hydrogen atom carried by
+/// the donor when the structure has explicit hydrogens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HydrogenBond {
+    pub donor: usize,
+    pub hydrogen: Option<usize>,
+    pub acceptor: usize,
+}
+
+/// Detect the hydrogen bonds between the atoms of `atoms`, a range of atoms of a single model.
+/// `positions` holds the position of every atom of the structure (trajectory frames move them).
+/// When `selection` is given, only the hydrogen bonds with at least one atom selected are kept.
+///
+/// Donors are N, O and S atoms carrying a hydrogen, either explicit or implied by their free
+/// valence when the model has no hydrogen, acceptors are O and F atoms and the N atoms with a
+/// free lone pair. Explicit hydrogens must satisfy the donor, hydrogen, acceptor angle criterion,
+/// implicit ones must have room on the side of the acceptor. Atoms which are bonded or share a
+/// bonded neighbour are never hydrogen bonded.
+pub fn detect_hydrogen_bonds(
+    structure: &Structure,
+    positions: &[Point3<f32>],
+    atoms: Range<usize>,
+    criteria: &HydrogenBondCriteria,
+    selection: Option<&[bool]>,
+) -> Vec<HydrogenBond> {
+    let neighbours = bonding::neighbours(structure.atom_count(), &structure.bonds);
+    let element = |atom: usize| structure.atoms[atom].element;
+    let hydrogens = |atom: usize| {
+        neighbours[atom]
+            .iter()
+            .map(|(neighbour, _)| *neighbour)
+            .filter(move |&neighbour| element(neighbour) == Some(Element::H))
+    };
+    // Models with hydrogens are expected to be complete, implicit hydrogens are only deduced
+    // for models without any.
+    let has_explicit_hydrogens = atoms.clone().any(|atom| element(atom) == Some(Element::H));
+    let implicit_hydrogens = |atom: usize| {
+        if has_explicit_hydrogens {
+            return 0.0;
+        }
+        let valence = match element(atom) {
+            Some(Element::N) => 3.0,
+            Some(Element::O | Element::S) => 2.0,
+            _ => return 0.0,
+        };
+        let used = neighbours[atom]
+            .iter()
+            .map(|(_, order)| match order {
+                BondOrder::Single => 1.0,
+                BondOrder::Aromatic => 1.5,
+                BondOrder::Double => 2.0,
+                BondOrder::Triple => 3.0,
+            })
+            .sum::<f32>();
+        (valence - used).max(0.0)
+    };
+
+    let is_donor = |atom: usize| {
+        matches!(element(atom), Some(Element::N | Element::O | Element::S))
+            && (hydrogens(atom).next().is_some() || implicit_hydrogens(atom) >= 1.0)
+    };
+    let is_acceptor = |atom: usize| match element(atom) {
+        Some(Element::O | Element::F) => true,
+        Some(Element::N) => {
+            hydrogens(atom).next().is_none()
+                && implicit_hydrogens(atom) < 1.0
+                && neighbours[atom].len() <= 2
+        }
+        _ => false,
+    };
+    let is_selected = |atom: usize| selection.is_none_or(|selection| selection[atom]);
+    let are_close_in_graph = |first: usize, second: usize| {
+        neighbours[first].iter().any(|&(neighbour, _)| {
+            neighbour == second
+                || neighbours[neighbour]
+                    .iter()
+                    .any(|&(next, _)| next == second)
+        })
+    };
+
+    let acceptors = atoms
+        .clone()
+        .filter(|&atom| is_acceptor(atom))
+        .collect::<Vec<_>>();
+    let grid = SpatialGrid::new(
+        &acceptors
+            .iter()
+            .map(|&atom| positions[atom])
+            .collect::<Vec<_>>(),
+        criteria.max_distance,
+    );
+
+    let mut seen = HashSet::new();
+    let mut hydrogen_bonds = vec![];
+    for donor in atoms.filter(|&atom| is_donor(atom)) {
+        let donor_position = positions[donor];
+        for acceptor in grid
+            .within(&donor_position, criteria.max_distance)
+            .into_iter()
+            .map(|index| acceptors[index])
+        {
+            if acceptor == donor
+                || (!is_selected(donor) && !is_selected(acceptor))
+                || are_close_in_graph(donor, acceptor)
+                || seen.contains(&(donor.min(acceptor), donor.max(acceptor)))
+            {
+                continue;
+            }
+            let acceptor_position = positions[acceptor];
+
+            let hydrogen = if hydrogens(donor).next().is_some() {
+                let best = hydrogens(donor)
+                    .map(|hydrogen| {
+                        let position = positions[hydrogen];
+                        let angle = (donor_position - position)
+                            .angle(&(acceptor_position - position))
+                            .to_degrees();
+                        (hydrogen, angle)
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+                match best {
+                    Some((hydrogen, angle)) if angle >= criteria.min_angle => Some(hydrogen),
+                    _ => continue,
+                }
+            } else {
+                let has_room = neighbours[donor].iter().all(|&(neighbour, _)| {
+                    let angle = (positions[neighbour] - donor_position)
+                        .angle(&(acceptor_position - donor_position))
+                        .to_degrees();
+                    angle >= MIN_IMPLICIT_HYDROGEN_ANGLE
+                });
+                if !has_room {
+                    continue;
+                }
+                None
+            };
+
+            seen.insert((donor.min(acceptor), donor.max(acceptor)));
+            hydrogen_bonds.push(HydrogenBond {
+                donor,
+                hydrogen,
+                acceptor,
+            });
+        }
+    }
+    hydrogen_bonds
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::{bonding::BondSource, io::LoadOptions};
+
+    fn water_dimer() -> Structure {
+        let mut structure = Structure::load(
+            Path::new("resources/molecules/water_dimer.xyz"),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+        structure.assign_bonds(BondSource::Inferred, 0.45);
+        structure
+    }
+
+    fn detect(
+        structure: &Structure,
+        positions: &[Point3<f32>],
+        max_distance: f32,
+        min_angle: f32,
+        selection: Option<&[bool]>,
+    ) -> Vec<HydrogenBond> {
+        let criteria = HydrogenBondCriteria {
+            max_distance,
+            min_angle,
+        };
+        detect_hydrogen_bonds(
+            structure,
+            positions,
+            0..structure.atom_count(),
+            &criteria,
+            selection,
+        )
+    }
+
+    #[test]
+    fn distance_criterion() {
+        let dimer = water_dimer();
+        let positions = dimer.positions().collect::<Vec<_>>();
+        let expected = HydrogenBond {
+            donor: 0,
+            hydrogen: Some(1),
+            acceptor: 3,
+        };
+        assert_eq!(detect(&dimer, &positions, 3.5, 120.0, None), [expected]);
+        // The oxygens are 2.9 Å apart.
+        assert_eq!(detect(&dimer, &positions, 2.9, 120.0, None), [expected]);
+        assert!(detect(&dimer, &positions, 2.8, 120.0, None).is_empty());
+
+        // Moving the acceptor molecule away.
+        let moved = positions
+            .iter()
+            .enumerate()
+            .map(|(atom, position)| match atom < 3 {
+                true => *position,
+                false => position + Vector3::new(0.7, 0.0, 0.0),
+            })
+            .collect::<Vec<_>>();
+        assert!(detect(&dimer, &moved, 3.5, 120.0, None).is_empty());
+    }
+
+    #[test]
+    fn angle_criterion() {
+        let dimer = water_dimer();
+        let mut positions = dimer.positions().collect::<Vec<_>>();
+        // Tilt the donated hydrogen by 40°, out of the oxygen to oxygen axis.
+        let (sin, cos) = 40f32.to_radians().sin_cos();
+        positions[1] = Point3::new(0.96 * cos, 0.96 * sin, 0.0);
+        let angle = (positions[0] - positions[1])
+            .angle(&(positions[3] - positions[1]))
+            .to_degrees();
+        assert!((120.0..170.0).contains(&angle));
+
+        assert_eq!(detect(&dimer, &positions, 3.5, angle - 0.1, None).len(), 1);
+        assert!(detect(&dimer, &positions, 3.5, angle + 0.1, None).is_empty());
+    }
+
+    #[test]
+    fn selection() {
+        let dimer = water_dimer();
+        let positions = dimer.positions().collect::<Vec<_>>();
+        let acceptor_molecule = [false, false, false, true, true, true];
+        assert_eq!(
+            detect(&dimer, &positions, 3.5, 120.0, Some(&acceptor_molecule)).len(),
+            1
+        );
+        let hydrogens = [false, false, true, false, true, true];
+        assert!(detect(&dimer, &positions, 3.5, 120.0, Some(&hydrogens)).is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use nalgebra::Point3;
+
+use super::trajectory::Trajectory;
+
+/// Size of the Fortran record markers surrounding every block of a DCD file.
+const MARKER_SIZE: u64 = 4;
+
+/// Size of the first header record, `CORD` followed by 20 control integers.
+const HEADER_RECORD_SIZE: u32 = 84;
+
+/// CHARMM/NAMD DCD trajectory reader.
+/// DCD frames have a fixed size, so the frame offsets are computed from the header rather than
+/// scanned. Coordinates are stored in Å.
+pub struct DcdReader {
+    reader: BufReader<File>,
+    big_endian: bool,
+    atom_count: usize,
+    frame_count: usize,
+    first_frame_offset: u64,
+    frame_size: u64,
+    has_unit_cell: bool,
+}
+
+impl DcdReader {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut marker = [0; 4];
+        reader.read_exact(&mut marker)?;
+        let big_endian = if u32::from_le_bytes(marker) == HEADER_RECORD_SIZE {
+            false
+        } else if u32::from_be_bytes(marker) == HEADER_RECORD_SIZE {
+            true
+        } else {
+            return Err(anyhow::format_err!(
+                "`{}` is not a DCD file, or uses 64 bits record markers",
+                path.display()
+            ));
+        };
+
+        let mut dcd = Self {
+            reader,
+            big_endian,
+            atom_count: 0,
+            frame_count: 0,
+            first_frame_offset: 0,
+            frame_size: 0,
+            has_unit_cell: false,
+        };
+
+        let header = dcd.read_bytes(HEADER_RECORD_SIZE as usize)?;
+        dcd.read_u32()?;
+        if &header[0..4] != b"CORD" {
+            return Err(anyhow::format_err!(
+                "`{}` is not a coordinate DCD file",
+                path.display()
+            ));
+        }
+        let control = |index: usize| {
+            let bytes = [
+                header[4 + 4 * index],
+                header[5 + 4 * index],
+                header[6 + 4 * index],
+                header[7 + 4 * index],
+            ];
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+
+        let fixed_atoms = control(8);
+        let is_charmm = control(19) != 0;
+        dcd.has_unit_cell = is_charmm && control(10) != 0;
+        let has_fourth_dimension = is_charmm && control(11) != 0;
+        if fixed_atoms != 0 {
+            return Err(anyhow::format_err!(
+                "`{}` has fixed atoms, which are not supported",
+                path.display()
+            ));
+        }
+
+        // Title record, skipped.
+        let title_size = dcd.read_u32()?;
+        dcd.reader.seek_relative(title_size as i64)?;
+        dcd.read_u32()?;
+
+        // Atom count record.
+        dcd.read_u32()?;
+        dcd.atom_count = dcd.read_u32()? as usize;
+        dcd.read_u32()?;
+
+        dcd.first_frame_offset = dcd.reader.stream_position()?;
+        let coordinate_record = 2 * MARKER_SIZE + 4 * dcd.atom_count as u64;
+        dcd.frame_size = 3 * coordinate_record;
+        if dcd.has_unit_cell {
+            dcd.frame_size += 2 * MARKER_SIZE + 48;
+        }
+        if has_fourth_dimension {
+            dcd.frame_size += coordinate_record;
+        }
+
+        // The frame count of the header is not reliable (unfinished simulations), it is deduced
+        // from the file size instead.
+        dcd.frame_count = file_size
+            .saturating_sub(dcd.first_frame_offset)
+            .checked_div(dcd.frame_size)
+            .unwrap_or_default() as usize;
+
+        Ok(dcd)
+    }
+
+    fn read_bytes(&mut self, count: usize) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = vec![0; count];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> anyhow::Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Read a record of `atom_count` floats.
+    fn read_coordinates(&mut self) -> anyhow::Result<Vec<f32>> {
+        let size = self.read_u32()? as usize;
+        if size != 4 * self.atom_count {
+            return Err(anyhow::format_err!(
+                "corrupted DCD coordinate record of {} bytes, expected {}",
+                size,
+                4 * self.atom_count
+            ));
+        }
+
+        let bytes = self.read_bytes(size)?;
+        self.read_u32()?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| {
+                let chunk = [chunk[0], chunk[1], chunk[2], chunk[3]];
+                if self.big_endian {
+                    f32::from_be_bytes(chunk)
+                } else {
+                    f32::from_le_bytes(chunk)
+                }
+            })
+            .collect())
+    }
+}
+
+impl Trajectory for DcdReader {
+    fn atom_count(&self) -> usize {
+        self.atom_count
+    }
+
+    fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    fn read_frame(&mut self, index: usize) -> anyhow::Result<Vec<Point3<f32>>> {
+        if index >= self.frame_count {
+            return Err(anyhow::format_err!("DCD frame {} does not exist", index));
+        }
+
+        let mut offset = self.first_frame_offset + index as u64 * self.frame_size;
+        if self.has_unit_cell {
+            offset += 2 * MARKER_SIZE + 48;
+        }
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        let x = self.read_coordinates()?;
+        let y = self.read_coordinates()?;
+        let z = self.read_coordinates()?;
+
+        Ok((0..self.atom_count)
+            .map(|atom| Point3::new(x[atom], y[atom], z[atom]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::io::scratch_path;
+
+    /// Write a little endian CHARMM DCD file, with a unit cell record before each frame.
+    fn write_dcd(path: &Path, frames: &[Vec<[f32; 3]>]) {
+        let mut bytes = Vec::new();
+        let mut record = |content: &[u8]| {
+            bytes.extend((content.len() as u32).to_le_bytes());
+            bytes.extend(content);
+            bytes.extend((content.len() as u32).to_le_bytes());
+        };
+
+        let mut header = b"CORD".to_vec();
+        let mut control = [0u32; 20];
+        control[0] = frames.len() as u32;
+        control[10] = 1;
+        control[19] = 24;
+        header.extend(control.iter().flat_map(|value| value.to_le_bytes()));
+        record(&header);
+
+        let mut title = 1u32.to_le_bytes().to_vec();
+        title.extend([b' '; 80]);
+        record(&title);
+        record(&(frames[0].len() as u32).to_le_bytes());
+
+        for frame in frames {
+            record(&[0; 48]);
+            for axis in 0..3 {
+                let coordinates = frame
+                    .iter()
+                    .flat_map(|position| position[axis].to_le_bytes())
+                    .collect::<Vec<_>>();
+                record(&coordinates);
+            }
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn read_frames() {
+        let path = scratch_path("frames.dcd");
+        let frames = (0..3)
+            .map(|frame| {
+                (0..4)
+                    .map(|atom| [frame as f32, atom as f32, -1.5])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        write_dcd(&path, &frames);
+        // A truncated last frame is ignored.
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.extend([0; 20]);
+        fs::write(&path, bytes).unwrap();
+
+        let mut dcd = DcdReader::open(&path).unwrap();
+        assert_eq!(dcd.atom_count(), 4);
+        assert_eq!(dcd.frame_count(), 3);
+        let frame = dcd.read_frame(2).unwrap();
+        assert_eq!(frame[3], Point3::new(2.0, 3.0, -1.5));
+        assert_eq!(dcd.read_frame(0).unwrap()[1], Point3::new(0.0, 1.0, -1.5));
+        assert!(dcd.read_frame(3).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn not_a_dcd() {
+        let path = scratch_path("invalid.dcd");
+        fs::write(&path, [0; 64]).unwrap();
+        assert!(DcdReader::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Environment variable holding the root of the local PDB mirror, when `--mirror` is not given.
+pub const MIRROR_ENV: &str = "MOLVIZ_PDB_MIRROR";
+
+/// Sub folders of the mirror root holding the divided mmCIF and PDB archives, as laid out by the
+/// RCSB rsync mirrors. Mirrors with both formats in the same divided tree are supported too.
+const MMCIF_FOLDERS: [&str; 2] = ["mmCIF", ""];
+const PDB_FOLDERS: [&str; 2] = ["pdb", ""];
+
+/// A four characters PDB identifier, stored in lower case as in the archive file names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdbId(String);
+
+impl FromStr for PdbId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.trim().to_ascii_lowercase();
+        let is_valid = id.len() == 4
+            && id.starts_with(|c: char| c.is_ascii_digit() && c != '0')
+            && id.chars().all(|c| c.is_ascii_alphanumeric());
+
+        if is_valid {
+            Ok(Self(id))
+        } else {
+            Err(format!(
+                "invalid PDB ID `{}`, expected a digit followed by 3 letters or digits (1ABC)",
+                s
+            ))
+        }
+    }
+}
+
+impl fmt::Display for PdbId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.to_ascii_uppercase())
+    }
+}
+
+impl PdbId {
+    /// Folder of the divided layout holding the entry, made of the two middle characters of the
+    /// ID (`xy` for `1xyz`).
+    fn divided_folder(&self) -> &str {
+        &self.0[1..3]
+    }
+
+    /// Candidate paths of the entry inside the mirror, mmCIF files first.
+    fn candidates(&self, root: &Path) -> Vec<PathBuf> {
+        let archives = [
+            (
+                MMCIF_FOLDERS,
+                [format!("{}.cif.gz", self.0), format!("{}.cif", self.0)],
+            ),
+            (
+                PDB_FOLDERS,
+                [
+                    format!("pdb{}.ent.gz", self.0),
+                    format!("pdb{}.ent", self.0),
+                ],
+            ),
+        ];
+
+        let mut candidates = vec![];
+        for (folders, names) in &archives {
+            for folder in folders {
+                for name in names {
+                    candidates.push(root.join(folder).join(self.divided_folder()).join(name));
+                }
+            }
+        }
+        candidates
+    }
+}
+
+/// Find the file of a PDB entry in a local mirror using the divided layout
+/// (`xy/1xyz.cif.gz`, `xy/pdb1xyz.ent.gz`), mmCIF files being preferred over PDB ones.
+pub fn find_entry(root: Option<&Path>, id: &PdbId) -> anyhow::Result<PathBuf> {
+    let root = root.ok_or_else(|| {
+        anyhow::format_err!(
+            "Looking up `{}` requires a PDB mirror, use `--mirror <DIR>` or set {}",
+            id,
+            MIRROR_ENV
+        )
+    })?;
+    if !root.is_dir() {
+        return Err(anyhow::format_err!(
+            "The PDB mirror `{}` is not a directory",
+            root.display()
+        ));
+    }
+
+    let candidates = id.candidates(root);
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::format_err!(
+                "`{}` was not found in the PDB mirror, looked for:\n{}",
+                id,
+                candidates
+                    .iter()
+                    .map(|candidate| format!("  {}", candidate.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::io::scratch_path;
+
+    #[test]
+    fn parse_id() {
+        let id = "1ABc".parse::<PdbId>().unwrap();
+        assert_eq!(id, PdbId("1abc".to_string()));
+        assert_eq!(id.to_string(), "1ABC");
+        assert_eq!(" 4hhb ".parse(), Ok(PdbId("4hhb".to_string())));
+
+        for invalid in ["0abc", "abcd", "1abcd", "1ab", "1a-c", ""] {
+            assert!(invalid.parse::<PdbId>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn divided_layout() {
+        let id = "1XYZ".parse::<PdbId>().unwrap();
+        let root = Path::new("/mirror");
+        let candidates = id.candidates(root);
+        assert_eq!(candidates.len(), 8);
+        assert_eq!(candidates[0], root.join("mmCIF/xy/1xyz.cif.gz"));
+        assert_eq!(candidates[1], root.join("mmCIF/xy/1xyz.cif"));
+        assert_eq!(candidates[2], root.join("xy/1xyz.cif.gz"));
+        assert_eq!(candidates[4], root.join("pdb/xy/pdb1xyz.ent.gz"));
+        assert_eq!(candidates[7], root.join("xy/pdb1xyz.ent"));
+    }
+
+    #[test]
+    fn find_in_mirror() {
+        let root = scratch_path("mirror");
+        let id = "1xyz".parse::<PdbId>().unwrap();
+        assert!(find_entry(None, &id).is_err());
+        assert!(find_entry(Some(&root), &id)
+            .unwrap_err()
+            .to_string()
+            .contains("is not a directory"));
+
+        fs::create_dir_all(root.join("pdb/xy")).unwrap();
+        let error = find_entry(Some(&root), &id).unwrap_err().to_string();
+        assert!(error.contains("`1XYZ` was not found"));
+
+        let pdb = root.join("pdb/xy/pdb1xyz.ent.gz");
+        fs::write(&pdb, "").unwrap();
+        assert_eq!(find_entry(Some(&root), &id).unwrap(), pdb);
+
+        // mmCIF files are preferred, in either layout.
+        fs::create_dir_all(root.join("xy")).unwrap();
+        let cif = root.join("xy/1xyz.cif");
+        fs::write(&cif, "").unwrap();
+        assert_eq!(find_entry(Some(&root), &id).unwrap(), cif);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
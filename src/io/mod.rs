@@ -0,0 +1,384 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use flate2::read::MultiGzDecoder;
+use pdbtbx::{Atom, Chain, Conformer, Model, Residue, PDB};
+
+use crate::{bonding::Bond, io::mirror::PdbId, secondary_structure::SecondaryStructureRange};
+
+pub mod dcd;
+pub mod mirror;
+pub mod mol2;
+pub mod pdb;
+pub mod sdf;
+pub mod trajectory;
+pub mod xtc;
+pub mod xyz;
+
+/// Number of lines inspected when the format can't be deduced from the file extension.
+const SNIFFED_LINES: usize = 64;
+
+/// First bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Structure file formats the application is able to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructureFormat {
+    Pdb,
+    Mmcif,
+    Xyz,
+    Sdf,
+    Mol2,
+}
+
+/// A parsed structure file, free of any GPU related data.
+/// `bonds` only contains the bonds explicitly written in the file, the atoms are referenced by
+/// their index in the `pdb.atoms()` iteration order. `links` holds the bonds between residues
+/// declared by the PDB `SSBOND` and `LINK` records, and `secondary_structure` the helices and
+/// strands of its `HELIX` and `SHEET` records.
+/// `properties` holds the free form key/value properties of each model, when the format has some
+/// (extended XYZ comment line for example).
+/// `atom_properties` is either empty, or holds one entry per atom in the `pdb.atoms()` iteration
+/// order.
+#[derive(Debug)]
+pub struct LoadedStructure {
+    pub pdb: PDB,
+    pub bonds: Vec<Bond>,
+    pub links: Vec<Bond>,
+    pub secondary_structure: Vec<SecondaryStructureRange>,
+    pub format: StructureFormat,
+    pub properties: Vec<BTreeMap<String, String>>,
+    pub atom_properties: Vec<AtomProperties>,
+}
+
+/// Per atom data read from the file which does not fit in a `pdbtbx::Atom`.
+#[derive(Debug, Clone, Default)]
+pub struct AtomProperties {
+    /// Mass number of the atom, when it is not the natural isotope mixture.
+    pub isotope: Option<u16>,
+    /// Partial charge of the atom, in elementary charge unit.
+    pub partial_charge: Option<f32>,
+    /// Force field atom type (Sybyl type for MOL2 files, `C.ar`, `N.am`, ...).
+    pub atom_type: Option<String>,
+}
+
+/// Select a record of a multi-record file, either by its index (starting at 1) or by its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for RecordSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("record indices start at 1".to_string()),
+            Ok(index) => Ok(Self::Index(index)),
+            Err(_) => Ok(Self::Name(s.to_string())),
+        }
+    }
+}
+
+/// Options driving how a structure file is read.
+/// `record` selects a record of a multi-record file (SDF), the first one is used when unset.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub record: Option<RecordSelector>,
+}
+
+impl StructureFormat {
+    /// Whether the bonds of the structure are read from the file.
+    /// Structures in a format without connectivity always have their bonds inferred.
+    pub fn has_connectivity(&self) -> bool {
+        match self {
+            Self::Pdb | Self::Sdf | Self::Mol2 => true,
+            Self::Mmcif | Self::Xyz => false,
+        }
+    }
+
+    /// Whether the file lists every bond of the structure. PDB files only write the `CONECT`
+    /// records of the hetero groups, the bonds of the standard residues come from their templates.
+    pub fn has_complete_connectivity(&self) -> bool {
+        match self {
+            Self::Sdf | Self::Mol2 => true,
+            Self::Pdb | Self::Mmcif | Self::Xyz => false,
+        }
+    }
+
+    /// Whether the format writes the order of its bonds. PDB files only do it for the bonds
+    /// repeated in the `CONECT` records, which is checked on the bonds themselves.
+    pub fn has_bond_orders(&self) -> bool {
+        match self {
+            Self::Sdf | Self::Mol2 => true,
+            Self::Pdb | Self::Mmcif | Self::Xyz => false,
+        }
+    }
+
+    /// Detect the format of a structure file, from its extension when it is a known one, and from
+    /// its content otherwise.
+    pub fn detect(path: &Path) -> anyhow::Result<Self> {
+        if let Some(format) = Self::from_extension(path) {
+            return Ok(format);
+        }
+
+        let lines = open_file(path)?
+            .lines()
+            .take(SNIFFED_LINES)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::from_content(&lines).ok_or_else(|| {
+            anyhow::format_err!(
+                "Could not determine the format of `{}`, expected a PDB, mmCIF/PDBx, XYZ, SDF/MOL or MOL2 file",
+                path.display()
+            )
+        })
+    }
+
+    /// The `.gz` extension of compressed files is skipped (`1abc.pdb.gz` is a PDB file).
+    fn from_extension(path: &Path) -> Option<Self> {
+        let mut extension = path.extension().and_then(OsStr::to_str)?;
+        if extension.eq_ignore_ascii_case("gz") {
+            extension = Path::new(path.file_stem()?)
+                .extension()
+                .and_then(OsStr::to_str)?;
+        }
+        match extension.to_ascii_lowercase().as_str() {
+            "pdb" | "pdb1" | "ent" => Some(Self::Pdb),
+            "cif" | "mmcif" => Some(Self::Mmcif),
+            "xyz" | "extxyz" => Some(Self::Xyz),
+            "sdf" | "sd" | "mol" => Some(Self::Sdf),
+            "mol2" => Some(Self::Mol2),
+            _ => None,
+        }
+    }
+
+    fn from_content(lines: &[String]) -> Option<Self> {
+        const PDB_RECORDS: [&str; 8] = [
+            "HEADER", "REMARK", "CRYST1", "MODEL", "ATOM", "HETATM", "COMPND", "TITLE",
+        ];
+
+        if lines.iter().any(|line| line.starts_with("@<TRIPOS>")) {
+            return Some(Self::Mol2);
+        }
+
+        // MDL files have a counts line ending with the connection table version as fourth line.
+        if lines.get(3).is_some_and(|line| {
+            let line = line.trim_end();
+            line.ends_with("V2000") || line.ends_with("V3000")
+        }) {
+            return Some(Self::Sdf);
+        }
+
+        // XYZ files start with the atom count of their first frame.
+        let first_line = lines.iter().find(|line| !line.trim().is_empty())?;
+        if first_line.trim().parse::<usize>().is_ok() {
+            return Some(Self::Xyz);
+        }
+
+        for line in lines.iter().map(|line| line.trim_end()) {
+            if line.starts_with("data_") || line.starts_with("loop_") || line.starts_with("_atom") {
+                return Some(Self::Mmcif);
+            }
+            if PDB_RECORDS.iter().any(|record| line.starts_with(record)) {
+                return Some(Self::Pdb);
+            }
+        }
+        None
+    }
+}
+
+/// Resolve the path of a structure file given on the command line, either a file path or a PDB ID
+/// looked up in the `mirror` directory.
+pub fn resolve_path(
+    file: Option<&str>,
+    id: Option<&PdbId>,
+    mirror: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+    if let Some(id) = id {
+        return mirror::find_entry(mirror, id);
+    }
+
+    let file = file.ok_or_else(|| anyhow::format_err!("No structure file given"))?;
+    let path = PathBuf::from(file);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(anyhow::format_err!("No such structure file: `{}`", file))
+    }
+}
+
+/// Open a structure file for reading, gzip compressed files are detected from their first bytes and
+/// decompressed on the fly.
+pub fn open_file(path: &Path) -> anyhow::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Detect the format and parse the given structure file.
+pub fn load_structure(path: &Path, options: &LoadOptions) -> anyhow::Result<LoadedStructure> {
+    let format = StructureFormat::detect(path)?;
+
+    match format {
+        StructureFormat::Pdb | StructureFormat::Mmcif => pdb::read(path, format),
+        StructureFormat::Xyz => xyz::read(path),
+        StructureFormat::Sdf => sdf::read(path, options.record.as_ref()),
+        StructureFormat::Mol2 => mol2::read(path),
+    }
+}
+
+/// Build a model holding a single residue in a single chain, used by the small molecules formats
+/// which have no hierarchy. Return `None` if the residue name is not a valid identifier.
+pub(crate) fn single_residue_model(
+    serial_number: usize,
+    residue_name: &str,
+    atoms: Vec<Atom>,
+) -> Option<Model> {
+    let mut conformer = Conformer::new(residue_name, None, None)?;
+    for atom in atoms {
+        conformer.add_atom(atom);
+    }
+
+    let mut chain = Chain::new("A")?;
+    chain.add_residue(Residue::new(1, None, Some(conformer))?);
+
+    let mut model = Model::new(serial_number);
+    model.add_chain(chain);
+    Some(model)
+}
+
+/// Path of a scratch file for the tests, unique to the test process.
+#[cfg(test)]
+pub(crate) fn scratch_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "molecular_visualization_{}_{}",
+        std::process::id(),
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn fixture_lines(path: &str) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn format_from_extension() {
+        let format = |path: &str| StructureFormat::from_extension(Path::new(path));
+        assert_eq!(format("1abc.pdb"), Some(StructureFormat::Pdb));
+        assert_eq!(format("1abc.pdb.gz"), Some(StructureFormat::Pdb));
+        assert_eq!(format("1ABC.CIF.GZ"), Some(StructureFormat::Mmcif));
+        assert_eq!(format("frames.extxyz"), Some(StructureFormat::Xyz));
+        assert_eq!(format("ligand.sd"), Some(StructureFormat::Sdf));
+        assert_eq!(format("ligand.mol2"), Some(StructureFormat::Mol2));
+        assert_eq!(format("structure.txt"), None);
+        assert_eq!(format("structure.gz"), None);
+    }
+
+    #[test]
+    fn format_from_content() {
+        let format = |path: &str| StructureFormat::from_content(&fixture_lines(path));
+        assert_eq!(
+            format("resources/pdb/ethylene.pdb"),
+            Some(StructureFormat::Pdb)
+        );
+        assert_eq!(
+            format("resources/molecules/ethylene.cif"),
+            Some(StructureFormat::Mmcif)
+        );
+        assert_eq!(
+            format("resources/molecules/water.xyz"),
+            Some(StructureFormat::Xyz)
+        );
+        assert_eq!(
+            format("resources/molecules/ligands.sdf"),
+            Some(StructureFormat::Sdf)
+        );
+        assert_eq!(
+            format("resources/molecules/benzene.mol"),
+            Some(StructureFormat::Sdf)
+        );
+        assert_eq!(
+            format("resources/molecules/ethanol.mol2"),
+            Some(StructureFormat::Mol2)
+        );
+        assert_eq!(
+            StructureFormat::from_content(&["not a structure".to_string()]),
+            None
+        );
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        for fixture in [
+            "resources/pdb/ethylene.pdb",
+            "resources/molecules/water.xyz",
+            "resources/molecules/ligands.sdf",
+            "resources/molecules/ethanol.mol2",
+        ] {
+            let path = Path::new(fixture);
+            let compressed_path = scratch_path(&format!(
+                "{}.gz",
+                path.file_name().unwrap().to_str().unwrap()
+            ));
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&fs::read(path).unwrap()).unwrap();
+            fs::write(&compressed_path, encoder.finish().unwrap()).unwrap();
+
+            let mut content = String::new();
+            open_file(&compressed_path)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, fs::read_to_string(path).unwrap());
+
+            let options = LoadOptions::default();
+            let plain = load_structure(path, &options).unwrap();
+            let compressed = load_structure(&compressed_path, &options).unwrap();
+            fs::remove_file(&compressed_path).unwrap();
+            assert_eq!(compressed.format, plain.format);
+            assert_eq!(compressed.bonds, plain.bonds);
+            assert_eq!(compressed.properties, plain.properties);
+            let coordinates = |structure: &LoadedStructure| {
+                structure
+                    .pdb
+                    .atoms()
+                    .map(|atom| (atom.name().to_string(), atom.pos()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(coordinates(&compressed), coordinates(&plain));
+        }
+    }
+
+    #[test]
+    fn record_selector() {
+        assert_eq!("2".parse(), Ok(RecordSelector::Index(2)));
+        assert_eq!(
+            "aspirin".parse(),
+            Ok(RecordSelector::Name("aspirin".to_string()))
+        );
+        assert!("0".parse::<RecordSelector>().is_err());
+    }
+}
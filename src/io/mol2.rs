@@ -0,0 +1,340 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::BufRead,
+    path::Path,
+};
+
+use pdbtbx::{Atom, Element, Model, PDB};
+
+use crate::bonding::{Bond, BondOrder};
+
+use super::{open_file, AtomProperties, LoadedStructure, StructureFormat};
+
+/// Prefix of the record type indicator lines splitting a MOL2 file in sections.
+const RECORD_PREFIX: &str = "@<TRIPOS>";
+
+/// Chain used for the atoms whose substructure does not define one.
+const DEFAULT_CHAIN: &str = "A";
+
+/// Residue name used for the atoms without substructure.
+const DEFAULT_RESIDUE_NAME: &str = "MOL";
+
+/// Sybyl types of the lone pairs and dummy atoms, which are not actual atoms.
+const PSEUDO_ATOM_TYPES: [&str; 2] = ["LP", "Du"];
+
+/// Generic Sybyl types (any atom, halogen, heteroatom, heavy atom), which don't tell the element.
+const GENERIC_ATOM_TYPES: [&str; 4] = ["Any", "Hal", "Het", "Hev"];
+
+/// Read the first molecule of a Tripos MOL2 file.
+/// Atoms are grouped in residues and chains from their substructure, and their Sybyl type and
+/// partial charge are kept in `LoadedStructure::atom_properties`. Lone pairs and dummy atoms are
+/// skipped, along with their bonds.
+pub fn read(path: &Path) -> anyhow::Result<LoadedStructure> {
+    let mut sections: HashMap<String, Vec<String>> = HashMap::new();
+    let mut current = None;
+
+    for line in open_file(path)?.lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if let Some(record) = trimmed.strip_prefix(RECORD_PREFIX) {
+            // Only the first molecule of a multi-molecule file is read.
+            if record == "MOLECULE" && sections.contains_key("MOLECULE") {
+                break;
+            }
+            current = Some(record.to_string());
+            sections.entry(record.to_string()).or_default();
+        } else if let Some(record) = &current {
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                sections.entry(record.clone()).or_default().push(line);
+            }
+        }
+    }
+
+    let section = |name: &str| sections.get(name).map(Vec::as_slice).unwrap_or(&[]);
+
+    let atom_lines = section("ATOM");
+    if atom_lines.is_empty() {
+        return Err(anyhow::format_err!(
+            "`{}` does not contain any @<TRIPOS>ATOM record",
+            path.display()
+        ));
+    }
+
+    let chains = parse_substructure_chains(section("SUBSTRUCTURE"));
+    let mut model = Model::new(1);
+    let mut atom_ids = HashMap::new();
+    let mut pseudo_atom_ids = HashSet::new();
+    let mut atom_properties = Vec::with_capacity(atom_lines.len());
+
+    for (line_index, line) in atom_lines.iter().enumerate() {
+        let atom = parse_atom(line).map_err(|error| {
+            anyhow::format_err!("atom {}: {} in `{}`", line_index + 1, error, line.trim())
+        })?;
+        if PSEUDO_ATOM_TYPES.contains(&atom.sybyl_type.split('.').next().unwrap_or_default()) {
+            pseudo_atom_ids.insert(atom.id);
+            continue;
+        }
+        let index = atom_properties.len();
+        let chain = chains
+            .get(&atom.substructure_id)
+            .map_or(DEFAULT_CHAIN, String::as_str);
+
+        atom_ids.insert(atom.id, index);
+        atom_properties.push(AtomProperties {
+            partial_charge: atom.partial_charge,
+            atom_type: Some(atom.sybyl_type),
+            ..Default::default()
+        });
+        model.add_atom(
+            atom.atom,
+            chain,
+            (atom.substructure_id as isize, None),
+            (atom.residue_name.as_str(), None),
+        );
+    }
+
+    let mut bonds = Vec::new();
+    for line in section("BOND") {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 4 {
+            return Err(anyhow::format_err!("invalid bond `{}`", line.trim()));
+        }
+        let is_pseudo_atom = |id: &str| {
+            id.parse::<usize>()
+                .is_ok_and(|id| pseudo_atom_ids.contains(&id))
+        };
+        if is_pseudo_atom(fields[1]) || is_pseudo_atom(fields[2]) {
+            continue;
+        }
+        let atom = |id: &str| {
+            id.parse::<usize>()
+                .ok()
+                .and_then(|id| atom_ids.get(&id).copied())
+                .ok_or_else(|| anyhow::format_err!("bond to an unknown atom `{}`", id))
+        };
+        bonds.push(Bond::with_order(
+            atom(fields[1])?,
+            atom(fields[2])?,
+            bond_order(fields[3]),
+        ));
+    }
+
+    let mut properties = BTreeMap::new();
+    if let Some(name) = section("MOLECULE").first() {
+        properties.insert("name".to_string(), name.trim().to_string());
+    }
+
+    // `Model::add_atom` creates the residues in the order they are first seen, which is not
+    // always the atoms order, the atom properties are reordered to follow `pdb.atoms()`.
+    let order = model
+        .atoms()
+        .map(|atom| atom_ids[&atom.serial_number()])
+        .collect::<Vec<_>>();
+    let remap = order
+        .iter()
+        .enumerate()
+        .map(|(new, &old)| (old, new))
+        .collect::<HashMap<_, _>>();
+    let atom_properties = order
+        .iter()
+        .map(|&old| atom_properties[old].clone())
+        .collect();
+    let bonds = bonds
+        .into_iter()
+        .map(|bond| Bond::with_order(remap[&bond.first], remap[&bond.second], bond.order))
+        .collect();
+
+    let mut pdb = PDB::new();
+    pdb.add_model(model);
+
+    Ok(LoadedStructure {
+        pdb,
+        bonds,
+        links: vec![],
+        secondary_structure: vec![],
+        format: StructureFormat::Mol2,
+        properties: vec![properties],
+        atom_properties,
+    })
+}
+
+/// An atom line of the `@<TRIPOS>ATOM` section.
+struct Mol2Atom {
+    id: usize,
+    atom: Atom,
+    sybyl_type: String,
+    substructure_id: usize,
+    residue_name: String,
+    partial_charge: Option<f32>,
+}
+
+/// Parse `atom_id atom_name x y z atom_type [subst_id [subst_name [charge [status_bit]]]]`.
+fn parse_atom(line: &str) -> anyhow::Result<Mol2Atom> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    if fields.len() < 6 {
+        return Err(anyhow::format_err!("missing fields"));
+    }
+
+    let id = fields[0]
+        .parse::<usize>()
+        .map_err(|_| anyhow::format_err!("invalid atom id `{}`", fields[0]))?;
+    let mut coordinates = [0.0; 3];
+    for (axis, coordinate) in coordinates.iter_mut().enumerate() {
+        *coordinate = fields[2 + axis]
+            .parse::<f64>()
+            .map_err(|_| anyhow::format_err!("invalid coordinate `{}`", fields[2 + axis]))?;
+    }
+
+    // The element is the part of the Sybyl type before the dot (`C.ar`, `N.am`, `Cl`, ...).
+    // Generic and pseudo atom types don't give one, their atoms have no element.
+    let sybyl_type = fields[5].to_string();
+    let symbol = sybyl_type.split('.').next().unwrap_or_default();
+    let element = if GENERIC_ATOM_TYPES.contains(&symbol) || PSEUDO_ATOM_TYPES.contains(&symbol) {
+        None
+    } else {
+        Some(
+            Element::from_symbol(symbol)
+                .ok_or_else(|| anyhow::format_err!("unsupported atom type `{}`", sybyl_type))?,
+        )
+    };
+
+    let substructure_id = fields
+        .get(6)
+        .and_then(|id| id.parse::<usize>().ok())
+        .unwrap_or(1);
+    let residue_name = fields
+        .get(7)
+        .map(|name| residue_name(name))
+        .unwrap_or_else(|| DEFAULT_RESIDUE_NAME.to_string());
+    let partial_charge = fields.get(8).and_then(|charge| charge.parse::<f32>().ok());
+
+    // pdbtbx guesses a missing element from the atom name (`CL1` being a carbon), the atom is
+    // created without name in that case and named afterwards.
+    let invalid_name = || anyhow::format_err!("invalid atom name `{}`", fields[1]);
+    let mut atom = Atom::new(
+        true,
+        id,
+        element.map_or("", |_| fields[1]),
+        coordinates[0],
+        coordinates[1],
+        coordinates[2],
+        1.0,
+        0.0,
+        element.as_ref().map_or("", Element::symbol),
+        0,
+    )
+    .ok_or_else(invalid_name)?;
+    if element.is_none() {
+        atom.set_name(fields[1]).map_err(|_| invalid_name())?;
+    }
+
+    Ok(Mol2Atom {
+        id,
+        atom,
+        sybyl_type,
+        substructure_id,
+        residue_name,
+        partial_charge,
+    })
+}
+
+/// Substructure names usually carry the residue number (`ALA12`), only the name is kept.
+fn residue_name(substructure_name: &str) -> String {
+    let name = substructure_name.trim_end_matches(|c: char| c.is_ascii_digit());
+    if name.is_empty() || substructure_name == "****" {
+        DEFAULT_RESIDUE_NAME.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Read the chain of every substructure, from the lines
+/// `subst_id subst_name root_atom [subst_type [dict_type [chain ...]]]`.
+fn parse_substructure_chains(lines: &[String]) -> HashMap<usize, String> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let id = fields.first()?.parse::<usize>().ok()?;
+            let chain = fields.get(5).filter(|chain| **chain != "****")?;
+            Some((id, chain.to_string()))
+        })
+        .collect()
+}
+
+fn bond_order(bond_type: &str) -> BondOrder {
+    match bond_type {
+        "2" => BondOrder::Double,
+        "3" => BondOrder::Triple,
+        "ar" => BondOrder::Aromatic,
+        // Amide bonds are single bonds with a partial double bond character, dummy, unknown and
+        // not connected bonds have no better representation.
+        _ => BondOrder::Single,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_substructures() {
+        let ethanol = read(Path::new("resources/molecules/ethanol.mol2")).unwrap();
+        assert_eq!(ethanol.properties[0]["name"], "ethanol");
+
+        // The lone pair is skipped, and the water atoms follow the ethanol ones.
+        let atoms = ethanol.pdb.atoms().collect::<Vec<_>>();
+        let names = atoms.iter().map(|atom| atom.name()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["C1", "C2", "O1", "H1", "H2", "H3", "H4", "H5", "HO", "OW", "HW1", "HW2"]
+        );
+        assert_eq!(atoms[2].pos(), (2.0, 1.34, 0.0));
+        assert_eq!(atoms[9].element(), Some(&Element::O));
+
+        let chains = ethanol
+            .pdb
+            .chains()
+            .map(|chain| (chain.id(), chain.residues().next().unwrap().name()))
+            .collect::<Vec<_>>();
+        assert_eq!(chains, [("A", Some("ETH")), ("B", Some("HOH"))]);
+
+        // Atom properties follow the atoms order.
+        let property = |index: usize| &ethanol.atom_properties[index];
+        assert_eq!(property(2).atom_type.as_deref(), Some("O.3"));
+        assert_eq!(property(2).partial_charge, Some(-0.683));
+        assert_eq!(property(8).partial_charge, Some(0.418));
+        assert_eq!(property(9).partial_charge, Some(-0.834));
+        assert_eq!(ethanol.atom_properties.len(), 12);
+
+        // The bond to the lone pair is dropped, the other ones follow the atoms order.
+        assert_eq!(ethanol.bonds.len(), 10);
+        assert!(ethanol.bonds.contains(&Bond::new(2, 8)));
+        assert!(ethanol.bonds.contains(&Bond::new(9, 11)));
+    }
+
+    #[test]
+    fn atom_types() {
+        let atom = |atom_type: &str| {
+            parse_atom(&format!("1 X1 0.0 0.0 0.0 {atom_type} 1 LIG1 0.25")).unwrap()
+        };
+        assert_eq!(atom("C.ar").atom.element(), Some(&Element::C));
+        assert_eq!(atom("Cl").atom.element(), Some(&Element::Cl));
+        assert_eq!(atom("N.am").residue_name, "LIG");
+        assert_eq!(atom("N.am").partial_charge, Some(0.25));
+        for generic in ["Any", "Hal", "Het", "Hev", "LP", "Du", "Du.C"] {
+            let atom = atom(generic);
+            assert_eq!(atom.atom.element(), None, "{generic}");
+            assert_eq!(atom.atom.name(), "X1");
+        }
+        assert!(parse_atom("1 X1 0.0 0.0 0.0 Xx 1 LIG1").is_err());
+        assert!(parse_atom("1 X1 0.0 0.0").is_err());
+    }
+
+    #[test]
+    fn substructure_names() {
+        assert_eq!(residue_name("ALA12"), "ALA");
+        assert_eq!(residue_name("****"), DEFAULT_RESIDUE_NAME);
+        assert_eq!(residue_name("123"), DEFAULT_RESIDUE_NAME);
+    }
+}
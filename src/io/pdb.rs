@@ -0,0 +1,403 @@
+use std::{
+    collections::HashMap,
+    io::{BufReader, Read},
+    panic,
+    path::Path,
+};
+
+use pdbtbx::{Format, PDBError, ReadOptions, PDB};
+
+use crate::{
+    bonding::{Bond, BondOrder},
+    secondary_structure::{SecondaryStructure, SecondaryStructureRange},
+};
+
+use super::{open_file, LoadedStructure, StructureFormat};
+
+/// Read a PDB or mmCIF/PDBx file through `pdbtbx`.
+/// Breaking errors are turned into a single readable error, and the non breaking ones are
+/// reported on the standard error output.
+pub fn read(path: &Path, format: StructureFormat) -> anyhow::Result<LoadedStructure> {
+    let filename = path
+        .to_str()
+        .ok_or_else(|| anyhow::format_err!("`{}` is not a valid UTF-8 path", path.display()))?;
+
+    let pdbtbx_format = match format {
+        StructureFormat::Mmcif => Format::Mmcif,
+        _ => Format::Pdb,
+    };
+
+    // The file is read (and decompressed) once, then handed to pdbtbx as a stream and to the
+    // parsers of the records pdbtbx does not keep.
+    // pdbtbx can panic on some malformed records instead of reporting an error, the panic is
+    // caught so that a bad file never takes the application down.
+    let mut content = String::new();
+    open_file(path)?.read_to_string(&mut content)?;
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        ReadOptions::new()
+            .set_format(pdbtbx_format)
+            .read_raw(BufReader::new(content.as_bytes()))
+    }))
+    .map_err(|_| anyhow::format_err!("Failed to read `{}`: the file is malformed", filename))?;
+
+    let (pdb, warnings) = result.map_err(|errors| {
+        anyhow::format_err!(
+            "Failed to read `{}`:\n{}",
+            filename,
+            format_diagnostics(&errors)
+        )
+    })?;
+
+    if !warnings.is_empty() {
+        eprintln!(
+            "`{}` was read with warnings:\n{}",
+            filename,
+            format_diagnostics(&warnings)
+        );
+    }
+
+    let (bonds, links, secondary_structure) = match format {
+        StructureFormat::Pdb => (
+            resolve_conect_records(&parse_bonds(&content), &pdb),
+            resolve_link_records(&parse_links(&content), &pdb),
+            parse_secondary_structure(&content),
+        ),
+        _ => (vec![], vec![], vec![]),
+    };
+
+    Ok(LoadedStructure {
+        pdb,
+        bonds,
+        links,
+        secondary_structure,
+        format,
+        properties: vec![],
+        atom_properties: vec![],
+    })
+}
+
+fn format_diagnostics(errors: &[PDBError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.to_string().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Represents a CONECT record from a PDB file
+#[derive(Debug, Clone)]
+struct ConectRecord {
+    source_atom: usize,
+    bonded_atoms: Vec<usize>,
+}
+
+impl ConectRecord {
+    /// Parse a CONECT line into a ConectRecord
+    fn from_line(line: &str) -> Option<Self> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        // CONECT records must have at least 2 numbers
+        if parts.len() < 3 || !parts[0].starts_with("CONECT") {
+            return None;
+        }
+
+        // Parse source atom
+        let source_atom = parts[1].parse().ok()?;
+
+        // Parse bonded atoms
+        let bonded_atoms = parts[2..]
+            .iter()
+            .filter_map(|s| s.parse().ok())
+            .filter(|&num| num != source_atom) // Avoid self-bonds
+            .collect();
+
+        Some(ConectRecord {
+            source_atom,
+            bonded_atoms,
+        })
+    }
+}
+
+fn parse_bonds(content: &str) -> Vec<ConectRecord> {
+    content
+        .lines()
+        .filter_map(ConectRecord::from_line)
+        .collect()
+}
+
+/// Convert the `CONECT` records, which reference atoms by serial number, into bonds between
+/// atom indices. Serial numbers are only unique within a model, so the records are applied to
+/// each model separately. Records pointing to unknown atoms are ignored.
+/// A bond written several times from the same atom encodes its order (twice for a double bond,
+/// three times for a triple bond), the order of a bond is the highest of both directions.
+fn resolve_conect_records(records: &[ConectRecord], pdb: &PDB) -> Vec<Bond> {
+    let mut bonds = vec![];
+    let mut offset = 0;
+
+    for model in pdb.models() {
+        let atom_map = model
+            .atoms()
+            .enumerate()
+            .map(|(index, atom)| (atom.serial_number(), offset + index))
+            .collect::<HashMap<_, _>>();
+
+        let mut multiplicities = HashMap::new();
+        let mut pairs = vec![];
+        for record in records {
+            let Some(&start) = atom_map.get(&record.source_atom) else {
+                continue;
+            };
+
+            for connected in &record.bonded_atoms {
+                let Some(&end) = atom_map.get(connected) else {
+                    continue;
+                };
+
+                let multiplicity = multiplicities.entry((start, end)).or_insert(0);
+                *multiplicity += 1;
+                let pair = (start.min(end), start.max(end));
+                if *multiplicity == 1 && !multiplicities.contains_key(&(end, start)) {
+                    pairs.push(pair);
+                }
+            }
+        }
+
+        bonds.extend(pairs.into_iter().map(|(first, second)| {
+            let multiplicity = [(first, second), (second, first)]
+                .iter()
+                .filter_map(|pair| multiplicities.get(pair))
+                .max()
+                .copied()
+                .unwrap_or_default();
+            Bond::with_order(first, second, BondOrder::from_multiplicity(multiplicity))
+        }));
+        offset += model.atom_count();
+    }
+    bonds
+}
+
+/// Atom of a `SSBOND` or `LINK` record, referenced by its name and the identifier of its residue.
+#[derive(Debug, Clone)]
+struct LinkedAtom {
+    name: String,
+    chain_id: String,
+    residue_serial_number: isize,
+    insertion_code: Option<String>,
+}
+
+/// Represents a SSBOND (disulfide bond between two cysteine `SG`) or LINK record from a PDB file.
+#[derive(Debug, Clone)]
+struct LinkRecord {
+    first: LinkedAtom,
+    second: LinkedAtom,
+}
+
+impl LinkRecord {
+    /// Parse a SSBOND or LINK line, both are fixed columns records.
+    fn from_line(line: &str) -> Option<Self> {
+        let field = |range: std::ops::Range<usize>| line.get(range).map(str::trim);
+        let linked_atom = |name: Option<&str>, chain: usize, serial_number: usize| {
+            Some(LinkedAtom {
+                name: name?.to_string(),
+                chain_id: field(chain..chain + 1)?.to_string(),
+                residue_serial_number: field(serial_number..serial_number + 4)?.parse().ok()?,
+                insertion_code: field(serial_number + 4..serial_number + 5)
+                    .filter(|code| !code.is_empty())
+                    .map(str::to_string),
+            })
+        };
+
+        let (first, second) = if line.starts_with("SSBOND") {
+            (
+                linked_atom(Some("SG"), 15, 17)?,
+                linked_atom(Some("SG"), 29, 31)?,
+            )
+        } else if line.starts_with("LINK  ") {
+            (
+                linked_atom(field(12..16), 21, 22)?,
+                linked_atom(field(42..46), 51, 52)?,
+            )
+        } else {
+            return None;
+        };
+        Some(LinkRecord { first, second })
+    }
+}
+
+fn parse_links(content: &str) -> Vec<LinkRecord> {
+    content.lines().filter_map(LinkRecord::from_line).collect()
+}
+
+/// Convert the `SSBOND` and `LINK` records, which reference atoms by residue, into bonds between
+/// atom indices, in every model. Records pointing to unknown atoms are ignored, and only the
+/// first alternate location of an atom is linked.
+fn resolve_link_records(records: &[LinkRecord], pdb: &PDB) -> Vec<Bond> {
+    let mut bonds = vec![];
+    let mut offset = 0;
+
+    for model in pdb.models() {
+        let mut atom_map = HashMap::new();
+        let mut index = offset;
+        for chain in model.chains() {
+            for residue in chain.residues() {
+                for atom in residue.conformers().flat_map(|conformer| conformer.atoms()) {
+                    let key = (
+                        chain.id(),
+                        residue.serial_number(),
+                        residue.insertion_code(),
+                        atom.name(),
+                    );
+                    atom_map.entry(key).or_insert(index);
+                    index += 1;
+                }
+            }
+        }
+
+        let find = |atom: &LinkedAtom| {
+            atom_map
+                .get(&(
+                    atom.chain_id.as_str(),
+                    atom.residue_serial_number,
+                    atom.insertion_code.as_deref(),
+                    atom.name.as_str(),
+                ))
+                .copied()
+        };
+        bonds.extend(
+            records
+                .iter()
+                .filter_map(|record| Some(Bond::new(find(&record.first)?, find(&record.second)?))),
+        );
+        offset += model.atom_count();
+    }
+    bonds
+}
+
+/// Parse a HELIX or SHEET line into the range of residues it declares, both are fixed columns
+/// records. The helix class and the sheet registration are not kept.
+fn secondary_structure_from_line(line: &str) -> Option<SecondaryStructureRange> {
+    let field = |range: std::ops::Range<usize>| line.get(range).map(str::trim);
+    let residue = |serial_number: usize| {
+        Some((
+            field(serial_number..serial_number + 4)?.parse().ok()?,
+            field(serial_number + 4..serial_number + 5)
+                .filter(|code| !code.is_empty())
+                .map(str::to_string),
+        ))
+    };
+
+    let (kind, chain, start, end) = if line.starts_with("HELIX ") {
+        (SecondaryStructure::Helix, 19, 21, 33)
+    } else if line.starts_with("SHEET ") {
+        (SecondaryStructure::Strand, 21, 22, 33)
+    } else {
+        return None;
+    };
+    Some(SecondaryStructureRange {
+        kind,
+        chain_id: field(chain..chain + 1)?.to_string(),
+        start: residue(start)?,
+        end: residue(end)?,
+    })
+}
+
+fn parse_secondary_structure(content: &str) -> Vec<SecondaryStructureRange> {
+    content
+        .lines()
+        .filter_map(secondary_structure_from_line)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conect_multiplicity() {
+        let ethylene = read(
+            Path::new("resources/pdb/ethylene.pdb"),
+            StructureFormat::Pdb,
+        )
+        .unwrap();
+        assert_eq!(ethylene.pdb.atom_count(), 6);
+        assert_eq!(ethylene.bonds.len(), 5);
+        // The carbon-carbon bond is written twice from both carbons.
+        assert_eq!(ethylene.bonds[0], Bond::with_order(0, 1, BondOrder::Double));
+        assert!(ethylene.bonds[1..]
+            .iter()
+            .all(|bond| bond.order == BondOrder::Single));
+    }
+
+    #[test]
+    fn conect_line() {
+        let record = ConectRecord::from_line("CONECT    1    2    2    3    1").unwrap();
+        assert_eq!(record.source_atom, 1);
+        assert_eq!(record.bonded_atoms, [2, 2, 3]);
+        assert!(ConectRecord::from_line("CONECT    1").is_none());
+        assert!(ConectRecord::from_line("ATOM      1  C1").is_none());
+    }
+
+    #[test]
+    fn mmcif() {
+        let ethylene = read(
+            Path::new("resources/molecules/ethylene.cif"),
+            StructureFormat::Mmcif,
+        )
+        .unwrap();
+        let atoms = ethylene.pdb.atoms().collect::<Vec<_>>();
+        assert_eq!(atoms.len(), 6);
+        assert_eq!(atoms[1].pos(), (1.339, 0.0, 0.0));
+        assert_eq!(atoms[2].name(), "H1");
+        assert!(ethylene.bonds.is_empty());
+        assert_eq!(ethylene.format, StructureFormat::Mmcif);
+    }
+
+    #[test]
+    fn link_lines() {
+        let ssbond = LinkRecord::from_line(
+            "SSBOND   1 CYS A    6    CYS B  127A                         1555   1555  2.03  ",
+        )
+        .unwrap();
+        assert_eq!(ssbond.first.name, "SG");
+        assert_eq!(ssbond.first.chain_id, "A");
+        assert_eq!(ssbond.first.residue_serial_number, 6);
+        assert_eq!(ssbond.first.insertion_code, None);
+        assert_eq!(ssbond.second.chain_id, "B");
+        assert_eq!(ssbond.second.residue_serial_number, 127);
+        assert_eq!(ssbond.second.insertion_code.as_deref(), Some("A"));
+
+        let link = LinkRecord::from_line(
+            "LINK         O   GLY A  49                NA    NA A6001     1555   1555  2.39  ",
+        )
+        .unwrap();
+        assert_eq!(link.first.name, "O");
+        assert_eq!(link.first.residue_serial_number, 49);
+        assert_eq!(link.second.name, "NA");
+        assert_eq!(link.second.chain_id, "A");
+        assert_eq!(link.second.residue_serial_number, 6001);
+
+        assert!(LinkRecord::from_line("LINKR        O   GLY A  49").is_none());
+    }
+
+    #[test]
+    fn secondary_structure_lines() {
+        let helix = secondary_structure_from_line(
+            "HELIX    1  HA GLY A   86  GLY A   94  1                                   9    ",
+        )
+        .unwrap();
+        assert_eq!(helix.kind, SecondaryStructure::Helix);
+        assert_eq!(helix.chain_id, "A");
+        assert_eq!(helix.start, (86, None));
+        assert_eq!(helix.end, (94, None));
+
+        let strand = secondary_structure_from_line(
+            "SHEET    2   A 5 LEU A 120B ASN A 124  -1  N  VAL A 122   O  LEU A 121       ",
+        )
+        .unwrap();
+        assert_eq!(strand.kind, SecondaryStructure::Strand);
+        assert_eq!(strand.start, (120, Some("B".to_string())));
+        assert_eq!(strand.end, (124, None));
+
+        assert!(secondary_structure_from_line("TURN     1 T1 GLY A   1").is_none());
+    }
+}
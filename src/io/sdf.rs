@@ -0,0 +1,525 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::BufRead,
+    ops::Range,
+    path::Path,
+};
+
+use pdbtbx::{Atom, Element, PDB};
+
+use crate::bonding::{Bond, BondOrder};
+
+use super::{
+    open_file, single_residue_model, AtomProperties, LoadedStructure, RecordSelector,
+    StructureFormat,
+};
+
+/// Name of the residue holding the atoms of a MOL record.
+const RESIDUE_NAME: &str = "MOL";
+
+/// Line separating the records of an SDF file.
+const RECORD_SEPARATOR: &str = "$$$$";
+
+/// Prefix of every line of a V3000 connection table.
+const V3000_PREFIX: &str = "M  V30 ";
+
+/// Read a record of a MOL or SDF file, with a V2000 or V3000 connection table.
+/// The record is chosen with `selector`, the first one is used when no selector is given.
+/// The SDF data items following the connection table are kept in `LoadedStructure::properties`,
+/// along with the record name under the `name` key.
+pub fn read(path: &Path, selector: Option<&RecordSelector>) -> anyhow::Result<LoadedStructure> {
+    let mut record = Vec::new();
+    let mut index = 1;
+
+    // Records are scanned one at a time, so that picking a record in a large library does not
+    // require to parse, or keep in memory, the whole file.
+    for line in open_file(path)?.lines() {
+        let line = line?;
+        if line.trim_end() == RECORD_SEPARATOR {
+            if is_selected(selector, index, &record) {
+                return parse_record(&record)
+                    .map_err(|error| anyhow::format_err!("record {}: {}", index, error));
+            }
+            record.clear();
+            index += 1;
+        } else {
+            record.push(line);
+        }
+    }
+
+    // The last record of a MOL file, or of a sloppy SDF file, is not followed by a separator.
+    let has_last_record = record.iter().any(|line| !line.trim().is_empty());
+    if has_last_record && is_selected(selector, index, &record) {
+        return parse_record(&record)
+            .map_err(|error| anyhow::format_err!("record {}: {}", index, error));
+    }
+    let record_count = if has_last_record { index } else { index - 1 };
+
+    Err(match selector {
+        Some(RecordSelector::Index(wanted)) => anyhow::format_err!(
+            "`{}` has no record {}, it only contains {} records",
+            path.display(),
+            wanted,
+            record_count
+        ),
+        Some(RecordSelector::Name(name)) => {
+            anyhow::format_err!("`{}` has no record named `{}`", path.display(), name)
+        }
+        None => anyhow::format_err!("`{}` does not contain any record", path.display()),
+    })
+}
+
+fn is_selected(selector: Option<&RecordSelector>, index: usize, record: &[String]) -> bool {
+    match selector {
+        None => true,
+        Some(RecordSelector::Index(wanted)) => *wanted == index,
+        Some(RecordSelector::Name(name)) => record
+            .first()
+            .is_some_and(|title| title.trim() == name.trim()),
+    }
+}
+
+/// An atom of a connection table, before its conversion to a `pdbtbx::Atom`.
+struct MolAtom {
+    element: Element,
+    position: [f64; 3],
+    charge: isize,
+    isotope: Option<u16>,
+}
+
+/// Atoms and bonds of a connection table, bonds reference the atoms by their index in `atoms`.
+/// `end` is the index of the line following the connection table.
+struct ConnectionTable {
+    atoms: Vec<MolAtom>,
+    bonds: Vec<Bond>,
+    end: usize,
+}
+
+fn parse_record(lines: &[String]) -> anyhow::Result<LoadedStructure> {
+    if lines.len() < 4 {
+        return Err(anyhow::format_err!("the header block is truncated"));
+    }
+
+    let counts = &lines[3];
+    let table = if counts.contains("V3000") {
+        parse_v3000(lines)?
+    } else {
+        parse_v2000(lines)?
+    };
+
+    let mut properties = parse_data_items(&lines[table.end..]);
+    let name = lines[0].trim();
+    if !name.is_empty() {
+        properties.insert("name".to_string(), name.to_string());
+    }
+
+    let mut atoms = Vec::with_capacity(table.atoms.len());
+    let mut atom_properties = Vec::with_capacity(table.atoms.len());
+    for (index, atom) in table.atoms.iter().enumerate() {
+        let symbol = atom.element.symbol();
+        atoms.push(
+            Atom::new(
+                true,
+                index + 1,
+                format!("{}{}", symbol, index + 1),
+                atom.position[0],
+                atom.position[1],
+                atom.position[2],
+                1.0,
+                0.0,
+                symbol,
+                atom.charge,
+            )
+            .ok_or_else(|| anyhow::format_err!("atom {} is invalid", index + 1))?,
+        );
+        atom_properties.push(AtomProperties {
+            isotope: atom.isotope,
+            ..Default::default()
+        });
+    }
+
+    let model = single_residue_model(1, RESIDUE_NAME, atoms)
+        .ok_or_else(|| anyhow::format_err!("the molecule is invalid"))?;
+    let mut pdb = PDB::new();
+    pdb.add_model(model);
+
+    Ok(LoadedStructure {
+        pdb,
+        bonds: table.bonds,
+        links: vec![],
+        secondary_structure: vec![],
+        format: StructureFormat::Sdf,
+        properties: vec![properties],
+        atom_properties,
+    })
+}
+
+/// Return the trimmed content of a fixed width column, or an empty string when the line is too
+/// short.
+fn column(line: &str, range: Range<usize>) -> &str {
+    let end = range.end.min(line.len());
+    line.get(range.start.min(end)..end).unwrap_or("").trim()
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, what: &str) -> anyhow::Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::format_err!("invalid {} `{}`", what, value.trim()))
+}
+
+/// Convert an atom symbol into an element, deuterium and tritium are read as hydrogen isotopes.
+fn parse_symbol(symbol: &str) -> anyhow::Result<(Element, Option<u16>)> {
+    match symbol {
+        "D" => Ok((Element::H, Some(2))),
+        "T" => Ok((Element::H, Some(3))),
+        _ => Element::from_symbol(symbol)
+            .map(|element| (element, None))
+            .ok_or_else(|| anyhow::format_err!("unsupported atom symbol `{}`", symbol)),
+    }
+}
+
+fn bond_order(bond_type: u8) -> BondOrder {
+    match bond_type {
+        2 => BondOrder::Double,
+        3 => BondOrder::Triple,
+        4 => BondOrder::Aromatic,
+        // Single, and the query bond types (single or double, any, ...) which have no better
+        // representation.
+        _ => BondOrder::Single,
+    }
+}
+
+fn parse_v2000(lines: &[String]) -> anyhow::Result<ConnectionTable> {
+    let counts = &lines[3];
+    let atom_count: usize = parse_number(column(counts, 0..3), "atom count")?;
+    let bond_count: usize = parse_number(column(counts, 3..6), "bond count")?;
+
+    let atom_lines = lines
+        .get(4..4 + atom_count)
+        .ok_or_else(|| anyhow::format_err!("the atom block is truncated"))?;
+    let bond_lines = lines
+        .get(4 + atom_count..4 + atom_count + bond_count)
+        .ok_or_else(|| anyhow::format_err!("the bond block is truncated"))?;
+
+    let mut atoms = Vec::with_capacity(atom_count);
+    for line in atom_lines {
+        let (element, isotope) = parse_symbol(column(line, 31..34))?;
+
+        // The mass difference is relative to the mass of the natural isotope mixture.
+        let mass_difference: i32 = match column(line, 34..36) {
+            "" => 0,
+            value => parse_number(value, "mass difference")?,
+        };
+        let isotope = match (isotope, mass_difference) {
+            (Some(isotope), _) => Some(isotope),
+            (None, 0) => None,
+            (None, difference) => element
+                .weight()
+                .map(|weight| (weight.round() as i32 + difference) as u16),
+        };
+
+        let charge = match column(line, 36..39) {
+            "" => 0,
+            value => match parse_number::<u8>(value, "charge code")? {
+                1 => 3,
+                2 => 2,
+                3 => 1,
+                5 => -1,
+                6 => -2,
+                7 => -3,
+                // 0 is uncharged, and 4 a doublet radical.
+                _ => 0,
+            },
+        };
+
+        atoms.push(MolAtom {
+            element,
+            position: [
+                parse_number(column(line, 0..10), "x coordinate")?,
+                parse_number(column(line, 10..20), "y coordinate")?,
+                parse_number(column(line, 20..30), "z coordinate")?,
+            ],
+            charge,
+            isotope,
+        });
+    }
+
+    let mut bonds = Vec::with_capacity(bond_count);
+    for line in bond_lines {
+        let first: usize = parse_number(column(line, 0..3), "bond atom")?;
+        let second: usize = parse_number(column(line, 3..6), "bond atom")?;
+        let bond_type: u8 = parse_number(column(line, 6..9), "bond type")?;
+        bonds.push(Bond::with_order(
+            atom_index(first, atoms.len())?,
+            atom_index(second, atoms.len())?,
+            bond_order(bond_type),
+        ));
+    }
+
+    // Properties block, `M  CHG` and `M  ISO` entries supersede the values of the atom block.
+    let mut end = 4 + atom_count + bond_count;
+    let mut charges_reset = false;
+    while let Some(line) = lines.get(end) {
+        end += 1;
+        if line.starts_with("M  END") {
+            break;
+        }
+
+        let is_charge = line.starts_with("M  CHG");
+        if !is_charge && !line.starts_with("M  ISO") {
+            continue;
+        }
+        if is_charge && !charges_reset {
+            atoms.iter_mut().for_each(|atom| atom.charge = 0);
+            charges_reset = true;
+        }
+
+        let values = line[6..].split_whitespace().skip(1).collect::<Vec<_>>();
+        for pair in values.chunks_exact(2) {
+            let atom = atom_index(parse_number(pair[0], "property atom")?, atoms.len())?;
+            if is_charge {
+                atoms[atom].charge = parse_number(pair[1], "charge")?;
+            } else {
+                atoms[atom].isotope = Some(parse_number(pair[1], "isotope")?);
+            }
+        }
+    }
+
+    Ok(ConnectionTable { atoms, bonds, end })
+}
+
+fn parse_v3000(lines: &[String]) -> anyhow::Result<ConnectionTable> {
+    // Gather the V3000 lines, joining the ones continued with a trailing `-`.
+    let mut entries: Vec<String> = Vec::new();
+    let mut continued = false;
+    let mut end = lines.len();
+    for (index, line) in lines.iter().enumerate().skip(4) {
+        if line.starts_with("M  END") {
+            end = index + 1;
+            break;
+        }
+        let Some(content) = line.strip_prefix(V3000_PREFIX) else {
+            continue;
+        };
+        let (content, continues) = match content.trim_end().strip_suffix('-') {
+            Some(content) => (content, true),
+            None => (content.trim_end(), false),
+        };
+        match entries.last_mut() {
+            Some(last) if continued => last.push_str(content),
+            _ => entries.push(content.to_string()),
+        }
+        continued = continues;
+    }
+
+    let section = |name: &str| -> anyhow::Result<&[String]> {
+        let begin = entries
+            .iter()
+            .position(|entry| entry.trim() == format!("BEGIN {}", name))
+            .ok_or_else(|| anyhow::format_err!("missing V3000 {} block", name))?;
+        let length = entries[begin + 1..]
+            .iter()
+            .position(|entry| entry.trim() == format!("END {}", name))
+            .ok_or_else(|| anyhow::format_err!("unterminated V3000 {} block", name))?;
+        Ok(&entries[begin + 1..begin + 1 + length])
+    };
+
+    let mut atoms = Vec::new();
+    let mut atom_ids = HashMap::new();
+    for entry in section("ATOM")? {
+        let fields = entry.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 5 {
+            return Err(anyhow::format_err!("invalid V3000 atom `{}`", entry));
+        }
+        let (element, mut isotope) = parse_symbol(fields[1].trim_matches('"'))?;
+        let mut charge = 0;
+        for field in &fields[6.min(fields.len())..] {
+            if let Some(value) = field.strip_prefix("CHG=") {
+                charge = parse_number(value, "charge")?;
+            } else if let Some(value) = field.strip_prefix("MASS=") {
+                isotope = Some(parse_number::<f64>(value, "mass")?.round() as u16);
+            }
+        }
+
+        atom_ids.insert(fields[0].to_string(), atoms.len());
+        atoms.push(MolAtom {
+            element,
+            position: [
+                parse_number(fields[2], "x coordinate")?,
+                parse_number(fields[3], "y coordinate")?,
+                parse_number(fields[4], "z coordinate")?,
+            ],
+            charge,
+            isotope,
+        });
+    }
+
+    let mut bonds = Vec::new();
+    // A molecule without bonds may omit the whole block.
+    let bond_entries = section("BOND").unwrap_or(&[]);
+    for entry in bond_entries {
+        let fields = entry.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 4 {
+            return Err(anyhow::format_err!("invalid V3000 bond `{}`", entry));
+        }
+        let atom = |id: &str| {
+            atom_ids
+                .get(id)
+                .copied()
+                .ok_or_else(|| anyhow::format_err!("bond to an unknown atom `{}`", id))
+        };
+        bonds.push(Bond::with_order(
+            atom(fields[2])?,
+            atom(fields[3])?,
+            bond_order(parse_number(fields[1], "bond type")?),
+        ));
+    }
+
+    Ok(ConnectionTable { atoms, bonds, end })
+}
+
+/// Convert a 1-based atom number of a connection table into an index.
+fn atom_index(number: usize, atom_count: usize) -> anyhow::Result<usize> {
+    if number == 0 || number > atom_count {
+        return Err(anyhow::format_err!(
+            "reference to an unknown atom {}",
+            number
+        ));
+    }
+    Ok(number - 1)
+}
+
+/// Parse the SDF data items, `> <KEY>` header lines followed by the value lines, up to a blank
+/// line.
+fn parse_data_items(lines: &[String]) -> BTreeMap<String, String> {
+    let mut items = BTreeMap::new();
+    let mut lines = lines.iter();
+
+    while let Some(line) = lines.next() {
+        if !line.starts_with('>') {
+            continue;
+        }
+        let Some(key) = line
+            .split_once('<')
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map(|(key, _)| key.to_string())
+        else {
+            continue;
+        };
+
+        let value = lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        items.insert(key, value);
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIGANDS: &str = "resources/molecules/ligands.sdf";
+
+    fn read_record(selector: RecordSelector) -> LoadedStructure {
+        read(Path::new(LIGANDS), Some(&selector)).unwrap()
+    }
+
+    fn orders(structure: &LoadedStructure) -> Vec<BondOrder> {
+        structure.bonds.iter().map(|bond| bond.order).collect()
+    }
+
+    #[test]
+    fn v2000_columns() {
+        let ethylene = read(Path::new(LIGANDS), None).unwrap();
+        let atoms = ethylene.pdb.atoms().collect::<Vec<_>>();
+        assert_eq!(atoms.len(), 6);
+        assert_eq!(atoms[1].pos(), (1.339, 0.0, 0.0));
+        assert_eq!(atoms[2].pos(), (-0.558, 0.935, 0.0));
+        assert_eq!(atoms[0].element(), Some(&Element::C));
+        assert_eq!(atoms[5].element(), Some(&Element::H));
+        // The mass difference column makes the first carbon a carbon 13.
+        assert_eq!(ethylene.atom_properties[0].isotope, Some(13));
+        assert_eq!(ethylene.atom_properties[1].isotope, None);
+        assert_eq!(
+            orders(&ethylene),
+            [
+                BondOrder::Double,
+                BondOrder::Single,
+                BondOrder::Single,
+                BondOrder::Single,
+                BondOrder::Single
+            ]
+        );
+        assert_eq!((ethylene.bonds[0].first, ethylene.bonds[0].second), (0, 1));
+        assert_eq!(ethylene.properties[0]["name"], "ethylene");
+        assert_eq!(ethylene.properties[0]["SOURCE"], "hand written");
+        assert_eq!(ethylene.properties[0]["FORMULA"], "C2H4");
+    }
+
+    #[test]
+    fn v2000_charges() {
+        // Charge code 3 of the atom block.
+        let methylammonium = read_record(RecordSelector::Name("methylammonium".to_string()));
+        let charges = methylammonium
+            .pdb
+            .atoms()
+            .map(|atom| atom.charge())
+            .collect::<Vec<_>>();
+        assert_eq!(charges, [0, 1, 0, 0, 0, 0, 0, 0]);
+
+        // `M  CHG` entries of the properties block.
+        let sodium_acetate = read_record(RecordSelector::Index(3));
+        let charges = sodium_acetate
+            .pdb
+            .atoms()
+            .map(|atom| atom.charge())
+            .collect::<Vec<_>>();
+        assert_eq!(charges, [0, 0, 0, -1, 0, 0, 0, 1]);
+        assert_eq!(sodium_acetate.bonds.len(), 6);
+        assert_eq!(sodium_acetate.properties[0]["name"], "sodium acetate");
+    }
+
+    #[test]
+    fn missing_record() {
+        let error = read(Path::new(LIGANDS), Some(&RecordSelector::Index(4))).unwrap_err();
+        assert!(error.to_string().contains("only contains 3 records"));
+        let error = read(
+            Path::new(LIGANDS),
+            Some(&RecordSelector::Name("benzene".to_string())),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("no record named `benzene`"));
+
+        // The last record of a MOL file has no separator.
+        let path = Path::new("resources/molecules/benzene.mol");
+        let error = read(path, Some(&RecordSelector::Index(2))).unwrap_err();
+        assert!(error.to_string().contains("only contains 1 records"));
+    }
+
+    #[test]
+    fn v3000() {
+        let benzene = read(Path::new("resources/molecules/benzene.mol"), None).unwrap();
+        let atoms = benzene.pdb.atoms().collect::<Vec<_>>();
+        assert_eq!(atoms.len(), 12);
+        // The position of the first hydrogen is continued on the next line.
+        assert_eq!(atoms[6].pos(), (2.47, 0.0, 0.0));
+        assert_eq!(atoms[6].element(), Some(&Element::H));
+        assert_eq!(benzene.atom_properties[0].isotope, Some(13));
+        assert_eq!(benzene.bonds.len(), 12);
+        assert_eq!(orders(&benzene)[..6], [BondOrder::Aromatic; 6]);
+        assert_eq!(orders(&benzene)[6..], [BondOrder::Single; 6]);
+        assert_eq!((benzene.bonds[5].first, benzene.bonds[5].second), (0, 5));
+    }
+
+    #[test]
+    fn truncated_record() {
+        let lines = ["name", "", "", "  2  1  0  0  0  0  0  0  0  0999 V2000"].map(str::to_string);
+        let error = parse_record(&lines).unwrap_err();
+        assert_eq!(error.to_string(), "the atom block is truncated");
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufReader, Read},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+};
+
+use nalgebra::Point3;
+
+use super::{dcd::DcdReader, xtc::XtcReader};
+
+/// A coordinate trajectory, made of frames holding the position of every atom of a topology.
+/// Readers only keep an index of the frames, the coordinates are read from the file on demand so
+/// that trajectories larger than the memory can be played.
+pub trait Trajectory {
+    fn atom_count(&self) -> usize;
+
+    fn frame_count(&self) -> usize;
+
+    /// Read the coordinates (in Å) of the frame at `index`.
+    fn read_frame(&mut self, index: usize) -> anyhow::Result<Vec<Point3<f32>>>;
+}
+
+/// Range of frames to play, written `start:end` where both bounds are optional, `end` is
+/// excluded. A single number selects a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl FromStr for FrameRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_bound = |bound: &str| -> Result<Option<usize>, String> {
+            match bound.trim() {
+                "" => Ok(None),
+                bound => bound
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("invalid frame number `{}`", bound)),
+            }
+        };
+
+        let (start, end) = match s.split_once(':') {
+            Some((start, end)) => (parse_bound(start)?, parse_bound(end)?),
+            None => {
+                let frame = parse_bound(s)?.ok_or("empty frame range")?;
+                (Some(frame), Some(frame + 1))
+            }
+        };
+
+        Ok(Self {
+            start: start.unwrap_or(0),
+            end,
+        })
+    }
+}
+
+impl FrameRange {
+    /// Clamp the range to a trajectory of `frame_count` frames.
+    pub fn clamp(&self, frame_count: usize) -> Range<usize> {
+        let end = self.end.unwrap_or(frame_count).min(frame_count);
+        self.start.min(end)..end
+    }
+}
+
+/// Open a DCD or XTC trajectory, the format is deduced from the extension, or from the first bytes
+/// of the file.
+pub fn open(path: &Path) -> anyhow::Result<Box<dyn Trajectory>> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(|extension| extension.to_ascii_lowercase());
+
+    let is_xtc = match extension.as_deref() {
+        Some("xtc") => true,
+        Some("dcd") => false,
+        _ => {
+            // XTC frames start with the 1995 magic number, big endian.
+            let mut magic = [0; 4];
+            BufReader::new(File::open(path)?).read_exact(&mut magic)?;
+            i32::from_be_bytes(magic) == super::xtc::MAGIC
+        }
+    };
+
+    let trajectory: Box<dyn Trajectory> = if is_xtc {
+        Box::new(XtcReader::open(path)?)
+    } else {
+        Box::new(DcdReader::open(path)?)
+    };
+
+    if trajectory.frame_count() == 0 {
+        return Err(anyhow::format_err!(
+            "`{}` does not contain any frame",
+            path.display()
+        ));
+    }
+    Ok(trajectory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: usize, end: Option<usize>) -> FrameRange {
+        FrameRange { start, end }
+    }
+
+    #[test]
+    fn parse_frame_range() {
+        assert_eq!("10:20".parse(), Ok(range(10, Some(20))));
+        assert_eq!("10:".parse(), Ok(range(10, None)));
+        assert_eq!(":20".parse(), Ok(range(0, Some(20))));
+        assert_eq!(":".parse(), Ok(range(0, None)));
+        assert_eq!(" 5 ".parse(), Ok(range(5, Some(6))));
+        assert_eq!(
+            "a:20".parse::<FrameRange>(),
+            Err("invalid frame number `a`".to_string())
+        );
+        assert_eq!(
+            "".parse::<FrameRange>(),
+            Err("empty frame range".to_string())
+        );
+    }
+
+    #[test]
+    fn clamp_frame_range() {
+        assert_eq!(range(10, Some(20)).clamp(100), 10..20);
+        assert_eq!(range(10, None).clamp(100), 10..100);
+        assert_eq!(range(10, Some(200)).clamp(100), 10..100);
+        assert_eq!(range(150, None).clamp(100), 100..100);
+        assert_eq!(range(30, Some(20)).clamp(100), 20..20);
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use nalgebra::Point3;
+
+use super::trajectory::Trajectory;
+
+/// Magic number starting every XTC frame.
+pub const MAGIC: i32 = 1995;
+
+/// XTC coordinates are stored in nm.
+const NM_TO_ANGSTROM: f32 = 10.0;
+
+/// Frames with at most this many atoms are stored uncompressed.
+const UNCOMPRESSED_ATOM_COUNT: usize = 9;
+
+/// Size of the frame header (magic, atom count, step, time) followed by the 3x3 box.
+const HEADER_SIZE: u64 = 4 * 4 + 9 * 4;
+
+/// Smallest index of `MAGIC_INTS` used by the compression.
+const FIRST_INDEX: usize = 9;
+
+/// Integers `n` such that `n³` fits in `index` bits, used for the small deltas between
+/// consecutive atoms.
+const MAGIC_INTS: [i32; 73] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 10, 12, 16, 20, 25, 32, 40, 50, 64, 80, 101, 128, 161, 203, 256,
+    322, 406, 512, 645, 812, 1024, 1290, 1625, 2048, 2580, 3250, 4096, 5060, 6501, 8192, 10321,
+    13003, 16384, 20642, 26007, 32768, 41285, 52015, 65536, 82570, 104031, 131072, 165140, 208063,
+    262144, 330280, 416127, 524287, 660561, 832255, 1048576, 1321122, 1664510, 2097152, 2642245,
+    3329021, 4194304, 5284491, 6658042, 8388607, 10568983, 13316085, 16777216,
+];
+
+/// GROMACS XTC trajectory reader.
+/// Frames have a variable size, the file is scanned once when opened to index the frame offsets,
+/// skipping over the compressed coordinates.
+pub struct XtcReader {
+    reader: BufReader<File>,
+    atom_count: usize,
+    frame_offsets: Vec<u64>,
+}
+
+impl XtcReader {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut frame_offsets = Vec::new();
+        let mut atom_count = None;
+
+        loop {
+            let offset = reader.stream_position()?;
+            let magic = match read_i32(&mut reader) {
+                Ok(magic) => magic,
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error.into()),
+            };
+            if magic != MAGIC {
+                return Err(anyhow::format_err!(
+                    "`{}` is not a XTC file, or frame {} is corrupted",
+                    path.display(),
+                    frame_offsets.len()
+                ));
+            }
+
+            let frame_atoms = read_i32(&mut reader)? as usize;
+            if *atom_count.get_or_insert(frame_atoms) != frame_atoms {
+                return Err(anyhow::format_err!(
+                    "frame {} of `{}` has {} atoms instead of {}",
+                    frame_offsets.len(),
+                    path.display(),
+                    frame_atoms,
+                    atom_count.unwrap_or_default()
+                ));
+            }
+
+            // Step, time and box, then the atom count repeated by the coordinates block.
+            reader.seek_relative(HEADER_SIZE as i64 - 8)?;
+            read_i32(&mut reader)?;
+            let skipped = if frame_atoms <= UNCOMPRESSED_ATOM_COUNT {
+                12 * frame_atoms as i64
+            } else {
+                // Precision, minimum and maximum integer coordinates, small index.
+                reader.seek_relative(4 + 12 + 12 + 4)?;
+                let byte_count = read_i32(&mut reader)? as u64;
+                byte_count.next_multiple_of(4) as i64
+            };
+            reader.seek_relative(skipped)?;
+
+            // A truncated last frame is ignored.
+            if reader.stream_position()? > file_size {
+                break;
+            }
+            frame_offsets.push(offset);
+        }
+
+        Ok(Self {
+            reader,
+            atom_count: atom_count.unwrap_or_default(),
+            frame_offsets,
+        })
+    }
+}
+
+impl Trajectory for XtcReader {
+    fn atom_count(&self) -> usize {
+        self.atom_count
+    }
+
+    fn frame_count(&self) -> usize {
+        self.frame_offsets.len()
+    }
+
+    fn read_frame(&mut self, index: usize) -> anyhow::Result<Vec<Point3<f32>>> {
+        let offset = *self
+            .frame_offsets
+            .get(index)
+            .ok_or_else(|| anyhow::format_err!("XTC frame {} does not exist", index))?;
+        self.reader.seek(SeekFrom::Start(offset + HEADER_SIZE))?;
+
+        let atom_count = read_i32(&mut self.reader)? as usize;
+        let coordinates = if atom_count <= UNCOMPRESSED_ATOM_COUNT {
+            (0..3 * atom_count)
+                .map(|_| read_f32(&mut self.reader))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            decompress_coordinates(&mut self.reader, atom_count)?
+        };
+
+        Ok(coordinates
+            .chunks_exact(3)
+            .map(|position| Point3::new(position[0], position[1], position[2]) * NM_TO_ANGSTROM)
+            .collect())
+    }
+}
+
+fn read_i32(reader: &mut impl Read) -> std::io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_be_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> std::io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_be_bytes(bytes))
+}
+
+/// Decode the compressed coordinates of a frame, following the `xdr3dfcoord` algorithm of the
+/// GROMACS xdrfile library.
+fn decompress_coordinates(reader: &mut impl Read, atom_count: usize) -> anyhow::Result<Vec<f32>> {
+    let precision = read_f32(reader)?;
+    let mut min_int = [0; 3];
+    let mut max_int = [0; 3];
+    for value in min_int.iter_mut().chain(max_int.iter_mut()) {
+        *value = read_i32(reader)?;
+    }
+
+    let sizes = [0, 1, 2].map(|axis| (max_int[axis] as i64 - min_int[axis] as i64 + 1) as u32);
+    // Large boxes encode each coordinate on its own, otherwise the 3 coordinates are packed.
+    let (packed_bits, axis_bits) = if sizes.iter().any(|&size| size > 0xffffff) {
+        (0, sizes.map(bit_size))
+    } else {
+        (packed_bit_size(&sizes), [0; 3])
+    };
+
+    let mut small_index = read_i32(reader)? as usize;
+    if !(FIRST_INDEX..MAGIC_INTS.len()).contains(&small_index) {
+        return Err(anyhow::format_err!("corrupted XTC frame"));
+    }
+    let mut smaller = MAGIC_INTS[small_index.saturating_sub(1).max(FIRST_INDEX)] / 2;
+    let mut small_num = MAGIC_INTS[small_index] / 2;
+    let mut small_sizes = [MAGIC_INTS[small_index] as u32; 3];
+
+    let byte_count = read_i32(reader)? as usize;
+    let mut bytes = vec![0; byte_count.next_multiple_of(4)];
+    reader.read_exact(&mut bytes)?;
+    let mut bits = BitReader::new(&bytes);
+
+    let inverse_precision = 1.0 / precision;
+    let mut coordinates = Vec::with_capacity(3 * atom_count);
+    let mut push = |coordinate: [i32; 3]| {
+        coordinates.extend(coordinate.map(|value| value as f32 * inverse_precision));
+    };
+
+    let mut run = 0;
+    let mut atom = 0;
+    while atom < atom_count {
+        let mut this = if packed_bits == 0 {
+            let mut this = [0; 3];
+            for (value, bit_count) in this.iter_mut().zip(axis_bits) {
+                *value = bits.read(bit_count)? as i32;
+            }
+            this
+        } else {
+            bits.read_ints(packed_bits, sizes)?
+        };
+        atom += 1;
+        for axis in 0..3 {
+            this[axis] += min_int[axis];
+        }
+        let mut previous = this;
+
+        let mut is_smaller = 0;
+        if bits.read(1)? == 1 {
+            run = bits.read(5)? as i32;
+            is_smaller = run % 3;
+            run -= is_smaller;
+            is_smaller -= 1;
+        }
+
+        if run > 0 {
+            for k in (0..run).step_by(3) {
+                let mut small = bits.read_ints(small_index as u32, small_sizes)?;
+                atom += 1;
+                for axis in 0..3 {
+                    small[axis] += previous[axis] - small_num;
+                }
+                if k == 0 {
+                    // The first two atoms of a run are swapped by the encoder, which compresses
+                    // water molecules better.
+                    std::mem::swap(&mut small, &mut previous);
+                    push(previous);
+                } else {
+                    previous = small;
+                }
+                push(small);
+            }
+        } else {
+            push(this);
+        }
+
+        small_index = small_index
+            .checked_add_signed(is_smaller as isize)
+            .filter(|index| (FIRST_INDEX..MAGIC_INTS.len()).contains(index))
+            .ok_or_else(|| anyhow::format_err!("corrupted XTC frame"))?;
+        if is_smaller < 0 {
+            small_num = smaller;
+            smaller = if small_index > FIRST_INDEX {
+                MAGIC_INTS[small_index - 1] / 2
+            } else {
+                0
+            };
+        } else if is_smaller > 0 {
+            smaller = small_num;
+            small_num = MAGIC_INTS[small_index] / 2;
+        }
+        small_sizes = [MAGIC_INTS[small_index] as u32; 3];
+    }
+
+    coordinates.truncate(3 * atom_count);
+    Ok(coordinates)
+}
+
+/// Number of bits needed to store integers up to `size`.
+fn bit_size(size: u32) -> u32 {
+    let mut bits = 0;
+    let mut num = 1u64;
+    while size as u64 >= num && bits < 32 {
+        bits += 1;
+        num <<= 1;
+    }
+    bits
+}
+
+/// Number of bits needed to store the product of the 3 `sizes`, the integers being packed as a
+/// single mixed radix number.
+fn packed_bit_size(sizes: &[u32; 3]) -> u32 {
+    let mut bytes = vec![1u32];
+    for &size in sizes {
+        let mut carry = 0u64;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u64 * size as u64;
+            *byte = (carry & 0xff) as u32;
+            carry >>= 8;
+        }
+        while carry != 0 {
+            bytes.push((carry & 0xff) as u32);
+            carry >>= 8;
+        }
+    }
+
+    let last = *bytes.last().unwrap_or(&0);
+    let mut bits = 0;
+    let mut num = 1;
+    while last >= num {
+        bits += 1;
+        num *= 2;
+    }
+    bits + 8 * (bytes.len() as u32 - 1)
+}
+
+/// Big endian bit stream of the compressed coordinates.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    last_bits: u32,
+    last_byte: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            last_bits: 0,
+            last_byte: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> anyhow::Result<u32> {
+        let byte = self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| anyhow::format_err!("truncated XTC frame"))?;
+        self.position += 1;
+        Ok(*byte as u32)
+    }
+
+    fn read(&mut self, mut bit_count: u32) -> anyhow::Result<u32> {
+        let mask = ((1u64 << bit_count) - 1) as u32;
+        let mut num = 0u32;
+        while bit_count >= 8 {
+            self.last_byte = (self.last_byte << 8) | self.next_byte()?;
+            num |= (self.last_byte >> self.last_bits) << (bit_count - 8);
+            bit_count -= 8;
+        }
+        if bit_count > 0 {
+            if self.last_bits < bit_count {
+                self.last_bits += 8;
+                self.last_byte = (self.last_byte << 8) | self.next_byte()?;
+            }
+            self.last_bits -= bit_count;
+            num |= (self.last_byte >> self.last_bits) & ((1 << bit_count) - 1);
+        }
+        Ok(num & mask)
+    }
+
+    /// Read 3 integers packed on `bit_count` bits, the integer `i` being lower than `sizes[i]`.
+    fn read_ints(&mut self, mut bit_count: u32, sizes: [u32; 3]) -> anyhow::Result<[i32; 3]> {
+        let mut bytes = [0u32; 32];
+        let mut byte_count = 0;
+        while bit_count > 8 {
+            bytes[byte_count] = self.read(8)?;
+            byte_count += 1;
+            bit_count -= 8;
+        }
+        if bit_count > 0 {
+            bytes[byte_count] = self.read(bit_count)?;
+            byte_count += 1;
+        }
+
+        let mut values = [0; 3];
+        for axis in (1..3).rev() {
+            let mut num = 0u32;
+            for byte in bytes[..byte_count].iter_mut().rev() {
+                num = (num << 8) | *byte;
+                let quotient = num / sizes[axis];
+                *byte = quotient;
+                num -= quotient * sizes[axis];
+            }
+            values[axis] = num as i32;
+        }
+        values[0] = (bytes[0] | (bytes[1] << 8) | (bytes[2] << 16) | (bytes[3] << 24)) as i32;
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{io::scratch_path, io::trajectory};
+
+    /// Write an XTC file of uncompressed frames, coordinates in nm.
+    fn write_xtc(path: &Path, frames: &[Vec<[f32; 3]>]) {
+        let mut bytes = Vec::new();
+        for (step, frame) in frames.iter().enumerate() {
+            bytes.extend(MAGIC.to_be_bytes());
+            bytes.extend((frame.len() as i32).to_be_bytes());
+            bytes.extend((step as i32).to_be_bytes());
+            bytes.extend((step as f32).to_be_bytes());
+            bytes.extend([0; 36]);
+            bytes.extend((frame.len() as i32).to_be_bytes());
+            bytes.extend(frame.iter().flatten().flat_map(|value| value.to_be_bytes()));
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn read_uncompressed_frames() {
+        // Without extension, the format is detected from the magic number.
+        let path = scratch_path("frames_xtc");
+        let frames = (0..2)
+            .map(|frame| {
+                (0..3)
+                    .map(|atom| [0.1 * frame as f32, 0.2 * atom as f32, 0.5])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        write_xtc(&path, &frames);
+
+        let mut xtc = trajectory::open(&path).unwrap();
+        assert_eq!(xtc.atom_count(), 3);
+        assert_eq!(xtc.frame_count(), 2);
+        let frame = xtc.read_frame(1).unwrap();
+        assert!((frame[2] - Point3::new(1.0, 4.0, 5.0)).norm() < 1e-5);
+        assert!(xtc.read_frame(2).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn atom_count_change() {
+        let path = scratch_path("changing.xtc");
+        write_xtc(&path, &[vec![[0.0; 3]; 2], vec![[0.0; 3]; 3]]);
+        let error = XtcReader::open(&path).err().unwrap();
+        assert!(error.to_string().contains("has 3 atoms instead of 2"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+use std::{collections::BTreeMap, io::BufRead, path::Path};
+
+use pdbtbx::{Atom, Element, PDB};
+
+use super::{open_file, single_residue_model, LoadedStructure, StructureFormat};
+
+/// Name of the residue holding the atoms of an XYZ frame.
+const RESIDUE_NAME: &str = "XYZ";
+
+/// Read a (possibly multi-frame) XYZ or extended XYZ file.
+/// Each frame becomes a model of the returned structure, and the key/value pairs of the comment
+/// line are kept in `LoadedStructure::properties`. XYZ files have no connectivity, the returned
+/// structure never has any explicit bond.
+pub fn read(path: &Path) -> anyhow::Result<LoadedStructure> {
+    let mut lines = open_file(path)?.lines().enumerate();
+
+    let mut pdb = PDB::new();
+    let mut properties = Vec::new();
+
+    while let Some((line_number, line)) = lines.next() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let atom_count = line.trim().parse::<usize>().map_err(|_| {
+            anyhow::format_err!(
+                "line {}: expected the atom count of a frame, found `{}`",
+                line_number + 1,
+                line.trim()
+            )
+        })?;
+
+        let comment = match lines.next() {
+            Some((_, line)) => line?,
+            None => String::new(),
+        };
+        let frame_properties = parse_comment(&comment);
+        let columns = Columns::from_properties(&frame_properties)?;
+
+        let mut atoms = Vec::with_capacity(atom_count);
+        for serial_number in 1..=atom_count {
+            let Some((line_number, line)) = lines.next() else {
+                return Err(anyhow::format_err!(
+                    "frame {} is truncated, expected {} atoms but found {}",
+                    properties.len() + 1,
+                    atom_count,
+                    serial_number - 1
+                ));
+            };
+            atoms.push(
+                parse_atom(&line?, serial_number, &columns)
+                    .map_err(|error| anyhow::format_err!("line {}: {}", line_number + 1, error))?,
+            );
+        }
+
+        let model = single_residue_model(properties.len() + 1, RESIDUE_NAME, atoms)
+            .ok_or_else(|| anyhow::format_err!("frame {} is invalid", properties.len() + 1))?;
+        pdb.add_model(model);
+        properties.push(frame_properties);
+    }
+
+    if pdb.model_count() == 0 {
+        return Err(anyhow::format_err!(
+            "`{}` does not contain any frame",
+            path.display()
+        ));
+    }
+
+    Ok(LoadedStructure {
+        pdb,
+        bonds: vec![],
+        links: vec![],
+        secondary_structure: vec![],
+        format: StructureFormat::Xyz,
+        properties,
+        atom_properties: vec![],
+    })
+}
+
+/// Position of the species and coordinates in an atom line.
+/// Plain XYZ files use `species x y z`, extended XYZ files describe their columns with the
+/// `Properties` key of the comment line.
+struct Columns {
+    species: usize,
+    position: usize,
+}
+
+impl Columns {
+    /// Decode a `Properties=species:S:1:pos:R:3:...` description, where each column is described
+    /// by a `name:type:count` triplet.
+    fn from_properties(properties: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+        let Some(description) = properties.get("Properties") else {
+            return Ok(Self {
+                species: 0,
+                position: 1,
+            });
+        };
+
+        let fields = description.split(':').collect::<Vec<_>>();
+        if fields.len() % 3 != 0 {
+            return Err(anyhow::format_err!(
+                "invalid extended XYZ Properties `{}`",
+                description
+            ));
+        }
+
+        let mut species = None;
+        let mut position = None;
+        let mut column = 0;
+        for triplet in fields.chunks(3) {
+            let count = triplet[2].parse::<usize>().map_err(|_| {
+                anyhow::format_err!("invalid column count in Properties `{}`", description)
+            })?;
+            match triplet[0] {
+                "species" => species = Some(column),
+                "pos" if count == 3 => position = Some(column),
+                _ => {}
+            }
+            column += count;
+        }
+
+        match (species, position) {
+            (Some(species), Some(position)) => Ok(Self { species, position }),
+            _ => Err(anyhow::format_err!(
+                "extended XYZ Properties `{}` must describe `species` and `pos` columns",
+                description
+            )),
+        }
+    }
+}
+
+fn parse_atom(line: &str, serial_number: usize, columns: &Columns) -> anyhow::Result<Atom> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let field = |index: usize| {
+        fields
+            .get(index)
+            .copied()
+            .ok_or_else(|| anyhow::format_err!("missing column {} in `{}`", index + 1, line))
+    };
+
+    let species = field(columns.species)?;
+    let element = match species.parse::<usize>() {
+        Ok(atomic_number) => Element::new(atomic_number),
+        Err(_) => Element::from_symbol(species),
+    }
+    .ok_or_else(|| anyhow::format_err!("unknown element `{}`", species))?;
+
+    let mut coordinates = [0.0; 3];
+    for (axis, coordinate) in coordinates.iter_mut().enumerate() {
+        let value = field(columns.position + axis)?;
+        *coordinate = value
+            .parse::<f64>()
+            .map_err(|_| anyhow::format_err!("invalid coordinate `{}`", value))?;
+    }
+
+    Atom::new(
+        true,
+        serial_number,
+        element.symbol(),
+        coordinates[0],
+        coordinates[1],
+        coordinates[2],
+        1.0,
+        0.0,
+        element.symbol(),
+        0,
+    )
+    .ok_or_else(|| anyhow::format_err!("invalid atom `{}`", line))
+}
+
+/// Parse the comment line of a frame.
+/// Extended XYZ comment lines are made of `key=value` pairs, where values containing spaces are
+/// quoted, and keys without value are flags (`T`). A plain XYZ comment is kept as is under the
+/// `comment` key.
+fn parse_comment(comment: &str) -> BTreeMap<String, String> {
+    let mut properties = BTreeMap::new();
+    if !comment.contains('=') {
+        if !comment.trim().is_empty() {
+            properties.insert("comment".to_string(), comment.trim().to_string());
+        }
+        return properties;
+    }
+
+    let mut chars = comment.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+
+        let mut value = String::from("T");
+        if chars.next_if_eq(&'=').is_some() {
+            value.clear();
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(quote) => {
+                    for c in chars.by_ref() {
+                        if c == quote {
+                            break;
+                        }
+                        value.push(c);
+                    }
+                }
+                None => {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        value.push(c);
+                    }
+                }
+            }
+        }
+        properties.insert(key, value);
+    }
+    properties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::scratch_path;
+
+    #[test]
+    fn read_frames() {
+        let water = read(Path::new("resources/molecules/water.xyz")).unwrap();
+        assert_eq!(water.pdb.model_count(), 2);
+        assert_eq!(water.properties.len(), 2);
+
+        // Extended XYZ frame, the species follow the positions.
+        let first = water.pdb.model(0).unwrap();
+        let atoms = first.atoms().collect::<Vec<_>>();
+        assert_eq!(atoms.len(), 3);
+        assert_eq!(atoms[0].element(), Some(&Element::O));
+        assert_eq!(atoms[1].element(), Some(&Element::H));
+        assert_eq!(atoms[1].pos(), (0.0, 0.7572, -0.4692));
+        assert_eq!(water.properties[0]["energy"], "-14.2");
+        assert_eq!(water.properties[0]["pbc"], "T T T");
+        assert_eq!(
+            water.properties[0]["Lattice"],
+            "10.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 10.0"
+        );
+
+        // Plain XYZ frame, with an atomic number as species.
+        let second = water.pdb.model(1).unwrap();
+        let atoms = second.atoms().collect::<Vec<_>>();
+        assert_eq!(atoms[0].pos(), (0.0, 0.0, 0.12));
+        assert_eq!(atoms[2].element(), Some(&Element::H));
+        assert_eq!(water.properties[1]["comment"], "second frame");
+    }
+
+    #[test]
+    fn properties_columns() {
+        let columns = |comment: &str| Columns::from_properties(&parse_comment(comment));
+        let plain = columns("a plain comment").unwrap();
+        assert_eq!((plain.species, plain.position), (0, 1));
+        let extended = columns("Properties=species:S:1:pos:R:3").unwrap();
+        assert_eq!((extended.species, extended.position), (0, 1));
+        let reordered = columns("Properties=id:I:1:pos:R:3:mass:R:1:species:S:1").unwrap();
+        assert_eq!((reordered.species, reordered.position), (5, 1));
+
+        assert!(columns("Properties=species:S:1").is_err());
+        assert!(columns("Properties=species:S:1:pos:R").is_err());
+        assert!(columns("Properties=species:S:x:pos:R:3").is_err());
+    }
+
+    #[test]
+    fn comment_line() {
+        let properties = parse_comment(r#"key=value quoted="a b" single='c d' flag"#);
+        assert_eq!(properties["key"], "value");
+        assert_eq!(properties["quoted"], "a b");
+        assert_eq!(properties["single"], "c d");
+        assert_eq!(properties["flag"], "T");
+        assert!(parse_comment("  ").is_empty());
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let path = scratch_path("truncated.xyz");
+        std::fs::write(&path, "3\ncomment\nO 0.0 0.0 0.0\nH 0.0 0.8 -0.5\n").unwrap();
+        let error = read(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            error.to_string(),
+            "frame 1 is truncated, expected 3 atoms but found 2"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+
+use glium::{glutin::surface::WindowSurface, implement_vertex, program, Program, VertexBuffer};
+use nalgebra::{Point3, Point4, Vector2};
+
+/// Horizontal advance of a character, relative to the character height.
+const CHARACTER_ADVANCE: f32 = 0.8;
+
+/// Corners of the seven segments of a character cell, 0.5 wide and 1 high.
+const SEGMENTS: [[(f32, f32); 2]; 7] = [
+    [(0.0, 1.0), (0.5, 1.0)], // top
+    [(0.5, 1.0), (0.5, 0.5)], // top right
+    [(0.5, 0.5), (0.5, 0.0)], // bottom right
+    [(0.0, 0.0), (0.5, 0.0)], // bottom
+    [(0.0, 0.0), (0.0, 0.5)], // bottom left
+    [(0.0, 0.5), (0.0, 1.0)], // top left
+    [(0.0, 0.5), (0.5, 0.5)], // middle
+];
+
+/// Vertex of a label segment, every two vertices of a `LabelBatch` form a segment.
+/// `anchor` is the model space position the label is attached to, and `offset` the position of
+/// the vertex relative to the anchor in view space, so that the labels always face the camera.
+#[derive(Copy, Clone, Debug)]
+pub struct LabelVertex {
+    pub anchor: [f32; 3],
+    pub offset: [f32; 2],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(LabelVertex, anchor, offset, color);
+
+impl LabelVertex {
+    pub fn new(anchor: Point3<f32>, offset: Vector2<f32>, color: Point4<f32>) -> Self {
+        Self {
+            anchor: anchor.into(),
+            offset: offset.into(),
+            color: color.into(),
+        }
+    }
+}
+
+/// Segments of a character drawn in the seven segment style, in a cell 0.5 wide and 1 high.
+/// Digits, `-`, `.`, `°` and `Å` are supported, other characters are drawn as spaces.
+fn character_segments(character: char) -> Vec<[(f32, f32); 2]> {
+    // Bits of the segments lit, in the `SEGMENTS` order.
+    let mask: u8 = match character {
+        '0' => 0b0111111,
+        '1' => 0b0000110,
+        '2' => 0b1011011,
+        '3' => 0b1001111,
+        '4' => 0b1100110,
+        '5' => 0b1101101,
+        '6' => 0b1111101,
+        '7' => 0b0000111,
+        '8' => 0b1111111,
+        '9' => 0b1101111,
+        '-' => 0b1000000,
+        'Å' => 0b1110111,
+        _ => 0,
+    };
+    let mut segments = SEGMENTS
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| mask & (1 << index) != 0)
+        .map(|(_, segment)| *segment)
+        .collect::<Vec<_>>();
+
+    let square = |(x, y): (f32, f32), size: f32| {
+        [
+            [(x, y), (x + size, y)],
+            [(x + size, y), (x + size, y + size)],
+            [(x + size, y + size), (x, y + size)],
+            [(x, y + size), (x, y)],
+        ]
+    };
+    match character {
+        '.' => segments.extend(square((0.2, 0.0), 0.08)),
+        '°' => segments.extend(square((0.0, 0.75), 0.2)),
+        'Å' => segments.extend(square((0.17, 1.1), 0.16)),
+        _ => {}
+    }
+    segments
+}
+
+/// Segments drawing `text` next to `anchor`, `height` being the height of the characters in view
+/// space.
+pub fn text_vertices(
+    text: &str,
+    anchor: Point3<f32>,
+    height: f32,
+    color: Point4<f32>,
+) -> Vec<LabelVertex> {
+    let mut vertices = vec![];
+    for (index, character) in text.chars().enumerate() {
+        // Start a bit right of the anchor, so that the label doesn't cover it.
+        let origin = Vector2::new(0.5 + index as f32 * CHARACTER_ADVANCE, 0.5);
+        for [start, end] in character_segments(character) {
+            for (x, y) in [start, end] {
+                let offset = (origin + Vector2::new(x, y)) * height;
+                vertices.push(LabelVertex::new(anchor, offset, color));
+            }
+        }
+    }
+    vertices
+}
+
+/// This struct hold a batch of text labels drawn as unlit line segments facing the camera.
+/// `vertices` is the CPU copy of the segments, call `sync_buffer` to upload it after
+/// `update_vertices`.
+pub struct LabelBatch {
+    pub vertex_buffer: VertexBuffer<LabelVertex>,
+    pub vertices: Vec<LabelVertex>,
+}
+
+impl LabelBatch {
+    pub fn new(display: &glium::Display<WindowSurface>) -> anyhow::Result<Self> {
+        Ok(Self {
+            vertex_buffer: VertexBuffer::empty_dynamic(display, 0)?,
+            vertices: Vec::new(),
+        })
+    }
+
+    pub fn update_vertices(&mut self, vertices: &[LabelVertex]) {
+        self.vertices = vertices.to_vec();
+    }
+
+    pub fn sync_buffer(&mut self, display: &glium::Display<WindowSurface>) -> anyhow::Result<()> {
+        self.vertex_buffer = VertexBuffer::dynamic(display, &self.vertices)?;
+        Ok(())
+    }
+
+    /// Build the label GLSL Program and return it.
+    pub fn build_program(display: &glium::Display<WindowSurface>) -> anyhow::Result<Program> {
+        let vertex_shader = fs::read_to_string("./resources/shaders/label.vert")?;
+        let fragment_shader = fs::read_to_string("./resources/shaders/label.frag")?;
+
+        if vertex_shader.is_empty() || fragment_shader.is_empty() {
+            return Err(anyhow::format_err!(
+                "Fragment or Vertex shader file are empty"
+            ));
+        }
+
+        let program = program!(display,
+            410 => {
+                vertex: &vertex_shader,
+                fragment: &fragment_shader,
+            },
+        )?;
+        Ok(program)
+    }
+}
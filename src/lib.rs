@@ -2,14 +2,36 @@ use args::Args;
 use clap::Parser;
 use once_cell::sync::Lazy;
 
+pub mod animation;
 pub mod arcball;
 pub mod args;
 pub mod backend;
+pub mod bonding;
 pub mod camera;
+pub mod cartoon;
+pub mod clipping;
+pub mod coloring;
 pub mod cylinder_batch;
 pub mod geometry;
+pub mod hydrogen_bonds;
+pub mod io;
+pub mod label_batch;
+pub mod line_batch;
+pub mod measurement;
+pub mod mesh_batch;
 pub mod molecule;
+pub mod picking;
+pub mod representation;
+pub mod secondary_structure;
+pub mod selection;
+pub mod shaders;
+pub mod shadow;
+pub mod spatial_grid;
 pub mod sphere_batch;
+pub mod ssao;
+pub mod structure;
+pub mod surface;
+pub mod templates;
 
 /// These are the only version for which the program has been tested, on a macbook with apple
 /// sillicon, the program should work with more recent version, but i have no guarentee.
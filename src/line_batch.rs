@@ -0,0 +1,70 @@
+use std::fs;
+
+use glium::{glutin::surface::WindowSurface, implement_vertex, program, Program, VertexBuffer};
+use nalgebra::{Point3, Point4};
+
+use crate::shaders;
+
+/// Vertex of a line segment, every two vertices of a `LineBatch` form a segment.
+#[derive(Copy, Clone, Debug)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(LineVertex, position, color);
+
+impl LineVertex {
+    pub fn new(position: Point3<f32>, color: Point4<f32>) -> Self {
+        Self {
+            position: position.into(),
+            color: color.into(),
+        }
+    }
+}
+
+/// This struct hold a batch of unlit line segments (wireframe representation).
+/// `vertices` is the CPU copy of the segments, call `sync_buffer` to upload it after
+/// `update_vertices`.
+pub struct LineBatch {
+    pub vertex_buffer: VertexBuffer<LineVertex>,
+    pub vertices: Vec<LineVertex>,
+}
+
+impl LineBatch {
+    pub fn new(display: &glium::Display<WindowSurface>) -> anyhow::Result<Self> {
+        Ok(Self {
+            vertex_buffer: VertexBuffer::empty_dynamic(display, 0)?,
+            vertices: Vec::new(),
+        })
+    }
+
+    pub fn update_vertices(&mut self, vertices: &[LineVertex]) {
+        self.vertices = vertices.to_vec();
+    }
+
+    pub fn sync_buffer(&mut self, display: &glium::Display<WindowSurface>) -> anyhow::Result<()> {
+        self.vertex_buffer = VertexBuffer::dynamic(display, &self.vertices)?;
+        Ok(())
+    }
+
+    /// Build the line GLSL Program and return it.
+    pub fn build_program(display: &glium::Display<WindowSurface>) -> anyhow::Result<Program> {
+        let vertex_shader = fs::read_to_string("./resources/shaders/line.vert")?;
+        let fragment_shader = shaders::read_shader("line.frag", &["clipping.glsl"])?;
+
+        if vertex_shader.is_empty() || fragment_shader.is_empty() {
+            return Err(anyhow::format_err!(
+                "Fragment or Vertex shader file are empty"
+            ));
+        }
+
+        let program = program!(display,
+            410 => {
+                vertex: &vertex_shader,
+                fragment: &fragment_shader,
+            },
+        )?;
+        Ok(program)
+    }
+}
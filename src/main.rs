@@ -8,54 +8,114 @@ use glium::{
     uniforms::Uniforms,
     winit::{
         dpi::PhysicalPosition,
-        event::{ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
+        event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
+        keyboard::{Key, ModifiersState, NamedKey},
     },
     Frame, Program, Surface,
 };
 use molecular_visualization::{
     arcball::ArcballControl,
     backend::{ApplicationContext, FpsCounter, State},
-    camera::{Camera, PerspectiveCamera, Ready, Virtual},
+    camera::{Camera, OrthographicCamera, PerspectiveCamera, Ready, Virtual},
+    clipping::{ClippingSlab, FogMode},
     cylinder_batch::CylinderBatch,
     geometry::{Model, Rotate, Scale},
+    label_batch::LabelBatch,
+    line_batch::LineBatch,
+    measurement,
+    mesh_batch::MeshBatch,
     molecule::Molecule,
+    picking::{Pick, Ray},
+    shadow::ShadowMap,
     sphere_batch::SphereBatch,
+    ssao::{SsaoParameters, SsaoPass},
     ARGS,
 };
 use nalgebra::{Matrix4, Point3, Vector3};
 
+/// Color of the background, where nothing is drawn.
+const BACKGROUND_COLOR: (f32, f32, f32, f32) = (0.1294, 0.1294, 0.1294, 1.0);
+
+/// Distance (in Å) the clipping planes move by for each step of the mouse wheel.
+const CLIPPING_SCROLL_STEP: f32 = 0.5;
+
 /// OpenGL Application wrapper,
 /// contains all the necessary informations to make the program run,
 /// for more informations on how the glium/winit backend is running, see `backend.rs`.
 struct Application {
-    pub camera: PerspectiveCamera<Ready>,
+    pub perspective_camera: PerspectiveCamera<Ready>,
+    pub orthographic_camera: OrthographicCamera<Ready>,
+    /// Whether the orthographic camera is used rather than the perspective one.
+    pub orthographic: bool,
     pub arcball: ArcballControl,
     pub last_cursor_position: Option<PhysicalPosition<f64>>,
+    pub modifiers: ModifiersState,
     pub molecule: Molecule,
     pub sphere_instances_program: Program,
     pub cylinder_instance_program: Program,
+    pub mesh_program: Program,
+    pub line_program: Program,
+    pub label_program: Program,
+    pub ssao: SsaoPass,
+    pub shadow_map: ShadowMap,
+    pub clipping: ClippingSlab,
+    pub fog: FogMode,
     light: Point3<f32>,
     fps_counter: FpsCounter,
 }
 
 impl Application {
-    fn get_uniforms(&mut self, frame: &Frame) -> impl Uniforms {
+    fn camera(&self) -> &dyn Camera {
+        match self.orthographic {
+            true => &self.orthographic_camera,
+            false => &self.perspective_camera,
+        }
+    }
+
+    fn camera_mut(&mut self) -> &mut dyn Camera {
+        match self.orthographic {
+            true => &mut self.orthographic_camera,
+            false => &mut self.perspective_camera,
+        }
+    }
+
+    /// Switch between the perspective and orthographic projections, keeping the framing of the
+    /// molecule center.
+    fn toggle_projection(&mut self) {
+        self.orthographic = !self.orthographic;
+        match self.orthographic {
+            true => self
+                .orthographic_camera
+                .match_perspective(&self.perspective_camera),
+            false => self
+                .perspective_camera
+                .match_orthographic(&self.orthographic_camera),
+        }
+    }
+
+    fn update_model_matrix(&mut self) {
         self.molecule.reset_model_matrix();
         self.molecule
             .scale(Matrix4::new_scaling(self.molecule.scale_factor));
         self.molecule.rotate(self.arcball.get_rotation_matrix());
+    }
+
+    fn get_uniforms(&self, frame: &Frame) -> impl Uniforms + '_ {
         let molecule_model: [[f32; 4]; 4] = self.molecule.model_matrix().into();
 
-        let view: [[f32; 4]; 4] = self.camera.get_view_matrix().into();
+        let view: [[f32; 4]; 4] = self.camera().get_view_matrix().into();
 
         // HACK - the aspect ratio is passed dynamically at each frame mainly to avoid scaling with
         // a fixed base aspect ratio.
         let (width, height) = frame.get_dimensions();
         let aspect_ratio = width as f32 / height as f32;
-        let projection: [[f32; 4]; 4] = self.camera.get_projection_matrix(aspect_ratio).into();
+        let projection: [[f32; 4]; 4] = self.camera().get_projection_matrix(aspect_ratio).into();
 
         let light: [f32; 3] = self.light.into();
-        let camera_position: [f32; 3] = self.camera.get_position().into();
+        let camera_position: [f32; 3] = self.camera().get_position().into();
+        let light_space: [[f32; 4]; 4] = self.shadow_map.light_space(&self.light).into();
+        let (clip_near, clip_far) = self.clipping_depths();
+        let (red, green, blue, _) = BACKGROUND_COLOR;
 
         uniform! {
             view: view,
@@ -65,6 +125,427 @@ impl Application {
             debug_billboard: false,
             model: molecule_model,
             u_show_silhouette: self.molecule.show_silhouette,
+            parallel_rays: self.camera().is_orthographic(),
+            depth_only: false,
+            shadows: self.shadow_map.enabled,
+            shadow_map: self.shadow_map.sampler(),
+            light_space: light_space,
+            shadow_bias: self.shadow_map.bias(),
+            clip_view: view,
+            clip_near: clip_near,
+            clip_far: clip_far,
+            fog_mode: self.fog.shader_mode(),
+            fog_start: clip_near,
+            fog_end: clip_far,
+            fog_density: ARGS.fog_density,
+            fog_color: [red, green, blue],
+        }
+    }
+
+    /// Depths of the near and far clipping planes along the camera view direction, see
+    /// `ClippingSlab::view_depths`.
+    fn clipping_depths(&self) -> (f32, f32) {
+        self.clipping.view_depths(
+            self.camera().get_position().coords.norm(),
+            self.molecule.scale_factor,
+        )
+    }
+
+    /// Uniforms of the shadow pass, the scene is seen from the light with parallel rays and only
+    /// its depth is written. The clipping slab stays the one of the camera, so that the clipped
+    /// atoms cast no shadow.
+    fn get_shadow_uniforms(&self) -> impl Uniforms {
+        let molecule_model: [[f32; 4]; 4] = self.molecule.model_matrix().into();
+        let camera_view: [[f32; 4]; 4] = self.camera().get_view_matrix().into();
+        let (clip_near, clip_far) = self.clipping_depths();
+        let view: [[f32; 4]; 4] = self.shadow_map.light_view(&self.light).into();
+        let projection: [[f32; 4]; 4] = self.shadow_map.light_projection().into();
+        let light: [f32; 3] = self.light.into();
+        let light_eye: [f32; 3] = self.shadow_map.light_eye(&self.light).into();
+
+        uniform! {
+            view: view,
+            projection: projection,
+            light_position: light,
+            camera_position: light_eye,
+            debug_billboard: false,
+            model: molecule_model,
+            u_show_silhouette: false,
+            parallel_rays: true,
+            depth_only: true,
+            shadows: false,
+            clip_view: camera_view,
+            clip_near: clip_near,
+            clip_far: clip_far,
+        }
+    }
+
+    /// Frame playback controls: `Space` play/pause, `Left`/`Right` step and `L` toggle looping.
+    /// `H` cycles through the hydrogen bonds display modes, `C` toggles the cartoon and `R`
+    /// cycles the representation of the last `--representation` assignment, `K` cycles the
+    /// coloring and `S` the surface, `O` toggles the ambient occlusion and `D` the shadows, `F`
+    /// cycles the fog, `P` toggles the orthographic projection.
+    /// `M` cycles through the measurement modes, `Backspace` removes the last measurement and `E`
+    /// exports the measurements.
+    fn handle_key(&mut self, key: &Key) {
+        match key {
+            Key::Character(c) if c.eq_ignore_ascii_case("m") => {
+                self.molecule.cycle_measurement_mode();
+                match self.molecule.measurement_mode {
+                    Some(mode) => println!(
+                        "Measurement mode: {} (pick {} atoms)",
+                        mode.name(),
+                        mode.atom_count()
+                    ),
+                    None => println!("Measurement mode: off"),
+                }
+                return;
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.molecule.remove_last_measurement();
+                println!("{} measurements", self.molecule.measurements.len());
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("e") => {
+                if let Err(error) = self.molecule.export_measurements() {
+                    eprintln!("Error: failed to export the measurements: {error:#}");
+                }
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("k") => {
+                self.molecule.cycle_coloring();
+                println!("Coloring: {:?}", self.molecule.coloring);
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("r") => {
+                self.molecule.cycle_representation();
+                if let Some(assignment) = self.molecule.representations.last() {
+                    println!("Representation: {assignment}");
+                }
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("s") => {
+                self.molecule.cycle_surface();
+                match self.molecule.surface_kind {
+                    Some(kind) if self.molecule.playback.is_playing() => {
+                        println!("Surface: {kind:?} (built when the playback pauses)")
+                    }
+                    Some(kind) => println!(
+                        "Surface: {kind:?} ({} triangles)",
+                        self.molecule.surface.mesh.indices.len() / 3
+                    ),
+                    None => println!("Surface: off"),
+                }
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("o") => {
+                self.ssao.enabled = !self.ssao.enabled;
+                println!(
+                    "Ambient occlusion: {}",
+                    if self.ssao.enabled { "on" } else { "off" }
+                );
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("d") => {
+                self.shadow_map.enabled = !self.shadow_map.enabled;
+                println!(
+                    "Shadows: {}",
+                    if self.shadow_map.enabled { "on" } else { "off" }
+                );
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("f") => {
+                self.fog = self.fog.next();
+                println!("Fog: {:?}", self.fog);
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("p") => {
+                self.toggle_projection();
+                println!(
+                    "Projection: {}",
+                    if self.orthographic {
+                        "orthographic"
+                    } else {
+                        "perspective"
+                    }
+                );
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("c") => {
+                self.molecule.toggle_cartoon();
+                return;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("h") => {
+                self.molecule.cycle_hydrogen_bonds();
+                println!(
+                    "Hydrogen bonds: {:?} ({} shown)",
+                    self.molecule.hydrogen_bond_display,
+                    self.molecule.hydrogen_bonds.instances.len()
+                );
+                return;
+            }
+            Key::Named(NamedKey::Space) => self.molecule.playback.toggle_play(),
+            Key::Named(NamedKey::ArrowRight) => self.molecule.step_frame(1),
+            Key::Named(NamedKey::ArrowLeft) => self.molecule.step_frame(-1),
+            Key::Character(c) if c.eq_ignore_ascii_case("l") => {
+                self.molecule.playback.toggle_loop()
+            }
+            _ => return,
+        }
+
+        let playback = &self.molecule.playback;
+        println!(
+            "Frame {}/{} ({}, loop {})",
+            playback.current_frame() + 1,
+            playback.frame_count(),
+            if playback.is_playing() {
+                "playing"
+            } else {
+                "paused"
+            },
+            if playback.is_looping() { "on" } else { "off" },
+        );
+    }
+
+    fn print_clipping(&self) {
+        println!(
+            "Clipping slab: {:.1} Å to {:.1} Å from the center",
+            self.clipping.near, self.clipping.far
+        );
+    }
+
+    /// Cast a ray from the cursor through the inverse of the camera and model matrices, and print
+    /// the atom (or bond) under it. In a measurement mode, the picked atom is added to the
+    /// measurement in progress.
+    fn pick(&mut self, window: &glium::winit::window::Window) {
+        let Some(cursor) = self.last_cursor_position else {
+            return;
+        };
+        let size = window.inner_size();
+        let (width, height) = (size.width as f32, size.height as f32);
+        let transform = self.camera().get_projection_matrix(width / height)
+            * self.camera().get_view_matrix()
+            * self.molecule.model_matrix();
+        let Some(ray) = transform.try_inverse().and_then(|inverse| {
+            Ray::from_cursor(
+                (cursor.x as f32, cursor.y as f32),
+                (width, height),
+                &inverse,
+            )
+        }) else {
+            return;
+        };
+        let Some(pick) = self.molecule.pick(&ray) else {
+            println!("Nothing picked");
+            return;
+        };
+        println!("{}", self.molecule.describe_pick(pick));
+
+        let Pick::Atom(atom) = pick else {
+            return;
+        };
+        let Some(measurement) = self.molecule.add_measurement_atom(atom) else {
+            return;
+        };
+        let atoms = measurement
+            .atoms
+            .iter()
+            .map(|&atom| measurement::atom_label(&self.molecule.structure, atom))
+            .collect::<Vec<_>>();
+        println!(
+            "{:?} {}: {}",
+            measurement.kind,
+            self.molecule.measurement_label(&measurement),
+            atoms.join(" - ")
+        );
+    }
+    /// Draw the atoms, bonds, cartoon and opaque surface into the shadow map.
+    fn draw_shadow_casters(&self, target: &mut impl Surface, uniforms: &impl Uniforms) {
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let atoms = &self.molecule.atoms;
+        if !atoms.instances.is_empty() {
+            target
+                .draw(
+                    (
+                        &atoms.vertex_buffer,
+                        atoms.instance_buffer.per_instance().unwrap(),
+                    ),
+                    &atoms.index_buffer,
+                    &self.sphere_instances_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Shadow draw call have failed");
+        }
+        let bonds = &self.molecule.bonds;
+        if !bonds.instances.is_empty() {
+            target
+                .draw(
+                    (
+                        &bonds.vertex_buffer,
+                        bonds.instance_buffer.per_instance().unwrap(),
+                    ),
+                    &bonds.index_buffer,
+                    &self.cylinder_instance_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Shadow draw call have failed");
+        }
+        let mut meshes = vec![&self.molecule.cartoon];
+        if ARGS.surface_opacity >= 1.0 {
+            meshes.push(&self.molecule.surface);
+        }
+        for batch in meshes {
+            if !batch.mesh.is_empty() {
+                target
+                    .draw(
+                        &batch.vertex_buffer,
+                        &batch.index_buffer,
+                        &self.mesh_program,
+                        uniforms,
+                        &params,
+                    )
+                    .expect("Shadow draw call have failed");
+            }
+        }
+    }
+
+    /// Draw the molecule (everything but the labels) to `target`, either the window or the
+    /// ambient occlusion geometry buffer, every program must then write `frag_normal`.
+    fn draw_scene(&self, target: &mut impl Surface, uniforms: &impl Uniforms) {
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        if !self.molecule.atoms.instances.is_empty() {
+            target
+                .draw(
+                    (
+                        &self.molecule.atoms.vertex_buffer,
+                        self.molecule.atoms.instance_buffer.per_instance().unwrap(),
+                    ),
+                    &self.molecule.atoms.index_buffer,
+                    &self.sphere_instances_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Frame draw call have failed");
+        }
+
+        if !self.molecule.bonds.instances.is_empty() {
+            target
+                .draw(
+                    (
+                        &self.molecule.bonds.vertex_buffer,
+                        self.molecule.bonds.instance_buffer.per_instance().unwrap(),
+                    ),
+                    &self.molecule.bonds.index_buffer,
+                    &self.cylinder_instance_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Frame draw call have failed");
+        }
+
+        if !self.molecule.lines.vertices.is_empty() {
+            target
+                .draw(
+                    &self.molecule.lines.vertex_buffer,
+                    glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+                    &self.line_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Frame draw call have failed");
+        }
+
+        if !self.molecule.hydrogen_bonds.instances.is_empty() {
+            target
+                .draw(
+                    (
+                        &self.molecule.hydrogen_bonds.vertex_buffer,
+                        self.molecule
+                            .hydrogen_bonds
+                            .instance_buffer
+                            .per_instance()
+                            .unwrap(),
+                    ),
+                    &self.molecule.hydrogen_bonds.index_buffer,
+                    &self.cylinder_instance_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Frame draw call have failed");
+        }
+
+        if !self.molecule.measurement_lines.instances.is_empty() {
+            target
+                .draw(
+                    (
+                        &self.molecule.measurement_lines.vertex_buffer,
+                        self.molecule
+                            .measurement_lines
+                            .instance_buffer
+                            .per_instance()
+                            .unwrap(),
+                    ),
+                    &self.molecule.measurement_lines.index_buffer,
+                    &self.cylinder_instance_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Frame draw call have failed");
+        }
+
+        if !self.molecule.cartoon.mesh.is_empty() {
+            target
+                .draw(
+                    &self.molecule.cartoon.vertex_buffer,
+                    &self.molecule.cartoon.index_buffer,
+                    &self.mesh_program,
+                    uniforms,
+                    &params,
+                )
+                .expect("Frame draw call have failed");
+        }
+
+        // A translucent surface is drawn after the opaque geometry, blended with it and without
+        // hiding what lies behind it.
+        if !self.molecule.surface.mesh.is_empty() {
+            let surface_params = match ARGS.surface_opacity < 1.0 {
+                true => glium::DrawParameters {
+                    depth: glium::Depth {
+                        test: glium::DepthTest::IfLess,
+                        write: false,
+                        ..Default::default()
+                    },
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+                false => params.clone(),
+            };
+            target
+                .draw(
+                    &self.molecule.surface.vertex_buffer,
+                    &self.molecule.surface.index_buffer,
+                    &self.mesh_program,
+                    uniforms,
+                    &surface_params,
+                )
+                .expect("Frame draw call have failed");
         }
     }
 }
@@ -79,43 +560,105 @@ impl ApplicationContext for Application {
         let camera_pos = Point3::new(0.0, 0.0, 4.0);
         let camera_target = Point3::new(0.0, 0.0, 0.0);
         let camera_up = Vector3::y();
-        let camera = PerspectiveCamera::<Virtual> {
+        let perspective_camera = PerspectiveCamera::<Virtual> {
+            ..Default::default()
+        }
+        .place(camera_pos)
+        .point(camera_target, camera_up);
+        let mut orthographic_camera = OrthographicCamera::<Virtual> {
             ..Default::default()
         }
         .place(camera_pos)
         .point(camera_target, camera_up);
+        orthographic_camera.match_perspective(&perspective_camera);
 
         let mut molecule = Molecule::initialize_instances(display)
             .expect("Molecule have failed to initialize instances");
 
-        molecule
-            .init_molecule()
-            .expect("Failed to populate molecule instances");
+        // A structure that can't be read is a user error rather than a bug, report it and exit
+        // instead of panicking.
+        if let Err(error) = molecule.init_molecule() {
+            eprintln!("Error: {error:#}");
+            std::process::exit(1);
+        }
 
         molecule
             .sync_buffers(display)
             .expect("Failed to synchronize the molecule vertex buffer");
 
+        // The occlusion radius is given in Å, the molecule is scaled to fit the view.
+        let ssao = SsaoPass::new(
+            display,
+            ARGS.ssao,
+            SsaoParameters {
+                strength: ARGS.ssao_strength,
+                radius: ARGS.ssao_radius * molecule.scale_factor,
+                samples: ARGS.ssao_samples,
+            },
+        )
+        .expect("Ambient occlusion shader programs have failed to build");
+
+        let shadow_map = ShadowMap::new(
+            display,
+            ARGS.shadows,
+            ARGS.shadow_resolution,
+            molecule.bounding_radius(),
+        )
+        .expect("Shadow map has failed to be created");
+
+        let clipping = match ClippingSlab::new(
+            molecule.bounding_radius() / molecule.scale_factor,
+            ARGS.clip_near,
+            ARGS.clip_far,
+        ) {
+            Ok(clipping) => clipping,
+            Err(error) => {
+                eprintln!("Error: {error:#}");
+                std::process::exit(1);
+            }
+        };
+
         Self {
-            camera,
+            perspective_camera,
+            orthographic_camera,
+            orthographic: ARGS.orthographic,
             arcball,
             last_cursor_position: None,
+            modifiers: ModifiersState::empty(),
             molecule,
             sphere_instances_program: SphereBatch::build_program(display)
                 .expect("Sphere shader program has failed to build"),
             cylinder_instance_program: CylinderBatch::build_program(display)
                 .expect("Cylinder shader program has failed to build"),
+            mesh_program: MeshBatch::build_program(display)
+                .expect("Mesh shader program has failed to build"),
+            line_program: LineBatch::build_program(display)
+                .expect("Line shader program has failed to build"),
+            label_program: LabelBatch::build_program(display)
+                .expect("Label shader program has failed to build"),
+            ssao,
+            shadow_map,
+            clipping,
+            fog: ARGS.fog,
             light: Point3::new(0.0, 2.0, 1.0),
             fps_counter: FpsCounter::new(),
         }
     }
 
+    /// `Shift` + `Left-Click` picks the atom under the cursor instead of rotating the molecule.
+    /// `Ctrl` (or `Alt`) + `Mouse-Wheel` moves the near (or far) clipping plane instead of zooming.
     fn handle_window_event(
         &mut self,
         event: &glium::winit::event::WindowEvent,
-        _window: &glium::winit::window::Window,
+        window: &glium::winit::window::Window,
     ) {
         match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.modifiers.shift_key() => self.pick(window),
             WindowEvent::CursorMoved { position, .. } => {
                 self.last_cursor_position = Some(*position);
                 self.arcball
@@ -147,66 +690,102 @@ impl ApplicationContext for Application {
                     MouseScrollDelta::LineDelta(_, y) => *y,
                     MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.1,
                 };
-                self.camera.zoom(scroll_amount);
+                if self.modifiers.control_key() {
+                    self.clipping
+                        .move_near(scroll_amount * CLIPPING_SCROLL_STEP);
+                    self.print_clipping();
+                } else if self.modifiers.alt_key() {
+                    self.clipping.move_far(scroll_amount * CLIPPING_SCROLL_STEP);
+                    self.print_clipping();
+                } else {
+                    self.camera_mut().zoom(scroll_amount);
+                }
             }
             WindowEvent::Resized(size) => {
                 self.arcball.resize(size.width as f32, size.height as f32);
             }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        logical_key,
+                        ..
+                    },
+                ..
+            } => self.handle_key(logical_key),
             _ => {}
         }
     }
 
+    fn update(&mut self) {
+        self.molecule.update();
+    }
+
     fn draw_frame(&mut self, display: &glium::Display<WindowSurface>) {
         self.fps_counter.update();
         if ARGS.fps {
             println!("FPS: {}", self.fps_counter.fps);
         }
-        let mut frame = display.draw();
-        let uniforms = self.get_uniforms(&frame);
+        if self.molecule.buffers_outdated() {
+            self.molecule
+                .sync_buffers(display)
+                .expect("Failed to synchronize the molecule vertex buffer");
+        }
 
-        self.arcball.resize(
-            frame.get_dimensions().0 as f32,
-            frame.get_dimensions().1 as f32,
-        );
+        let mut frame = display.draw();
+        let (width, height) = frame.get_dimensions();
+        self.arcball.resize(width as f32, height as f32);
+        self.update_model_matrix();
+        if self.ssao.enabled {
+            self.ssao
+                .resize(display, (width, height))
+                .expect("Failed to create the ambient occlusion buffers");
+        }
 
         assert!(self.molecule.atoms.index_buffer.get_size() != 0);
         assert!(self.molecule.atoms.vertex_buffer.get_size() != 0);
 
-        let params = glium::DrawParameters {
-            depth: glium::Depth {
-                test: glium::DepthTest::IfLess,
-                write: true,
-                ..Default::default()
-            },
-            ..Default::default()
-        };
+        if self.shadow_map.enabled {
+            let mut target = self
+                .shadow_map
+                .framebuffer(display)
+                .expect("Failed to create the shadow map framebuffer");
+            target.clear_depth(1.0);
+            self.draw_shadow_casters(&mut target, &self.get_shadow_uniforms());
+        }
 
-        frame.clear_color_and_depth((0.1294, 0.1294, 0.1294, 1.0), 1.0);
-        frame
-            .draw(
-                (
-                    &self.molecule.atoms.vertex_buffer,
-                    self.molecule.atoms.instance_buffer.per_instance().unwrap(),
-                ),
-                &self.molecule.atoms.index_buffer,
-                &self.sphere_instances_program,
-                &uniforms,
-                &params,
-            )
-            .expect("Frame draw call have failed");
-
-        frame
-            .draw(
-                (
-                    &self.molecule.bonds.vertex_buffer,
-                    self.molecule.bonds.instance_buffer.per_instance().unwrap(),
-                ),
-                &self.molecule.bonds.index_buffer,
-                &self.cylinder_instance_program,
-                &uniforms,
-                &params,
-            )
-            .expect("Frame draw call have failed");
+        let uniforms = self.get_uniforms(&frame);
+        if self.ssao.enabled {
+            let projection = self
+                .camera()
+                .get_projection_matrix(width as f32 / height as f32);
+            let mut target = self
+                .ssao
+                .scene_framebuffer(display)
+                .expect("Failed to create the ambient occlusion framebuffer");
+            target.clear_color_and_depth(BACKGROUND_COLOR, 1.0);
+            self.draw_scene(&mut target, &uniforms);
+            frame.clear_depth(1.0);
+            self.ssao
+                .draw(display, &mut frame, &projection)
+                .expect("Ambient occlusion draw call have failed");
+        } else {
+            frame.clear_color_and_depth(BACKGROUND_COLOR, 1.0);
+            self.draw_scene(&mut frame, &uniforms);
+        }
+
+        // Labels are drawn last and on top of everything, so that they are always readable.
+        if !self.molecule.measurement_labels.vertices.is_empty() {
+            frame
+                .draw(
+                    &self.molecule.measurement_labels.vertex_buffer,
+                    glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+                    &self.label_program,
+                    &uniforms,
+                    &Default::default(),
+                )
+                .expect("Frame draw call have failed");
+        }
 
         frame.finish().unwrap();
     }
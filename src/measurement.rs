@@ -0,0 +1,293 @@
+use std::{io::Write, ops::Range};
+
+use nalgebra::Point3;
+
+use crate::structure::Structure;
+
+/// Kind of a measurement, given by its number of atoms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementKind {
+    Distance,
+    Angle,
+    Dihedral,
+}
+
+impl MeasurementKind {
+    /// Next measurement mode, `None` being the plain picking, used to cycle through the modes at
+    /// runtime.
+    pub fn next(mode: Option<Self>) -> Option<Self> {
+        match mode {
+            None => Some(Self::Distance),
+            Some(Self::Distance) => Some(Self::Angle),
+            Some(Self::Angle) => Some(Self::Dihedral),
+            Some(Self::Dihedral) => None,
+        }
+    }
+
+    pub fn atom_count(&self) -> usize {
+        match self {
+            Self::Distance => 2,
+            Self::Angle => 3,
+            Self::Dihedral => 4,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Distance => "distance",
+            Self::Angle => "angle",
+            Self::Dihedral => "dihedral",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Distance => "Å",
+            Self::Angle | Self::Dihedral => "°",
+        }
+    }
+}
+
+/// Distance, angle or dihedral between atoms, referenced by their index in `Structure::atoms`.
+/// The angle is measured at the second atom, and the dihedral around the bond between the second
+/// and third atoms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Measurement {
+    pub kind: MeasurementKind,
+    pub atoms: Vec<usize>,
+}
+
+impl Measurement {
+    /// Measurement of the given atoms, `None` when their count is not 2, 3 or 4.
+    pub fn new(atoms: Vec<usize>) -> Option<Self> {
+        let kind = match atoms.len() {
+            2 => MeasurementKind::Distance,
+            3 => MeasurementKind::Angle,
+            4 => MeasurementKind::Dihedral,
+            _ => return None,
+        };
+        Some(Self { kind, atoms })
+    }
+
+    /// Measurement of the frame whose atoms are `frame` in `Structure::atoms`, from a measurement
+    /// with atoms relative to the first atom of a frame. `None` when an atom is missing from the
+    /// frame (a model with fewer atoms).
+    pub fn in_frame(&self, frame: &Range<usize>) -> Option<Self> {
+        let atoms = self
+            .atoms
+            .iter()
+            .map(|&atom| Some(frame.start + atom).filter(|atom| frame.contains(atom)))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            kind: self.kind,
+            atoms,
+        })
+    }
+
+    /// Value of the measurement, in Å or degrees, `position` giving the position of an atom.
+    pub fn value(&self, position: impl Fn(usize) -> Point3<f32>) -> f32 {
+        let points = self
+            .atoms
+            .iter()
+            .map(|&atom| position(atom))
+            .collect::<Vec<_>>();
+        match self.kind {
+            MeasurementKind::Distance => distance(&points[0], &points[1]),
+            MeasurementKind::Angle => angle(&points[0], &points[1], &points[2]),
+            MeasurementKind::Dihedral => dihedral(&points[0], &points[1], &points[2], &points[3]),
+        }
+    }
+
+    /// Value with its unit, `-60.2°` or `3.52 Å`.
+    pub fn label(&self, position: impl Fn(usize) -> Point3<f32>) -> String {
+        match self.kind {
+            MeasurementKind::Distance => format!("{:.2} Å", self.value(position)),
+            MeasurementKind::Angle | MeasurementKind::Dihedral => {
+                format!("{:.1}°", self.value(position))
+            }
+        }
+    }
+}
+
+/// Add a picked atom of the frame whose atoms are `frame` in `Structure::atoms` to the `pending`
+/// atoms of a measurement of the given kind, stored relative to the first atom of the frame (see
+/// `Measurement::in_frame`). Return the measurement, with relative atoms, once it has all its
+/// atoms. Picking the same atom twice in a row is ignored.
+pub fn add_pending_atom(
+    pending: &mut Vec<usize>,
+    kind: MeasurementKind,
+    frame: &Range<usize>,
+    atom: usize,
+) -> Option<Measurement> {
+    if !frame.contains(&atom) {
+        return None;
+    }
+    let atom = atom - frame.start;
+    if pending.last() == Some(&atom) {
+        return None;
+    }
+    pending.push(atom);
+    if pending.len() < kind.atom_count() {
+        return None;
+    }
+    Measurement::new(std::mem::take(pending))
+}
+
+pub fn distance(first: &Point3<f32>, second: &Point3<f32>) -> f32 {
+    (second - first).norm()
+}
+
+/// Angle (in degrees) between the `first` and `third` points, at the `second` one.
+pub fn angle(first: &Point3<f32>, second: &Point3<f32>, third: &Point3<f32>) -> f32 {
+    (first - second).angle(&(third - second)).to_degrees()
+}
+
+/// Signed dihedral angle (in degrees, from -180 to 180) between the planes of the three first and
+/// three last points, following the IUPAC convention (0 for cis, 180 for trans).
+pub fn dihedral(
+    first: &Point3<f32>,
+    second: &Point3<f32>,
+    third: &Point3<f32>,
+    fourth: &Point3<f32>,
+) -> f32 {
+    let b1 = second - first;
+    let b2 = third - second;
+    let b3 = fourth - third;
+    let n1 = b1.cross(&b2);
+    let n2 = b2.cross(&b3);
+    (b2.norm() * b1.dot(&n2)).atan2(n1.dot(&n2)).to_degrees()
+}
+
+/// Short name of an atom, `chain/residue name residue number/atom name`, such as `A/HIS12/CA`.
+pub fn atom_label(structure: &Structure, atom: usize) -> String {
+    let residue = structure.residue(atom);
+    format!(
+        "{}/{}{}{}/{}",
+        structure.chain(atom).id.trim(),
+        residue.name.trim(),
+        residue.serial_number,
+        residue.insertion_code.as_deref().unwrap_or_default(),
+        structure.atoms[atom].name.trim(),
+    )
+}
+
+/// Write the measurements as CSV, one measurement per line with its kind, value, unit and atoms
+/// (see `atom_label`), `position` giving the position of an atom.
+pub fn write_csv(
+    measurements: &[Measurement],
+    structure: &Structure,
+    position: impl Fn(usize) -> Point3<f32>,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    writeln!(writer, "kind,value,unit,atom_1,atom_2,atom_3,atom_4")?;
+    for measurement in measurements {
+        let mut atoms = measurement
+            .atoms
+            .iter()
+            .map(|&atom| atom_label(structure, atom))
+            .collect::<Vec<_>>();
+        atoms.resize(4, String::new());
+        writeln!(
+            writer,
+            "{},{:.3},{},{}",
+            measurement.kind.name(),
+            measurement.value(&position),
+            measurement.kind.unit(),
+            atoms.join(","),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::io::LoadOptions;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-3, "{value} != {expected}");
+    }
+
+    /// Dihedral of a `first` atom along x and a `fourth` atom rotated by `angle` (in degrees)
+    /// around the z axis, the second and third atoms being on the z axis.
+    fn rotated_dihedral(angle: f32) -> f32 {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        dihedral(
+            &Point3::new(1.0, 0.0, 0.0),
+            &Point3::origin(),
+            &Point3::new(0.0, 0.0, 1.5),
+            &Point3::new(cos, sin, 1.5),
+        )
+    }
+
+    #[test]
+    fn distance_and_angle() {
+        let origin = Point3::origin();
+        assert_close(distance(&origin, &Point3::new(1.0, 2.0, 2.0)), 3.0);
+        let x = Point3::new(2.0, 0.0, 0.0);
+        assert_close(angle(&x, &origin, &Point3::new(0.0, 3.0, 0.0)), 90.0);
+        assert_close(angle(&x, &origin, &Point3::new(-1.0, 0.0, 0.0)), 180.0);
+        assert_close(angle(&x, &origin, &Point3::new(1.0, 1.0, 0.0)), 45.0);
+    }
+
+    #[test]
+    fn dihedral_angles() {
+        // Cis, trans and the clockwise (positive) and counterclockwise rotations, looking from
+        // the second atom to the third one.
+        assert_close(rotated_dihedral(0.0), 0.0);
+        assert_close(rotated_dihedral(180.0).abs(), 180.0);
+        assert_close(rotated_dihedral(90.0), 90.0);
+        assert_close(rotated_dihedral(-90.0), -90.0);
+        assert_close(rotated_dihedral(-60.0), -60.0);
+    }
+
+    #[test]
+    fn frame_relative_atoms() {
+        // Atoms picked in the second frame of 10 atoms, the same atom twice being ignored.
+        let frame = 10..20;
+        let mut pending = vec![];
+        let kind = MeasurementKind::Angle;
+        assert_eq!(add_pending_atom(&mut pending, kind, &frame, 12), None);
+        assert_eq!(add_pending_atom(&mut pending, kind, &frame, 12), None);
+        assert_eq!(add_pending_atom(&mut pending, kind, &frame, 25), None);
+        assert_eq!(add_pending_atom(&mut pending, kind, &frame, 13), None);
+        let measurement = add_pending_atom(&mut pending, kind, &frame, 19).unwrap();
+        assert!(pending.is_empty());
+        assert_eq!(measurement.kind, MeasurementKind::Angle);
+        assert_eq!(measurement.atoms, [2, 3, 9]);
+
+        // The same atoms in the other frames, when they have them.
+        assert_eq!(measurement.in_frame(&(0..10)).unwrap().atoms, [2, 3, 9]);
+        assert_eq!(measurement.in_frame(&(20..35)).unwrap().atoms, [22, 23, 29]);
+        assert_eq!(measurement.in_frame(&(35..44)), None);
+    }
+
+    #[test]
+    fn csv() {
+        let structure = Structure::load(
+            Path::new("resources/pdb/selection.pdb"),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+        let measurements = [
+            Measurement::new(vec![0, 3]).unwrap(),
+            Measurement::new(vec![2, 3, 8]).unwrap(),
+        ];
+        let mut csv = vec![];
+        write_csv(
+            &measurements,
+            &structure,
+            |atom| structure.atoms[atom].position,
+            &mut csv,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "kind,value,unit,atom_1,atom_2,atom_3,atom_4\n\
+             distance,5.000,Å,A/ALA9/N,A/ALA10/CA,,\n\
+             angle,90.000,°,A/ALA10/N,A/ALA10/CA,B/LIG100/C1,\n"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::fs;
+
+use glium::{
+    glutin::surface::WindowSurface, implement_vertex, index::PrimitiveType, program, IndexBuffer,
+    Program, VertexBuffer,
+};
+use nalgebra::{Point3, Point4, Vector3};
+
+use crate::shaders;
+
+/// Vertex of a triangle mesh, with its own normal and color.
+#[derive(Copy, Clone, Debug)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(MeshVertex, position, normal, color);
+
+impl MeshVertex {
+    pub fn new(position: Point3<f32>, normal: Vector3<f32>, color: Point4<f32>) -> Self {
+        Self {
+            position: position.into(),
+            normal: normal.into(),
+            color: color.into(),
+        }
+    }
+}
+
+/// Indexed triangle mesh built on the CPU, every three `indices` form a triangle.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Add a vertex and return its index.
+    pub fn push_vertex(&mut self, vertex: MeshVertex) -> u32 {
+        self.vertices.push(vertex);
+        (self.vertices.len() - 1) as u32
+    }
+
+    pub fn push_triangle(&mut self, first: u32, second: u32, third: u32) {
+        self.indices.extend([first, second, third]);
+    }
+}
+
+/// This struct hold a triangle mesh batch informations, unlike the imposter batches the geometry
+/// is fully described by the vertices (cartoon, surfaces).
+/// `mesh` is the CPU copy of the geometry, call `sync_buffer` to upload it after `update_mesh`.
+pub struct MeshBatch {
+    pub vertex_buffer: VertexBuffer<MeshVertex>,
+    pub index_buffer: IndexBuffer<u32>,
+    pub mesh: Mesh,
+}
+
+impl MeshBatch {
+    pub fn new(display: &glium::Display<WindowSurface>) -> anyhow::Result<Self> {
+        Ok(Self {
+            vertex_buffer: VertexBuffer::empty_dynamic(display, 0)?,
+            index_buffer: IndexBuffer::empty_dynamic(display, PrimitiveType::TrianglesList, 0)?,
+            mesh: Mesh::default(),
+        })
+    }
+
+    pub fn update_mesh(&mut self, mesh: Mesh) {
+        self.mesh = mesh;
+    }
+
+    pub fn sync_buffer(&mut self, display: &glium::Display<WindowSurface>) -> anyhow::Result<()> {
+        self.vertex_buffer = VertexBuffer::dynamic(display, &self.mesh.vertices)?;
+        self.index_buffer =
+            IndexBuffer::dynamic(display, PrimitiveType::TrianglesList, &self.mesh.indices)?;
+        Ok(())
+    }
+
+    /// Build the mesh GLSL Program and return it.
+    pub fn build_program(display: &glium::Display<WindowSurface>) -> anyhow::Result<Program> {
+        let vertex_shader = fs::read_to_string("./resources/shaders/mesh.vert")?;
+        let fragment_shader = shaders::read_shader("mesh.frag", &["clipping.glsl", "shadow.glsl"])?;
+
+        if vertex_shader.is_empty() || fragment_shader.is_empty() {
+            return Err(anyhow::format_err!(
+                "Fragment or Vertex shader file are empty"
+            ));
+        }
+
+        let program = program!(display,
+            410 => {
+                vertex: &vertex_shader,
+                fragment: &fragment_shader,
+            },
+        )?;
+        Ok(program)
+    }
+}
@@ -1,65 +1,583 @@
 use std::{
-    collections::{HashMap, HashSet},
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufWriter, Write},
+    ops::Range,
+    path::Path,
 };
 
+use clap::ValueEnum;
 use glium::glutin::surface::WindowSurface;
-use nalgebra::{Matrix4, Point3, Point4};
-use pdbtbx::{Atom, Element, PDB};
+use nalgebra::{Matrix4, Point3, Point4, Vector3};
 
 use crate::{
+    animation::Playback,
+    bonding::{self, Bond, BondOrder},
+    cartoon,
+    coloring::{self, AtomColoring},
     cylinder_batch::{CylinderBatch, CylinderInstanceData},
     geometry::{Model, Rotate, Scale, Translate},
+    hydrogen_bonds::{self, HydrogenBondCriteria, HydrogenBondDisplay},
+    io::{
+        self,
+        trajectory::{self, Trajectory},
+    },
+    label_batch::{self, LabelBatch},
+    line_batch::{LineBatch, LineVertex},
+    measurement::{self, Measurement, MeasurementKind},
+    mesh_batch::{Mesh, MeshBatch},
+    picking::{self, Pick, Ray},
+    representation::{
+        self, BondStyle, Representation, RepresentationAssignment, LICORICE_RADIUS, TRACE_RADIUS,
+    },
+    secondary_structure,
+    selection::Selection,
     sphere_batch::{SphereBatch, SphereInstanceData},
+    structure::Structure,
+    surface::{self, SurfaceKind, SurfaceParameters},
     ARGS,
 };
 
+/// How the models of a multi-model file (NMR ensembles for example) are displayed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelDisplay {
+    /// Each model is a frame of an animation.
+    Animate,
+    /// All the models are drawn on top of each other.
+    Superimpose,
+}
+
+/// Color of the dashed cylinders of the hydrogen bonds.
+const HYDROGEN_BOND_COLOR: Point4<f32> = Point4::new(0.3, 0.8, 1.0, 1.0);
+
+/// Color of the dashed cylinders and labels of the measurements.
+const MEASUREMENT_COLOR: Point4<f32> = Point4::new(1.0, 0.85, 0.2, 1.0);
+
+/// Height of the measurement labels characters, in view space.
+const MEASUREMENT_LABEL_HEIGHT: f32 = 0.08;
+
+/// Half length (in Å) of the cross drawn in place of the wireframe atoms without bonds.
+const WIREFRAME_CROSS_SIZE: f32 = 0.25;
+
+/// Margin (in Å) around the atom centers holding the largest atoms and the solvent accessible
+/// surface.
+const BOUNDING_MARGIN: f32 = 4.0;
+
+/// Instances of a single displayable frame of the molecule, `atom_range` being the atoms of the
+/// structure shown by the frame and `topology` the bonds between them.
+/// `atom_ids` and `bond_atoms` hold the atom (or the two atoms) each sphere and cylinder
+/// instance stands for, to pick them.
+pub struct MoleculeFrame {
+    pub atom_range: Range<usize>,
+    pub topology: Vec<Bond>,
+    pub atoms: Vec<SphereInstanceData>,
+    pub atom_ids: Vec<usize>,
+    pub bonds: Vec<CylinderInstanceData>,
+    pub bond_atoms: Vec<(usize, usize)>,
+    pub lines: Vec<LineVertex>,
+}
+
+/// Trajectory played on top of the topology, its frames are read on demand and only move the
+/// atoms of the topology. `frames` holds the trajectory frame index of each playback frame,
+/// `bonds` the topology bonds and `positions` the atom positions of the shown frame, the
+/// instances are rebuilt from.
+pub struct TrajectoryFrames {
+    reader: Box<dyn Trajectory>,
+    frames: Vec<usize>,
+    bonds: Vec<Bond>,
+    positions: Vec<Point3<f32>>,
+    molecule_center: Point3<f32>,
+}
+
+impl TrajectoryFrames {
+    /// Read the atom positions of the given playback frame, relative to the molecule center.
+    fn read(&mut self, index: usize) -> anyhow::Result<Vec<Point3<f32>>> {
+        let frame = self.frames[index];
+        Ok(self
+            .reader
+            .read_frame(frame)?
+            .into_iter()
+            .map(|position| (position - self.molecule_center).into())
+            .collect())
+    }
+}
+
+/// Batches whose instances have changed and need to be synchronized with the GPU.
+#[derive(Debug, Clone, Copy, Default)]
+struct OutdatedBuffers {
+    atoms: bool,
+    bonds: bool,
+    lines: bool,
+    hydrogen_bonds: bool,
+    cartoon: bool,
+    surface: bool,
+    measurements: bool,
+}
+
+impl OutdatedBuffers {
+    fn any(&self) -> bool {
+        self.atoms
+            || self.bonds
+            || self.lines
+            || self.hydrogen_bonds
+            || self.cartoon
+            || self.surface
+            || self.measurements
+    }
+}
+
+/// `frames` holds the instances of every frame, only the one selected by `playback` is uploaded
+/// in the `atoms`, `bonds` and `lines` batches. When a trajectory is loaded, `frames` only holds
+/// the topology and `playback` goes through the trajectory frames instead.
+/// `outdated` tracks the batches whose instances have changed, so that only those are
+/// synchronized with the GPU.
+/// The hydrogen bonds and the cartoon of the shown frame are built each time the frame changes,
+/// `positions` and `colors` hold the position (relative to its center) and the color of every
+/// atom of the structure.
+/// The surface is only rebuilt when the frame, its kind or the colors change, as it is the
+/// slowest to build. It is hidden while the playback runs and built for the frame the playback
+/// pauses on, `surface_frame` being the frame it was built for.
+/// `coloring` is the coloring `colors` were computed with.
+/// `representations` are the `--representation` assignments, resolved into the representation
+/// of each atom in `atom_representations`.
+/// Picked atoms are collected in `pending_measurement` while a `measurement_mode` is active,
+/// until there are enough of them to add a measurement to `measurements`. Both hold the atoms
+/// relative to the first atom of the shown frame, so that the measurements follow the same atoms
+/// through the models of an animation (see `frame_measurements`).
 pub struct Molecule {
+    pub structure: Structure,
+    positions: Vec<Point3<f32>>,
+    colors: Vec<Point4<f32>>,
+    pub coloring: AtomColoring,
+    pub representations: Vec<RepresentationAssignment>,
+    atom_representations: Vec<Representation>,
     pub atoms: SphereBatch,
     pub bonds: CylinderBatch,
+    pub lines: LineBatch,
+    /// Atoms of the `atoms` instances and bond atoms of the `bonds` instances, see `pick`.
+    shown_atoms: Vec<usize>,
+    shown_bonds: Vec<(usize, usize)>,
+    pub hydrogen_bonds: CylinderBatch,
+    pub cartoon: MeshBatch,
+    pub show_cartoon: bool,
+    pub surface: MeshBatch,
+    pub surface_kind: Option<SurfaceKind>,
+    surface_selection: Vec<bool>,
+    surface_frame: Option<usize>,
+    pub measurement_lines: CylinderBatch,
+    pub measurement_labels: LabelBatch,
+    pub measurement_mode: Option<MeasurementKind>,
+    pending_measurement: Vec<usize>,
+    pub measurements: Vec<Measurement>,
+    pub hydrogen_bond_display: HydrogenBondDisplay,
+    hydrogen_bond_criteria: HydrogenBondCriteria,
+    hydrogen_bond_selection: Vec<bool>,
     model_matrix: Matrix4<f32>,
     pub show_silhouette: bool,
     pub scale_factor: f32,
+    pub frames: Vec<MoleculeFrame>,
+    pub playback: Playback,
+    pub trajectory: Option<TrajectoryFrames>,
+    outdated: OutdatedBuffers,
 }
 
 impl Molecule {
     pub fn initialize_instances(display: &glium::Display<WindowSurface>) -> anyhow::Result<Self> {
         Ok(Self {
+            structure: Structure::default(),
+            positions: Vec::new(),
+            colors: Vec::new(),
+            coloring: ARGS.color_by,
+            representations: Vec::new(),
+            atom_representations: Vec::new(),
             atoms: SphereBatch::new(display)?,
             bonds: CylinderBatch::new(display)?,
+            lines: LineBatch::new(display)?,
+            shown_atoms: Vec::new(),
+            shown_bonds: Vec::new(),
+            hydrogen_bonds: CylinderBatch::new(display)?,
+            cartoon: MeshBatch::new(display)?,
+            show_cartoon: ARGS.cartoon,
+            surface: MeshBatch::new(display)?,
+            surface_kind: ARGS.surface,
+            surface_selection: Vec::new(),
+            surface_frame: None,
+            measurement_lines: CylinderBatch::new(display)?,
+            measurement_labels: LabelBatch::new(display)?,
+            measurement_mode: None,
+            pending_measurement: Vec::new(),
+            measurements: Vec::new(),
+            hydrogen_bond_display: ARGS.hbonds,
+            hydrogen_bond_criteria: HydrogenBondCriteria {
+                max_distance: ARGS.hbond_distance,
+                min_angle: ARGS.hbond_angle,
+            },
+            hydrogen_bond_selection: Vec::new(),
             model_matrix: Matrix4::<f32>::identity(),
             show_silhouette: false,
             scale_factor: 1.0,
+            frames: Vec::new(),
+            playback: Playback::new(0, ARGS.frame_rate),
+            trajectory: None,
+            outdated: OutdatedBuffers::default(),
         })
     }
 
+    /// Upload the instances of the outdated batches to the GPU.
     pub fn sync_buffers(&mut self, display: &glium::Display<WindowSurface>) -> anyhow::Result<()> {
-        self.atoms.sync_buffer(display)?;
-        self.bonds.sync_buffer(display)?;
+        if self.outdated.atoms {
+            self.atoms.sync_buffer(display)?;
+        }
+        if self.outdated.bonds {
+            self.bonds.sync_buffer(display)?;
+        }
+        if self.outdated.lines {
+            self.lines.sync_buffer(display)?;
+        }
+        if self.outdated.hydrogen_bonds {
+            self.hydrogen_bonds.sync_buffer(display)?;
+        }
+        if self.outdated.cartoon {
+            self.cartoon.sync_buffer(display)?;
+        }
+        if self.outdated.surface {
+            self.surface.sync_buffer(display)?;
+        }
+        if self.outdated.measurements {
+            self.measurement_lines.sync_buffer(display)?;
+            self.measurement_labels.sync_buffer(display)?;
+        }
+        self.outdated = OutdatedBuffers::default();
         Ok(())
     }
 
+    /// Whether the batches instances have changed since the last `sync_buffers`.
+    pub fn buffers_outdated(&self) -> bool {
+        self.outdated.any()
+    }
+
+    /// Advance the frame playback, should be called once per rendered frame. The surface is
+    /// built once the playback pauses.
+    pub fn update(&mut self) {
+        let index = self.playback.current_frame();
+        if self.playback.update() {
+            self.show_frame(self.playback.current_frame());
+        } else if !self.playback.is_playing() && self.surface_frame != Some(index) {
+            self.show_surface(index);
+        }
+    }
+
+    /// Step `delta` frames forward (or backward) and pause the playback.
+    pub fn step_frame(&mut self, delta: isize) {
+        if self.playback.step(delta) {
+            self.show_frame(self.playback.current_frame());
+        }
+    }
+
+    /// Replace the batches instances with the ones of the given frame. A trajectory frame which
+    /// can't be read pauses the playback and leaves the previous frame on screen.
+    fn show_frame(&mut self, index: usize) {
+        if let Some(trajectory) = &mut self.trajectory {
+            match trajectory.read(index) {
+                Ok(positions) => trajectory.positions = positions,
+                Err(error) => {
+                    eprintln!("Error: failed to read trajectory frame: {error:#}");
+                    self.playback.pause();
+                }
+            }
+        }
+        self.show_frame_representation(index);
+        self.show_frame_overlays(index);
+        self.show_surface(index);
+        self.show_measurements(index);
+    }
+
+    /// Atom positions of the given frame, and the range of atoms it shows.
+    fn frame_positions(&self, index: usize) -> Option<(&[Point3<f32>], Range<usize>)> {
+        match &self.trajectory {
+            Some(trajectory) => Some((&trajectory.positions, 0..trajectory.positions.len())),
+            None => Some((&self.positions, self.frames.get(index)?.atom_range.clone())),
+        }
+    }
+
+    /// Upload the atoms, bonds and lines instances of the given frame.
+    fn show_frame_representation(&mut self, index: usize) {
+        let rebuilt;
+        let frame = match &self.trajectory {
+            // Trajectory frames move the atoms of the topology, their instances are rebuilt.
+            Some(trajectory) => {
+                rebuilt = self.create_frame(
+                    0..trajectory.positions.len(),
+                    trajectory.bonds.clone(),
+                    &trajectory.positions,
+                );
+                &rebuilt
+            }
+            None => match self.frames.get(index) {
+                Some(frame) => frame,
+                None => return,
+            },
+        };
+        self.atoms.update_instances(&frame.atoms);
+        self.bonds.update_instances(&frame.bonds);
+        self.lines.update_vertices(&frame.lines);
+        self.shown_atoms = frame.atom_ids.clone();
+        self.shown_bonds = frame.bond_atoms.clone();
+        self.outdated.atoms = true;
+        self.outdated.bonds = true;
+        self.outdated.lines = true;
+    }
+
+    /// Rebuild the hydrogen bonds and the cartoon of the given frame.
+    fn show_frame_overlays(&mut self, index: usize) {
+        let Some((positions, range)) = self.frame_positions(index) else {
+            return;
+        };
+        let hydrogen_bonds = self.create_hydrogen_bond_instances(positions, range.clone());
+        let cartoon = self.create_cartoon(positions, range);
+        self.hydrogen_bonds.update_instances(&hydrogen_bonds);
+        self.cartoon.update_mesh(cartoon);
+        self.outdated.hydrogen_bonds = true;
+        self.outdated.cartoon = true;
+    }
+
+    /// Rebuild the surface of the given frame, or hide it while the playback runs.
+    fn show_surface(&mut self, index: usize) {
+        if self.playback.is_playing() {
+            if self.surface_frame.take().is_some() {
+                self.surface.update_mesh(Mesh::default());
+                self.outdated.surface = true;
+            }
+            return;
+        }
+        let Some((positions, range)) = self.frame_positions(index) else {
+            return;
+        };
+        let surface = self.create_surface(positions, range);
+        self.surface.update_mesh(surface);
+        self.surface_frame = Some(index);
+        self.outdated.surface = true;
+    }
+
+    /// Rebuild the dashed cylinders and the labels of the measurements in the given frame.
+    fn show_measurements(&mut self, index: usize) {
+        let measurements = self.frame_measurements(index);
+        let Some((positions, _)) = self.frame_positions(index) else {
+            return;
+        };
+        let mut lines = vec![];
+        let mut labels = vec![];
+        for measurement in &measurements {
+            let points = measurement
+                .atoms
+                .iter()
+                .map(|&atom| positions[atom])
+                .collect::<Vec<_>>();
+            lines.extend(points.windows(2).map(|pair| {
+                CylinderInstanceData::new(
+                    pair[0],
+                    pair[1],
+                    MEASUREMENT_COLOR,
+                    MEASUREMENT_COLOR,
+                    0.05,
+                )
+                .dashed(0.2)
+            }));
+            // Middle of the distance or of the dihedral axis, and apex of the angle.
+            let anchor = match measurement.kind {
+                MeasurementKind::Distance => points[0] + (points[1] - points[0]) * 0.5,
+                MeasurementKind::Angle => points[1],
+                MeasurementKind::Dihedral => points[1] + (points[2] - points[1]) * 0.5,
+            };
+            labels.extend(label_batch::text_vertices(
+                &self.measurement_label(measurement),
+                anchor,
+                MEASUREMENT_LABEL_HEIGHT,
+                MEASUREMENT_COLOR,
+            ));
+        }
+        self.measurement_lines.update_instances(&lines);
+        self.measurement_labels.update_vertices(&labels);
+        self.outdated.measurements = true;
+    }
+
+    /// Switch to the next measurement mode (none, distance, angle, dihedral), the atoms picked
+    /// for an unfinished measurement are dropped.
+    pub fn cycle_measurement_mode(&mut self) {
+        self.measurement_mode = MeasurementKind::next(self.measurement_mode);
+        self.pending_measurement.clear();
+    }
+
+    /// Measurements of the given frame, with their atoms indices in `Structure::atoms`. The
+    /// measurements of atoms missing from the frame (a model with fewer atoms) are left out.
+    pub fn frame_measurements(&self, index: usize) -> Vec<Measurement> {
+        let Some((_, range)) = self.frame_positions(index) else {
+            return vec![];
+        };
+        self.measurements
+            .iter()
+            .filter_map(|measurement| measurement.in_frame(&range))
+            .collect()
+    }
+
+    /// Add a picked atom of the shown frame to the measurement in progress, and return the
+    /// measurement when it has all its atoms. Picking the same atom twice in a row is ignored.
+    pub fn add_measurement_atom(&mut self, atom: usize) -> Option<Measurement> {
+        let mode = self.measurement_mode?;
+        let index = self.playback.current_frame();
+        let (_, range) = self.frame_positions(index)?;
+        let measurement =
+            measurement::add_pending_atom(&mut self.pending_measurement, mode, &range, atom)?;
+        self.measurements.push(measurement);
+        self.show_measurements(index);
+        self.frame_measurements(index).pop()
+    }
+
+    /// Remove the last measurement.
+    pub fn remove_last_measurement(&mut self) {
+        self.measurements.pop();
+        self.show_measurements(self.playback.current_frame());
+    }
+
+    /// Value and unit of a measurement of the shown frame (see `frame_measurements`), measured
+    /// on the file coordinates.
+    pub fn measurement_label(&self, measurement: &Measurement) -> String {
+        measurement.label(|atom| self.atom_position(atom))
+    }
+
+    /// Write the measurements of the shown frame as CSV to the `--export-measurements` file, or
+    /// to the standard output when not given.
+    pub fn export_measurements(&self) -> anyhow::Result<()> {
+        let measurements = self.frame_measurements(self.playback.current_frame());
+        let position = |atom: usize| self.atom_position(atom);
+        let Some(path) = &ARGS.export_measurements else {
+            return measurement::write_csv(
+                &measurements,
+                &self.structure,
+                position,
+                &mut std::io::stdout().lock(),
+            );
+        };
+        let file = File::create(path).map_err(|error| {
+            anyhow::format_err!("Failed to create `{}`: {}", path.display(), error)
+        })?;
+        let mut file = BufWriter::new(file);
+        measurement::write_csv(&measurements, &self.structure, position, &mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Switch to the next hydrogen bonds display mode (none, all, selection).
+    pub fn cycle_hydrogen_bonds(&mut self) {
+        self.hydrogen_bond_display = self.hydrogen_bond_display.next();
+        self.show_frame_overlays(self.playback.current_frame());
+    }
+
+    /// Show or hide the cartoon.
+    pub fn toggle_cartoon(&mut self) {
+        self.show_cartoon = !self.show_cartoon;
+        self.show_frame_overlays(self.playback.current_frame());
+    }
+
+    /// Switch to the next surface (none, solvent accessible, solvent excluded).
+    pub fn cycle_surface(&mut self) {
+        self.surface_kind = SurfaceKind::next(self.surface_kind);
+        self.show_surface(self.playback.current_frame());
+    }
+
+    /// Switch the last representation assignment to the next representation. Only the atoms,
+    /// bonds and lines instances are rebuilt.
+    pub fn cycle_representation(&mut self) {
+        let Some(assignment) = self.representations.last_mut() else {
+            return;
+        };
+        assignment.representation = assignment.representation.next();
+        self.atom_representations = representation::resolve(&self.structure, &self.representations);
+        self.rebuild_frames();
+        self.show_frame_representation(self.playback.current_frame());
+    }
+
+    /// Switch to the next coloring, the atoms, bonds, lines, cartoon and surface colors are
+    /// rebuilt.
+    pub fn cycle_coloring(&mut self) {
+        self.coloring = self.coloring.next();
+        self.colors = coloring::atom_colors(&self.structure, self.coloring);
+        self.rebuild_frames();
+        self.show_frame_representation(self.playback.current_frame());
+        self.show_frame_overlays(self.playback.current_frame());
+        self.show_surface(self.playback.current_frame());
+    }
+
+    /// Rebuild the instances of every frame, after a change of the atom representations or
+    /// colors.
+    fn rebuild_frames(&mut self) {
+        let frames = std::mem::take(&mut self.frames)
+            .into_iter()
+            .map(|frame| self.create_frame(frame.atom_range, frame.topology, &self.positions))
+            .collect();
+        self.frames = frames;
+    }
+
+    /// Open the `--trajectory` file, keeping the frames selected by `--frames` and `--stride`.
+    fn open_trajectory(
+        path: &Path,
+        bonds: Vec<Bond>,
+        topology_positions: Vec<Point3<f32>>,
+        molecule_center: Point3<f32>,
+    ) -> anyhow::Result<TrajectoryFrames> {
+        let topology_atom_count = topology_positions.len();
+        let reader = trajectory::open(path)
+            .map_err(|error| anyhow::format_err!("Failed to read `{}`: {error}", path.display()))?;
+        if reader.atom_count() != topology_atom_count {
+            return Err(anyhow::format_err!(
+                "`{}` has {} atoms but the topology has {}",
+                path.display(),
+                reader.atom_count(),
+                topology_atom_count
+            ));
+        }
+
+        let frames = ARGS
+            .frames
+            .unwrap_or_default()
+            .clamp(reader.frame_count())
+            .step_by(ARGS.stride.get())
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            return Err(anyhow::format_err!(
+                "no frame selected, `{}` has {} frames",
+                path.display(),
+                reader.frame_count()
+            ));
+        }
+
+        Ok(TrajectoryFrames {
+            reader,
+            frames,
+            bonds,
+            positions: topology_positions,
+            molecule_center,
+        })
+    }
+
     pub fn init_molecule(&mut self) -> anyhow::Result<()> {
-        let mut atom_map = HashMap::new();
-        let filename = format!("./resources/pdb/{}", &ARGS.file);
-
-        let pdb = pdbtbx::open(&filename).unwrap().0;
-
-        let bonds = parse_bonds(&filename)?;
-        let molecule_center = Self::calculate_molecule_center(&pdb);
-        let bounding_box = pdb.bounding_box();
-        let bottom_left = Point3::<f32>::new(
-            bounding_box.0 .0 as f32,
-            bounding_box.0 .1 as f32,
-            bounding_box.0 .2 as f32,
-        );
-
-        let top_right = Point3::<f32>::new(
-            bounding_box.1 .0 as f32,
-            bounding_box.1 .1 as f32,
-            bounding_box.1 .2 as f32,
-        );
+        let options = io::LoadOptions {
+            record: ARGS.record.clone(),
+        };
+        let path = io::resolve_path(
+            ARGS.file.as_deref(),
+            ARGS.id.as_ref(),
+            ARGS.mirror.as_deref(),
+        )?;
+        let mut structure = Structure::load(&path, &options)?;
+        structure.assign_bonds(ARGS.bonds, ARGS.bond_tolerance);
+        structure.assign_bond_orders(ARGS.bond_orders);
+        structure.assign_secondary_structure(ARGS.secondary_structure);
+        if let Some(path) = &ARGS.export_secondary_structure {
+            Self::export_secondary_structure(&structure, path)?;
+        }
+
+        let molecule_center = structure.center();
+        let (bottom_left, top_right) = structure.bounding_box();
 
         let dimension = top_right - bottom_left;
         let max_dimension = dimension.x.max(dimension.y).max(dimension.z);
@@ -74,195 +592,428 @@ impl Molecule {
 
         self.scale_factor = scale_factor;
 
-        let atom_instances = Self::create_atom_instances(&pdb, &mut atom_map, molecule_center);
-        let cylinder_instances = Self::create_bond_instances(&bonds, &atom_map, molecule_center);
+        let atoms = &structure.atoms;
+        let colors = coloring::atom_colors(&structure, self.coloring);
+        let positions = atoms
+            .iter()
+            .map(|atom| Point3::from(atom.position - molecule_center))
+            .collect::<Vec<_>>();
 
-        self.atoms.update_instances(&atom_instances);
-        self.bonds.update_instances(&cylinder_instances);
+        let model_ranges = structure
+            .models
+            .iter()
+            .map(|model| model.atoms.clone())
+            .collect::<Vec<_>>();
+        let frame_ranges = match ARGS.models {
+            _ if ARGS.trajectory.is_some() => model_ranges.into_iter().take(1).collect(),
+            ModelDisplay::Animate => model_ranges,
+            ModelDisplay::Superimpose => std::iter::once(0..atoms.len()).collect(),
+        };
+
+        // Bonds never cross models, so each of them belongs to the frame of its first atom.
+        let mut frame_bonds = vec![vec![]; frame_ranges.len()];
+        for bond in &structure.bonds {
+            if let Some(frame) = frame_ranges
+                .iter()
+                .position(|range| range.contains(&bond.first))
+            {
+                frame_bonds[frame].push(*bond);
+            }
+        }
+
+        if let Some(path) = &ARGS.trajectory {
+            self.trajectory = Some(Self::open_trajectory(
+                Path::new(path),
+                frame_bonds[0].clone(),
+                positions[frame_ranges[0].clone()].to_vec(),
+                molecule_center,
+            )?);
+        }
+
+        self.representations = ARGS.representations.clone();
+        if self.representations.is_empty() {
+            self.representations.push(RepresentationAssignment {
+                representation: Representation::default(),
+                selection: Selection::All,
+            });
+        }
+        self.atom_representations = representation::resolve(&structure, &self.representations);
+        self.hydrogen_bond_selection = ARGS.hbond_selection.evaluate(&structure);
+        self.surface_selection = ARGS.surface_selection.evaluate(&structure);
+        self.structure = structure;
+        self.positions = positions;
+        self.colors = colors;
+
+        let frames = frame_ranges
+            .into_iter()
+            .zip(frame_bonds)
+            .map(|(range, bonds)| self.create_frame(range, bonds, &self.positions))
+            .collect();
+        self.frames = frames;
+
+        let frame_count = match &self.trajectory {
+            Some(trajectory) => trajectory.frames.len(),
+            None => self.frames.len(),
+        };
+        self.playback = Playback::new(frame_count, ARGS.frame_rate);
+        self.show_frame(0);
 
         Ok(())
     }
 
-    fn create_atom_instances<'a>(
-        pdb: &'a PDB,
-        atom_map: &mut HashMap<usize, &'a Atom>,
-        molecule_center: Point3<f32>,
-    ) -> Vec<SphereInstanceData> {
-        let mut atom_instances = Vec::new();
+    /// Write the secondary structure assignment to `path`, or to the standard output for `-`.
+    fn export_secondary_structure(structure: &Structure, path: &Path) -> anyhow::Result<()> {
+        if path == Path::new("-") {
+            return secondary_structure::write_assignment(structure, &mut std::io::stdout().lock());
+        }
+        let file = File::create(path).map_err(|error| {
+            anyhow::format_err!("Failed to create `{}`: {}", path.display(), error)
+        })?;
+        let mut file = BufWriter::new(file);
+        secondary_structure::write_assignment(structure, &mut file)?;
+        file.flush()?;
+        Ok(())
+    }
 
-        for model in pdb.models() {
-            for atom in model.atoms() {
-                atom_map.insert(atom.serial_number(), atom);
+    /// Create the instances of the atoms of `range` according to their representation, `topology`
+    /// being the bonds between them and `positions` the position of every atom.
+    fn create_frame(
+        &self,
+        range: Range<usize>,
+        topology: Vec<Bond>,
+        positions: &[Point3<f32>],
+    ) -> MoleculeFrame {
+        let representations = &self.atom_representations;
+        let trace_atoms =
+            representation::trace_atoms(&self.structure, range.clone(), representations);
 
-                let position = Point3::new(
-                    atom.x() as f32 - molecule_center.x,
-                    atom.y() as f32 - molecule_center.y,
-                    atom.z() as f32 - molecule_center.z,
-                );
-                let color = Self::atom_color(atom);
-                let radius = Self::atom_size(atom);
-                atom_instances.push(SphereInstanceData::new(position, color, radius));
-            }
+        let (mut atom_ids, mut atoms): (Vec<_>, Vec<_>) = range
+            .clone()
+            .filter_map(|atom| {
+                let radius = representations[atom].atom_radius(&self.structure.atoms[atom])?;
+                Some((
+                    atom,
+                    SphereInstanceData::new(positions[atom], self.colors[atom], radius),
+                ))
+            })
+            .unzip();
+        for &atom in &trace_atoms {
+            atom_ids.push(atom);
+            atoms.push(SphereInstanceData::new(
+                positions[atom],
+                self.colors[atom],
+                TRACE_RADIUS,
+            ));
+        }
+
+        let (mut bonds, mut bond_atoms, lines) =
+            self.create_bond_instances(&topology, positions, range.clone());
+        for bond in representation::trace_bonds(&self.structure, positions, &trace_atoms) {
+            bond_atoms.push((bond.first, bond.second));
+            bonds.push(CylinderInstanceData::new(
+                positions[bond.first],
+                positions[bond.second],
+                self.colors[bond.first],
+                self.colors[bond.second],
+                TRACE_RADIUS,
+            ));
+        }
+
+        MoleculeFrame {
+            atom_range: range,
+            topology,
+            atoms,
+            atom_ids,
+            bonds,
+            bond_atoms,
+            lines,
         }
-        atom_instances
     }
 
-    fn create_bond_instances<'a>(
-        bonds: &[ConectRecord],
-        atom_map: &'a HashMap<usize, &'a Atom>,
-        molecule_center: Point3<f32>,
-    ) -> Vec<CylinderInstanceData> {
+    /// Create the cylinder instances and the line segments of the bonds, drawn according to the
+    /// representation of their atoms (see `representation::bond_style`), `positions` is indexed
+    /// by the bond atom indices. Every line is split in two halves of the color of their atom,
+    /// and the wireframe atoms of `range` without bonds are drawn as a small cross.
+    /// With the ball-and-stick representation, single bonds are one cylinder, double and triple
+    /// bonds are parallel thinner cylinders, and aromatic bonds are a solid cylinder with a dashed
+    /// one on the ring side.
+    /// The cylinders are returned along with the atoms of the bond they stand for.
+    fn create_bond_instances(
+        &self,
+        bonds: &[Bond],
+        positions: &[Point3<f32>],
+        range: Range<usize>,
+    ) -> (
+        Vec<CylinderInstanceData>,
+        Vec<(usize, usize)>,
+        Vec<LineVertex>,
+    ) {
+        let colors = &self.colors;
+        let representations = &self.atom_representations;
+        let neighbours = bonding::neighbours(positions.len(), bonds);
         let mut cylinder_instances = vec![];
-        let mut already_connected = HashSet::new();
+        let mut bond_atoms = vec![];
+        let mut lines = vec![];
+        let mut has_line = vec![false; positions.len()];
 
         for bond in bonds {
-            let start = match atom_map.get(&(bond.source_atom)) {
-                Some(atom) => atom,
-                None => continue,
+            let Some(style) = representation::bond_style(
+                representations[bond.first],
+                representations[bond.second],
+            ) else {
+                continue;
             };
+            let start_pos = positions[bond.first];
+            let end_pos = positions[bond.second];
 
-            for &connected in &bond.bonded_atoms {
-                let end = match atom_map.get(&(connected)) {
-                    Some(atom) => atom,
-                    None => continue,
-                };
-
-                if already_connected.contains(&(start.serial_number(), end.serial_number()))
-                    || already_connected.contains(&(end.serial_number(), start.serial_number()))
-                {
+            let order = match style {
+                BondStyle::Line => {
+                    let middle = start_pos + (end_pos - start_pos) * 0.5;
+                    lines.extend([
+                        LineVertex::new(start_pos, colors[bond.first]),
+                        LineVertex::new(middle, colors[bond.first]),
+                        LineVertex::new(middle, colors[bond.second]),
+                        LineVertex::new(end_pos, colors[bond.second]),
+                    ]);
+                    has_line[bond.first] = true;
+                    has_line[bond.second] = true;
                     continue;
                 }
+                BondStyle::Stick => BondOrder::Single,
+                BondStyle::Orders => bond.order,
+            };
 
-                let start_pos = [
-                    start.x() as f32 - molecule_center.x,
-                    start.y() as f32 - molecule_center.y,
-                    start.z() as f32 - molecule_center.z,
-                ];
-                let end_pos = [
-                    end.x() as f32 - molecule_center.x,
-                    end.y() as f32 - molecule_center.y,
-                    end.z() as f32 - molecule_center.z,
-                ];
-
-                cylinder_instances.push(CylinderInstanceData {
-                    instance_start_pos: start_pos,
-                    instance_end_pos: end_pos,
-                    instance_color_first_half: Self::atom_color(start).into(),
-                    instance_color_second_half: Self::atom_color(end).into(),
-                    instance_radius: 0.15,
-                });
-
-                already_connected.insert((start.serial_number(), end.serial_number()));
+            let offset_direction =
+                Self::bond_offset_direction(bond, positions, &neighbours).unwrap_or_default();
+            let cylinder = |offset: f32, radius: f32| {
+                CylinderInstanceData::new(
+                    start_pos + offset_direction * offset,
+                    end_pos + offset_direction * offset,
+                    colors[bond.first],
+                    colors[bond.second],
+                    radius,
+                )
+            };
+
+            match order {
+                BondOrder::Single if style == BondStyle::Stick => {
+                    cylinder_instances.push(cylinder(0.0, LICORICE_RADIUS))
+                }
+                BondOrder::Single => cylinder_instances.push(cylinder(0.0, 0.15)),
+                BondOrder::Double => {
+                    cylinder_instances.push(cylinder(-0.1, 0.07));
+                    cylinder_instances.push(cylinder(0.1, 0.07));
+                }
+                BondOrder::Triple => {
+                    cylinder_instances.push(cylinder(-0.14, 0.055));
+                    cylinder_instances.push(cylinder(0.0, 0.055));
+                    cylinder_instances.push(cylinder(0.14, 0.055));
+                }
+                BondOrder::Aromatic => {
+                    cylinder_instances.push(cylinder(0.0, 0.1));
+                    cylinder_instances.push(cylinder(0.2, 0.05).dashed(0.1));
+                }
             }
+            bond_atoms.resize(cylinder_instances.len(), (bond.first, bond.second));
         }
-        cylinder_instances
-    }
 
-    pub fn toggle_silhouette(&mut self) {
-        self.show_silhouette = !self.show_silhouette;
+        for atom in range
+            .filter(|&atom| representations[atom] == Representation::Wireframe && !has_line[atom])
+        {
+            for axis in [Vector3::x(), Vector3::y(), Vector3::z()] {
+                let offset = axis * WIREFRAME_CROSS_SIZE;
+                lines.extend([
+                    LineVertex::new(positions[atom] - offset, colors[atom]),
+                    LineVertex::new(positions[atom] + offset, colors[atom]),
+                ]);
+            }
+        }
+        (cylinder_instances, bond_atoms, lines)
     }
 
-    /// Take a reference to a `Atom` and return a normalized RGBA color
-    /// according to the CPK coloring.
-    fn atom_color(atom: &Atom) -> Point4<f32> {
-        match atom.element().unwrap() {
-            Element::H => Point4::new(1.0, 1.0, 1.0, 1.0), // 255/255
-            Element::He => Point4::new(0.851, 1.0, 1.0, 1.0), // 217/255
-            Element::Li => Point4::new(0.8, 0.502, 1.0, 1.0), // 204/255, 128/255
-            Element::Be => Point4::new(0.761, 1.0, 0.0, 1.0), // 194/255
-            Element::B => Point4::new(1.0, 0.710, 0.710, 1.0), // 181/255
-            Element::C => Point4::new(0.565, 0.565, 0.565, 1.0), // 144/255
-            Element::N => Point4::new(0.188, 0.314, 0.973, 1.0), // 48/255, 80/255, 248/255
-            Element::O => Point4::new(1.0, 0.051, 0.051, 1.0), // 13/255
-            Element::F => Point4::new(0.565, 0.878, 0.314, 1.0), // 144/255, 224/255, 80/255
-            Element::Ne => Point4::new(0.702, 0.890, 0.961, 1.0), // 179/255, 227/255, 245/255
-            Element::Na => Point4::new(0.671, 0.361, 0.949, 1.0), // 171/255, 92/255, 242/255
-            Element::Mg => Point4::new(0.541, 1.0, 0.0, 1.0), // 138/255
-            Element::Al => Point4::new(0.749, 0.651, 0.651, 1.0), // 191/255, 166/255
-            Element::Si => Point4::new(0.941, 0.784, 0.627, 1.0), // 240/255, 200/255, 160/255
-            Element::P => Point4::new(1.0, 0.502, 0.0, 1.0), // 128/255
-            Element::S => Point4::new(1.0, 1.0, 0.188, 1.0), // 48/255
-            Element::Cl => Point4::new(0.122, 0.941, 0.122, 1.0), // 31/255, 240/255
-            Element::Ar => Point4::new(0.502, 0.820, 0.890, 1.0), // 128/255, 209/255, 227/255
-            Element::K => Point4::new(0.561, 0.251, 0.831, 1.0), // 143/255, 64/255, 212/255
-            Element::Ca => Point4::new(0.239, 1.0, 0.0, 1.0), // 61/255
-            Element::Fe => Point4::new(0.878, 0.400, 0.200, 1.0), // 224/255, 102/255, 51/255
-            Element::Cu => Point4::new(0.784, 0.502, 0.200, 1.0), // 200/255, 128/255, 51/255
-            Element::Zn => Point4::new(0.490, 0.502, 0.690, 1.0), // 125/255, 128/255, 176/255
-            Element::Br => Point4::new(0.651, 0.161, 0.161, 1.0), // 166/255, 41/255
-            Element::Ag => Point4::new(0.753, 0.753, 0.753, 1.0), // 192/255
-            Element::I => Point4::new(0.580, 0.0, 0.580, 1.0), // 148/255
-            Element::Au => Point4::new(1.0, 0.820, 0.137, 1.0), // 255/255, 209/255, 35/255
-            Element::Pb => Point4::new(0.341, 0.349, 0.380, 1.0), // 87/255, 89/255, 97/255
-            Element::U => Point4::new(0.0, 0.561, 1.0, 1.0), // 0, 143/255, 255/255
-            _ => Point4::new(1.0, 0.078, 0.576, 1.0),      // 255/255, 20/255, 147/255 (pink)
+    /// Direction, perpendicular to the bond, in which the cylinders of a multiple bond are
+    /// offset: towards a neighbour of the bond atoms so that the cylinders lie in the plane of the
+    /// molecule (inside the ring for aromatic bonds), or any perpendicular direction when the bond
+    /// atoms have no other neighbour.
+    fn bond_offset_direction(
+        bond: &Bond,
+        positions: &[Point3<f32>],
+        neighbours: &[Vec<(usize, BondOrder)>],
+    ) -> Option<Vector3<f32>> {
+        if bond.order == BondOrder::Single {
+            return None;
         }
+        let start_pos = positions[bond.first];
+        let axis = (positions[bond.second] - start_pos).try_normalize(f32::EPSILON)?;
+        let perpendicular =
+            |vector: Vector3<f32>| (vector - axis * vector.dot(&axis)).try_normalize(1e-3);
+
+        // Aromatic bonds prefer a neighbour from the same ring.
+        let candidates = [(bond.first, bond.second), (bond.second, bond.first)]
+            .into_iter()
+            .flat_map(|(atom, other)| {
+                neighbours[atom]
+                    .iter()
+                    .filter(move |(neighbour, _)| *neighbour != other)
+            })
+            .collect::<Vec<_>>();
+        let neighbour = candidates
+            .iter()
+            .find(|(_, order)| bond.order == BondOrder::Aromatic && *order == BondOrder::Aromatic)
+            .or(candidates.first())
+            .and_then(|(neighbour, _)| perpendicular(positions[*neighbour] - start_pos));
+
+        neighbour
+            .or_else(|| perpendicular(Vector3::x()))
+            .or_else(|| perpendicular(Vector3::y()))
     }
 
-    /// Assign a normalized atomic size based on the CPK radii.
-    /// The sizes are scaled to fit OpenGL rendering.
-    fn atom_size(atom: &Atom) -> f32 {
-        let cpk_radii = match atom.element().unwrap() {
-            Element::H => 1.20,
-            Element::C => 1.70,
-            Element::N => 1.55,
-            Element::O => 1.52,
-            Element::F => 1.47,
-            Element::Cl => 1.75,
-            Element::Br => 1.85,
-            Element::I => 1.98,
-            Element::He => 1.40,
-            Element::Ne => 1.54,
-            Element::Ar => 1.88,
-            Element::P => 1.80,
-            Element::S => 1.80,
-            Element::B => 2.00,
-            Element::Li => 1.82,
-            Element::Na => 2.27,
-            Element::K => 2.75,
-            Element::Rb => 3.03,
-            Element::Cs => 3.43,
-            Element::Fr => 3.48,
-            Element::Be => 1.53,
-            Element::Mg => 1.73,
-            Element::Ca => 2.31,
-            Element::Sr => 2.49,
-            Element::Ba => 2.68,
-            Element::Ra => 2.83,
-            Element::Ti => 1.60,
-            Element::Fe => 1.52,
-            _ => 1.75,
+    /// Create the dashed cylinder instances of the hydrogen bonds between the atoms of `range`,
+    /// according to the display mode. Each model of the range is searched separately, so that
+    /// superimposed models are never bonded together. The cylinders go from the hydrogen (or the
+    /// donor when it has no explicit hydrogen) to the acceptor.
+    fn create_hydrogen_bond_instances(
+        &self,
+        positions: &[Point3<f32>],
+        range: Range<usize>,
+    ) -> Vec<CylinderInstanceData> {
+        let selection = match self.hydrogen_bond_display {
+            HydrogenBondDisplay::Off => return vec![],
+            HydrogenBondDisplay::All => None,
+            HydrogenBondDisplay::Selection => Some(self.hydrogen_bond_selection.as_slice()),
         };
 
-        let scale: f32 = 0.25;
+        self.structure
+            .models
+            .iter()
+            .map(|model| model.atoms.start.max(range.start)..model.atoms.end.min(range.end))
+            .filter(|atoms| !atoms.is_empty())
+            .flat_map(|atoms| {
+                hydrogen_bonds::detect_hydrogen_bonds(
+                    &self.structure,
+                    positions,
+                    atoms,
+                    &self.hydrogen_bond_criteria,
+                    selection,
+                )
+            })
+            .map(|hydrogen_bond| {
+                CylinderInstanceData::new(
+                    positions[hydrogen_bond.hydrogen.unwrap_or(hydrogen_bond.donor)],
+                    positions[hydrogen_bond.acceptor],
+                    HYDROGEN_BOND_COLOR,
+                    HYDROGEN_BOND_COLOR,
+                    0.04,
+                )
+                .dashed(0.1)
+            })
+            .collect()
+    }
 
-        cpk_radii as f32 * scale
+    /// Build the cartoon of the atoms of `range` when it is shown, see `cartoon::build_cartoon`.
+    fn create_cartoon(&self, positions: &[Point3<f32>], range: Range<usize>) -> Mesh {
+        if !self.show_cartoon {
+            return Mesh::default();
+        }
+        cartoon::build_cartoon(&self.structure, positions, range, &self.colors)
     }
 
-    pub fn calculate_molecule_center(pdb: &PDB) -> Point3<f32> {
-        let mut total_x = 0.0;
-        let mut total_y = 0.0;
-        let mut total_z = 0.0;
-        let mut atom_count = 0;
+    /// Build the surface of the `--surface-selection` atoms of `range` when it is shown, see
+    /// `surface::build_surface`.
+    fn create_surface(&self, positions: &[Point3<f32>], range: Range<usize>) -> Mesh {
+        let Some(kind) = self.surface_kind else {
+            return Mesh::default();
+        };
+        let atoms = range
+            .filter(|&atom| self.surface_selection[atom])
+            .collect::<Vec<_>>();
+        let parameters = SurfaceParameters {
+            probe_radius: ARGS.probe_radius,
+            resolution: ARGS.surface_resolution,
+            opacity: ARGS.surface_opacity,
+        };
+        surface::build_surface(
+            kind,
+            &atoms
+                .iter()
+                .map(|&atom| positions[atom])
+                .collect::<Vec<_>>(),
+            &atoms
+                .iter()
+                .map(|&atom| representation::van_der_waals_radius(&self.structure.atoms[atom]))
+                .collect::<Vec<_>>(),
+            &atoms
+                .iter()
+                .map(|&atom| self.colors[atom])
+                .collect::<Vec<_>>(),
+            &parameters,
+        )
+    }
 
-        for model in pdb.models() {
-            for atom in model.atoms() {
-                total_x += atom.x() as f32;
-                total_y += atom.y() as f32;
-                total_z += atom.z() as f32;
-                atom_count += 1;
-            }
+    /// Atom or bond of the shown frame hit first by a ray in model space, the wireframe lines
+    /// can't be picked.
+    pub fn pick(&self, ray: &Ray) -> Option<Pick> {
+        let spheres = self.atoms.instances.iter().map(|instance| {
+            (
+                Point3::from(instance.instance_pos),
+                instance.instance_radius,
+            )
+        });
+        let cylinders = self.bonds.instances.iter().map(|instance| {
+            (
+                Point3::from(instance.instance_start_pos),
+                Point3::from(instance.instance_end_pos),
+                instance.instance_radius,
+            )
+        });
+        let atom = picking::closest_sphere(ray, spheres)
+            .map(|hit| (Pick::Atom(self.shown_atoms[hit.index]), hit.distance));
+        let bond = picking::closest_cylinder(ray, cylinders).map(|hit| {
+            let (first, second) = self.shown_bonds[hit.index];
+            (Pick::Bond(first, second), hit.distance)
+        });
+        atom.into_iter()
+            .chain(bond)
+            .min_by(|(_, first), (_, second)| first.total_cmp(second))
+            .map(|(pick, _)| pick)
+    }
+
+    /// Position (in Å, in the file coordinates) of an atom in the shown frame.
+    pub fn atom_position(&self, atom: usize) -> Point3<f32> {
+        match &self.trajectory {
+            Some(trajectory) => trajectory.positions[atom] + trajectory.molecule_center.coords,
+            None => self.structure.atoms[atom].position,
         }
+    }
 
-        if atom_count > 0 {
-            return Point3::new(
-                total_x / atom_count as f32,
-                total_y / atom_count as f32,
-                total_z / atom_count as f32,
-            );
+    /// Information about a picked atom (or the two atoms and the length of a picked bond).
+    pub fn describe_pick(&self, pick: Pick) -> String {
+        let describe =
+            |atom: usize| picking::describe_atom(&self.structure, atom, &self.atom_position(atom));
+        match pick {
+            Pick::Atom(atom) => describe(atom),
+            Pick::Bond(first, second) => format!(
+                "Bond of {:.3} Å\n  {}\n  {}",
+                (self.atom_position(second) - self.atom_position(first)).norm(),
+                describe(first),
+                describe(second),
+            ),
         }
+    }
 
-        Point3::origin()
+    pub fn toggle_silhouette(&mut self) {
+        self.show_silhouette = !self.show_silhouette;
+    }
+
+    /// Radius of the sphere around the molecule center holding the whole molecule, in world
+    /// space units (after the molecule scaling).
+    pub fn bounding_radius(&self) -> f32 {
+        let radius = self
+            .positions
+            .iter()
+            .map(|position| position.coords.norm())
+            .fold(0.0, f32::max);
+        (radius + BOUNDING_MARGIN) * self.scale_factor
     }
 }
 
@@ -293,50 +1044,3 @@ impl Model for Molecule {
         self.model_matrix = Matrix4::<f32>::identity();
     }
 }
-
-/// Represents a CONECT record from a PDB file
-#[derive(Debug, Clone)]
-struct ConectRecord {
-    source_atom: usize,
-    bonded_atoms: Vec<usize>,
-}
-
-impl ConectRecord {
-    /// Parse a CONECT line into a ConectRecord
-    fn from_line(line: &str) -> Option<Self> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-
-        // CONECT records must have at least 2 numbers
-        if parts.len() < 3 || !parts[0].starts_with("CONECT") {
-            return None;
-        }
-
-        // Parse source atom
-        let source_atom = parts[1].parse().ok()?;
-
-        // Parse bonded atoms
-        let bonded_atoms = parts[2..]
-            .iter()
-            .filter_map(|s| s.parse().ok())
-            .filter(|&num| num != source_atom) // Avoid self-bonds
-            .collect();
-
-        Some(ConectRecord {
-            source_atom,
-            bonded_atoms,
-        })
-    }
-}
-
-fn parse_bonds(file_path: &str) -> anyhow::Result<Vec<ConectRecord>> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-
-    let bonds = reader
-        .lines()
-        .map_while(Result::ok)
-        .filter_map(|line| ConectRecord::from_line(&line))
-        .collect::<Vec<_>>();
-
-    Ok(bonds)
-}
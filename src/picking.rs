@@ -0,0 +1,304 @@
+use nalgebra::{Matrix4, Point3, Point4, Vector3};
+
+use crate::structure::Structure;
+
+/// Half line used to pick the instances under the cursor, `direction` is normalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+/// What lies under the cursor, referenced by the atom indices in `Structure::atoms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pick {
+    Atom(usize),
+    Bond(usize, usize),
+}
+
+impl Ray {
+    /// Ray through the cursor, in pixels from the top left corner of a viewport of `viewport`
+    /// pixels. `inverse_transform` is the inverse of the matrix from the ray space to the clip
+    /// space (the inverse of `projection * view * model` for a ray in model space).
+    /// The ray starts on the near plane and goes to the far plane, so it works the same for
+    /// perspective and orthographic projections.
+    pub fn from_cursor(
+        cursor: (f32, f32),
+        viewport: (f32, f32),
+        inverse_transform: &Matrix4<f32>,
+    ) -> Option<Self> {
+        let x = 2.0 * cursor.0 / viewport.0 - 1.0;
+        let y = 1.0 - 2.0 * cursor.1 / viewport.1;
+        let unproject = |z: f32| {
+            let point = inverse_transform * Point4::new(x, y, z, 1.0);
+            (point.w.abs() > f32::EPSILON).then(|| Point3::from(point.xyz().coords / point.w))
+        };
+        let near = unproject(-1.0)?;
+        let far = unproject(1.0)?;
+        Some(Self {
+            origin: near,
+            direction: (far - near).try_normalize(f32::EPSILON)?,
+        })
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Distance along the ray to the first intersection with a sphere, `None` when the ray
+    /// misses it or the sphere is behind the origin.
+    pub fn intersect_sphere(&self, center: &Point3<f32>, radius: f32) -> Option<f32> {
+        let offset = self.origin - center;
+        let b = offset.dot(&self.direction);
+        let c = offset.norm_squared() - radius * radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [-b - root, -b + root]
+            .into_iter()
+            .find(|&distance| distance >= 0.0)
+    }
+
+    /// Distance along the ray to the first intersection with the side of a cylinder going from
+    /// `start` to `end` (its ends are covered by the atoms spheres).
+    pub fn intersect_cylinder(
+        &self,
+        start: &Point3<f32>,
+        end: &Point3<f32>,
+        radius: f32,
+    ) -> Option<f32> {
+        let length = (end - start).norm();
+        let axis = (end - start).try_normalize(f32::EPSILON)?;
+        let offset = self.origin - start;
+        let direction = self.direction - axis * self.direction.dot(&axis);
+        let offset_perpendicular = offset - axis * offset.dot(&axis);
+
+        let a = direction.norm_squared();
+        if a <= f32::EPSILON {
+            // The ray is parallel to the axis.
+            return None;
+        }
+        let b = direction.dot(&offset_perpendicular);
+        let c = offset_perpendicular.norm_squared() - radius * radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-b - root) / a, (-b + root) / a]
+            .into_iter()
+            .filter(|&distance| distance >= 0.0)
+            .find(|&distance| {
+                let along_axis = (self.at(distance) - start).dot(&axis);
+                (0.0..=length).contains(&along_axis)
+            })
+    }
+}
+
+/// Instance hit by a ray, `index` being its index in the tested instances and `distance` the
+/// distance along the ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub index: usize,
+    pub distance: f32,
+}
+
+fn closest(hits: impl Iterator<Item = Hit>) -> Option<Hit> {
+    hits.min_by(|first, second| first.distance.total_cmp(&second.distance))
+}
+
+/// Closest of the spheres (`center`, `radius`) hit by the ray.
+pub fn closest_sphere(ray: &Ray, spheres: impl Iterator<Item = (Point3<f32>, f32)>) -> Option<Hit> {
+    closest(spheres.enumerate().filter_map(|(index, (center, radius))| {
+        let distance = ray.intersect_sphere(&center, radius)?;
+        Some(Hit { index, distance })
+    }))
+}
+
+/// Closest of the cylinders (`start`, `end`, `radius`) hit by the ray.
+pub fn closest_cylinder(
+    ray: &Ray,
+    cylinders: impl Iterator<Item = (Point3<f32>, Point3<f32>, f32)>,
+) -> Option<Hit> {
+    closest(
+        cylinders
+            .enumerate()
+            .filter_map(|(index, (start, end, radius))| {
+                let distance = ray.intersect_cylinder(&start, &end, radius)?;
+                Some(Hit { index, distance })
+            }),
+    )
+}
+
+/// One line description of an atom: element, name, residue, chain, serial number, coordinates
+/// (in Å) and B-factor.
+pub fn describe_atom(structure: &Structure, atom: usize, position: &Point3<f32>) -> String {
+    let residue = structure.residue(atom);
+    let chain = structure.chain(atom);
+    let data = &structure.atoms[atom];
+    let element = data.element.map_or("?", |element| element.symbol());
+    let insertion_code = residue.insertion_code.as_deref().unwrap_or_default();
+    format!(
+        "{element} {} | {} {}{insertion_code} chain {} | serial {} | ({:.3}, {:.3}, {:.3}) | B-factor {:.2}",
+        data.name.trim(),
+        residue.name.trim(),
+        residue.serial_number,
+        chain.id.trim(),
+        data.serial_number,
+        position.x,
+        position.y,
+        position.z,
+        data.b_factor,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, OrthographicCamera, PerspectiveCamera};
+
+    const EPSILON: f32 = 1e-4;
+
+    /// Ray along the z axis, towards the negative z.
+    fn ray(x: f32, y: f32) -> Ray {
+        Ray {
+            origin: Point3::new(x, y, 10.0),
+            direction: -Vector3::z(),
+        }
+    }
+
+    #[test]
+    fn sphere() {
+        let center = Point3::new(0.0, 0.0, 1.0);
+        let distance = ray(0.0, 0.0).intersect_sphere(&center, 1.0).unwrap();
+        assert!((distance - 8.0).abs() < EPSILON);
+        let distance = ray(0.5, 0.0).intersect_sphere(&center, 1.0).unwrap();
+        assert!((distance - (9.0 - 0.75f32.sqrt())).abs() < EPSILON);
+
+        assert_eq!(ray(1.1, 0.0).intersect_sphere(&center, 1.0), None);
+        // Behind the origin.
+        let center = Point3::new(0.0, 0.0, 12.0);
+        assert_eq!(ray(0.0, 0.0).intersect_sphere(&center, 1.0), None);
+        // From inside, the exit point is hit.
+        let center = Point3::new(0.0, 0.0, 10.0);
+        let distance = ray(0.0, 0.0).intersect_sphere(&center, 1.0).unwrap();
+        assert!((distance - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn cylinder() {
+        // Along the x axis, from -1 to 1.
+        let start = Point3::new(-1.0, 0.0, 0.0);
+        let end = Point3::new(1.0, 0.0, 0.0);
+        let distance = ray(0.0, 0.0).intersect_cylinder(&start, &end, 0.5).unwrap();
+        assert!((distance - 9.5).abs() < EPSILON);
+        let distance = ray(0.9, 0.3).intersect_cylinder(&start, &end, 0.5).unwrap();
+        assert!((distance - 9.6).abs() < EPSILON);
+
+        assert_eq!(ray(0.0, 0.6).intersect_cylinder(&start, &end, 0.5), None);
+        // Past the ends of the cylinder.
+        assert_eq!(ray(1.1, 0.0).intersect_cylinder(&start, &end, 0.5), None);
+        assert_eq!(ray(-1.1, 0.0).intersect_cylinder(&start, &end, 0.5), None);
+        // Along the axis, the ends are left to the atoms spheres.
+        let start = Point3::new(0.0, 0.0, -1.0);
+        let end = Point3::new(0.0, 0.0, 1.0);
+        assert_eq!(ray(0.0, 0.0).intersect_cylinder(&start, &end, 0.5), None);
+    }
+
+    #[test]
+    fn closest_hit() {
+        let spheres = [
+            (Point3::new(0.0, 0.0, 0.0), 1.0),
+            (Point3::new(0.0, 0.0, 1.0), 1.0),
+            (Point3::new(0.0, 0.0, 5.0), 0.5),
+            (Point3::new(3.0, 0.0, 6.0), 1.0),
+        ];
+        let hit = closest_sphere(&ray(0.0, 0.0), spheres.into_iter()).unwrap();
+        assert_eq!(hit.index, 2);
+        assert!((hit.distance - 4.5).abs() < EPSILON);
+        let hit = closest_sphere(&ray(0.0, 0.0), spheres[..2].iter().copied()).unwrap();
+        assert_eq!(hit.index, 1);
+        assert_eq!(closest_sphere(&ray(5.0, 0.0), spheres.into_iter()), None);
+
+        let cylinders = [
+            (Point3::new(-1.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), 0.5),
+            (Point3::new(-1.0, 0.0, 2.0), Point3::new(1.0, 0.0, 2.0), 0.5),
+            (Point3::new(0.0, -1.0, 1.0), Point3::new(0.0, 1.0, 1.0), 0.5),
+        ];
+        let hit = closest_cylinder(&ray(0.0, 0.0), cylinders.into_iter()).unwrap();
+        assert_eq!(hit.index, 1);
+        assert!((hit.distance - 7.5).abs() < EPSILON);
+        let hit = closest_cylinder(&ray(0.0, 0.9), cylinders.into_iter()).unwrap();
+        assert_eq!(hit.index, 2);
+        assert_eq!(
+            closest_cylinder(&ray(0.0, 2.0), cylinders.into_iter()),
+            None
+        );
+    }
+
+    /// The ray through the center of the viewport goes from the camera to its target, whatever
+    /// the projection.
+    fn assert_center_ray(camera: &impl Camera, eye: Point3<f32>, target: Point3<f32>) {
+        let transform = camera.get_projection_matrix(1.5) * camera.get_view_matrix();
+        let ray = Ray::from_cursor(
+            (300.0, 200.0),
+            (600.0, 400.0),
+            &transform.try_inverse().unwrap(),
+        )
+        .unwrap();
+        let direction = (target - eye).normalize();
+        assert!((ray.direction - direction).norm() < EPSILON);
+        // The origin is on the near plane, on the line of sight.
+        let offset = ray.origin - eye;
+        assert!((offset - direction * offset.dot(&direction)).norm() < EPSILON);
+        assert!((offset.dot(&direction) - 0.1).abs() < EPSILON);
+
+        let hit = closest_sphere(&ray, [(target, 1.0)].into_iter()).unwrap();
+        assert!((ray.at(hit.distance) - (target - direction)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn center_ray() {
+        let eye = Point3::new(1.0, 2.0, 10.0);
+        let target = Point3::new(1.0, -1.0, 0.0);
+        let perspective = PerspectiveCamera::default()
+            .place(eye)
+            .point(target, Vector3::y());
+        assert!(!perspective.is_orthographic());
+        assert_center_ray(&perspective, eye, target);
+
+        let orthographic = OrthographicCamera::default()
+            .place(eye)
+            .point(target, Vector3::y());
+        assert!(orthographic.is_orthographic());
+        assert_center_ray(&orthographic, eye, target);
+    }
+
+    #[test]
+    fn parallel_orthographic_rays() {
+        let eye = Point3::new(0.0, 0.0, 10.0);
+        let camera = OrthographicCamera::default()
+            .place(eye)
+            .point(Point3::origin(), Vector3::y());
+        let inverse = (camera.get_projection_matrix(1.0) * camera.get_view_matrix())
+            .try_inverse()
+            .unwrap();
+        // The view volume is 8 units high, the top left corner ray starts at (-4, 4).
+        let ray = Ray::from_cursor((0.0, 0.0), (400.0, 400.0), &inverse).unwrap();
+        assert!((ray.direction + Vector3::z()).norm() < EPSILON);
+        assert!((ray.origin - Point3::new(-4.0, 4.0, 9.9)).norm() < EPSILON);
+
+        let camera = PerspectiveCamera::default()
+            .place(eye)
+            .point(Point3::origin(), Vector3::y());
+        let inverse = (camera.get_projection_matrix(1.0) * camera.get_view_matrix())
+            .try_inverse()
+            .unwrap();
+        // A 90° field of view, the corner rays diverge from the camera.
+        let ray = Ray::from_cursor((0.0, 0.0), (400.0, 400.0), &inverse).unwrap();
+        let expected = Vector3::new(-1.0, 1.0, -1.0).normalize();
+        assert!((ray.direction - expected).norm() < EPSILON);
+    }
+}
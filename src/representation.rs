@@ -0,0 +1,346 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+use clap::ValueEnum;
+use nalgebra::Point3;
+use pdbtbx::Element;
+
+use crate::{
+    bonding::Bond,
+    cartoon,
+    selection::Selection,
+    structure::{Atom, Structure},
+};
+
+/// Scale applied to the van der Waals radii for the balls of the ball-and-stick representation.
+const BALL_SCALE: f32 = 0.25;
+
+/// Radius (in Å) of both the atoms and the bonds of the licorice representation, so that the
+/// sticks end with rounded caps.
+pub const LICORICE_RADIUS: f32 = 0.2;
+
+/// Radius (in Å) of the spheres and cylinders of the backbone trace.
+pub const TRACE_RADIUS: f32 = 0.3;
+
+/// How the atoms (and the bonds between them) are drawn.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Representation {
+    /// Spheres of the van der Waals radius, without bonds.
+    Spacefill,
+    /// Small spheres and thin cylinders, showing the bond orders.
+    #[default]
+    BallAndStick,
+    /// Sticks of a single radius with rounded ends.
+    Licorice,
+    /// Lines between the bonded atoms.
+    Wireframe,
+    /// Tube through the `CA` atoms of the amino acids (`P` of the nucleotides), the other atoms
+    /// are hidden.
+    Trace,
+    /// Not drawn.
+    Hidden,
+}
+
+/// How the bonds are drawn, from the thinnest to the thickest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BondStyle {
+    Line,
+    /// Parallel cylinders for the multiple bonds, see `BondOrder`.
+    Orders,
+    Stick,
+}
+
+impl Representation {
+    /// Next representation, used to cycle through the representations at runtime.
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Spacefill => Self::BallAndStick,
+            Self::BallAndStick => Self::Licorice,
+            Self::Licorice => Self::Wireframe,
+            Self::Wireframe => Self::Trace,
+            Self::Trace => Self::Hidden,
+            Self::Hidden => Self::Spacefill,
+        }
+    }
+
+    /// Radius of the sphere of an atom, `None` when the atom has no sphere (the trace spheres are
+    /// only drawn on the backbone, see `trace_atoms`).
+    pub fn atom_radius(&self, atom: &Atom) -> Option<f32> {
+        match self {
+            Self::Spacefill => Some(van_der_waals_radius(atom)),
+            Self::BallAndStick => Some(van_der_waals_radius(atom) * BALL_SCALE),
+            Self::Licorice => Some(LICORICE_RADIUS),
+            Self::Wireframe | Self::Trace | Self::Hidden => None,
+        }
+    }
+
+    fn bond_style(&self) -> Option<BondStyle> {
+        match self {
+            Self::BallAndStick => Some(BondStyle::Orders),
+            Self::Licorice => Some(BondStyle::Stick),
+            Self::Wireframe => Some(BondStyle::Line),
+            Self::Spacefill | Self::Trace | Self::Hidden => None,
+        }
+    }
+}
+
+/// How a bond between atoms of two representations is drawn: only when both representations
+/// draw bonds, in the thinnest of their styles.
+pub fn bond_style(first: Representation, second: Representation) -> Option<BondStyle> {
+    Some(first.bond_style()?.min(second.bond_style()?))
+}
+
+/// Van der Waals radius (in Å) of an atom, from its element.
+pub fn van_der_waals_radius(atom: &Atom) -> f32 {
+    match atom.element.unwrap_or(Element::C) {
+        Element::H => 1.20,
+        Element::C => 1.70,
+        Element::N => 1.55,
+        Element::O => 1.52,
+        Element::F => 1.47,
+        Element::Cl => 1.75,
+        Element::Br => 1.85,
+        Element::I => 1.98,
+        Element::He => 1.40,
+        Element::Ne => 1.54,
+        Element::Ar => 1.88,
+        Element::P => 1.80,
+        Element::S => 1.80,
+        Element::B => 2.00,
+        Element::Li => 1.82,
+        Element::Na => 2.27,
+        Element::K => 2.75,
+        Element::Rb => 3.03,
+        Element::Cs => 3.43,
+        Element::Fr => 3.48,
+        Element::Be => 1.53,
+        Element::Mg => 1.73,
+        Element::Ca => 2.31,
+        Element::Sr => 2.49,
+        Element::Ba => 2.68,
+        Element::Ra => 2.83,
+        Element::Ti => 1.60,
+        Element::Fe => 1.52,
+        _ => 1.75,
+    }
+}
+
+/// A representation applied to the atoms of a selection, written `representation[:selection]`
+/// (`licorice:ligand`, `trace:protein`), the selection being every atom when omitted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepresentationAssignment {
+    pub representation: Representation,
+    pub selection: Selection,
+}
+
+impl FromStr for RepresentationAssignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (representation, selection) = match s.split_once(':') {
+            Some((representation, selection)) => (representation, selection.parse()?),
+            None => (s, Selection::All),
+        };
+        Ok(Self {
+            representation: Representation::from_str(representation.trim(), true)?,
+            selection,
+        })
+    }
+}
+
+impl fmt::Display for RepresentationAssignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let representation = self
+            .representation
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        write!(f, "{}:{}", representation, self.selection)
+    }
+}
+
+/// Representation of every atom of the structure, each assignment overriding the previous ones
+/// on its selection. Atoms outside of every selection use the default representation.
+pub fn resolve(
+    structure: &Structure,
+    assignments: &[RepresentationAssignment],
+) -> Vec<Representation> {
+    let mut representations = vec![Representation::default(); structure.atom_count()];
+    for assignment in assignments {
+        let selection = assignment.selection.evaluate(structure);
+        for (representation, selected) in representations.iter_mut().zip(selection) {
+            if selected {
+                *representation = assignment.representation;
+            }
+        }
+    }
+    representations
+}
+
+/// Backbone atoms of the residues of `atoms` drawn as a trace, in chain order, see
+/// `cartoon::guide_atom`.
+pub fn trace_atoms(
+    structure: &Structure,
+    atoms: Range<usize>,
+    representations: &[Representation],
+) -> Vec<usize> {
+    structure
+        .residues
+        .iter()
+        .enumerate()
+        .filter(|(_, residue)| atoms.start <= residue.atoms.start && residue.atoms.end <= atoms.end)
+        .filter_map(|(index, _)| cartoon::guide_atom(structure, index))
+        .map(|(_, atom)| atom)
+        .filter(|&atom| representations[atom] == Representation::Trace)
+        .collect()
+}
+
+/// Pseudo bonds of the trace between the backbone atoms of consecutive residues of a chain,
+/// `trace_atoms` being the result of the function of the same name.
+pub fn trace_bonds(
+    structure: &Structure,
+    positions: &[Point3<f32>],
+    trace_atoms: &[usize],
+) -> Vec<Bond> {
+    trace_atoms
+        .windows(2)
+        .filter_map(|pair| {
+            let (first, second) = (pair[0], pair[1]);
+            let (first_residue, second_residue) = (
+                structure.atoms[first].residue,
+                structure.atoms[second].residue,
+            );
+            let (kind, _) = cartoon::guide_atom(structure, first_residue)?;
+            let (second_kind, _) = cartoon::guide_atom(structure, second_residue)?;
+            let is_continuous = kind == second_kind
+                && second_residue == first_residue + 1
+                && structure.residues[first_residue].chain
+                    == structure.residues[second_residue].chain
+                && (positions[second] - positions[first]).norm() <= kind.max_guide_distance();
+            is_continuous.then(|| Bond::new(first, second))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::io::LoadOptions;
+
+    /// A helix (chain A, residues 1-10) and two strands (chains B and C) of alanines.
+    fn fold() -> Structure {
+        Structure::load(
+            Path::new("resources/pdb/secondary_structure.pdb"),
+            &LoadOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn assignments(assignments: &[&str]) -> Vec<RepresentationAssignment> {
+        assignments
+            .iter()
+            .map(|assignment| assignment.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parse_assignment() {
+        let assignment: RepresentationAssignment = "Licorice : chain A".parse().unwrap();
+        assert_eq!(assignment.representation, Representation::Licorice);
+        assert_eq!(
+            assignment.selection,
+            Selection::Chains(vec!["A".to_string()])
+        );
+        assert_eq!(assignment.to_string(), "licorice:chain A");
+        let assignment: RepresentationAssignment = "trace".parse().unwrap();
+        assert_eq!(assignment.selection, Selection::All);
+        assert!("cartoon:all".parse::<RepresentationAssignment>().is_err());
+    }
+
+    #[test]
+    fn later_assignments_override() {
+        let fold = fold();
+        let representations = resolve(
+            &fold,
+            &assignments(&["spacefill:chain A B", "trace:chain B", "hidden:name CA"]),
+        );
+        let representation = |chain: usize, name: &str| {
+            let atom = fold.chains[chain]
+                .atoms
+                .clone()
+                .find(|&atom| fold.atoms[atom].name == name)
+                .unwrap();
+            representations[atom]
+        };
+        assert_eq!(representation(0, "N"), Representation::Spacefill);
+        assert_eq!(representation(1, "N"), Representation::Trace);
+        assert_eq!(representation(0, "CA"), Representation::Hidden);
+        assert_eq!(representation(1, "CA"), Representation::Hidden);
+        // Outside of every selection.
+        assert_eq!(representation(2, "N"), Representation::BallAndStick);
+        assert_eq!(representation(2, "CA"), Representation::Hidden);
+
+        let representations = resolve(&fold, &[]);
+        assert!(representations
+            .iter()
+            .all(|&representation| representation == Representation::default()));
+    }
+
+    #[test]
+    fn trace() {
+        let fold = fold();
+        let positions: Vec<_> = fold.positions().collect();
+        let representations = resolve(&fold, &assignments(&["trace:chain A B"]));
+
+        let atoms = trace_atoms(&fold, 0..fold.atom_count(), &representations);
+        assert_eq!(atoms.len(), 16);
+        assert!(atoms.iter().all(|&atom| fold.atoms[atom].name == "CA"));
+        assert!(atoms.windows(2).all(|pair| pair[0] < pair[1]));
+        // Only the residues fully inside the atom range.
+        let chain = &fold.chains[1];
+        assert_eq!(
+            trace_atoms(&fold, chain.atoms.clone(), &representations).len(),
+            6
+        );
+
+        // The consecutive residues of a chain, not the last residue of A and the first of B.
+        let bonds = trace_bonds(&fold, &positions, &atoms);
+        assert_eq!(bonds.len(), 9 + 5);
+        assert_eq!(bonds[0], Bond::new(atoms[0], atoms[1]));
+        assert!(!bonds.contains(&Bond::new(atoms[9], atoms[10])));
+
+        // A chain break between two residues too far apart.
+        let mut positions = positions;
+        positions[atoms[5]].x += 10.0;
+        let bonds = trace_bonds(&fold, &positions, &atoms);
+        assert_eq!(bonds.len(), 9 + 5 - 2);
+    }
+
+    #[test]
+    fn nucleic_acid_trace() {
+        let structure = Structure::load(
+            Path::new("resources/pdb/trinucleotide.pdb"),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+        let positions: Vec<_> = structure.positions().collect();
+        let representations = vec![Representation::Trace; structure.atom_count()];
+        let atoms = trace_atoms(&structure, 0..structure.atom_count(), &representations);
+        assert_eq!(atoms.len(), 3);
+        assert!(atoms.iter().all(|&atom| structure.atoms[atom].name == "P"));
+        assert_eq!(trace_bonds(&structure, &positions, &atoms).len(), 2);
+    }
+
+    #[test]
+    fn bond_styles() {
+        use Representation::*;
+        assert_eq!(bond_style(Licorice, Licorice), Some(BondStyle::Stick));
+        assert_eq!(bond_style(Licorice, BallAndStick), Some(BondStyle::Orders));
+        assert_eq!(bond_style(BallAndStick, Wireframe), Some(BondStyle::Line));
+        for representation in [Spacefill, Trace, Hidden] {
+            assert_eq!(bond_style(representation, Licorice), None);
+            assert_eq!(bond_style(Licorice, representation), None);
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::{collections::HashSet, io::Write};
+
+use clap::ValueEnum;
+use nalgebra::Point3;
+
+use crate::{spatial_grid::SpatialGrid, structure::Structure};
+
+/// Maximum distance (in Å) between the `C` of a residue and the `N` of the next one, longer ones
+/// are chain breaks.
+const MAX_PEPTIDE_BOND_LENGTH: f32 = 2.5;
+
+/// Residues whose `CA` are further apart (in Å) are never hydrogen bonded.
+const MAX_CA_DISTANCE: f32 = 9.0;
+
+/// Electrostatic factor of the DSSP hydrogen bond energy: the partial charges of the `C=O` and
+/// `N-H` groups (0.42e and 0.20e) times the dimensional factor 332, in kcal/mol.
+const COUPLING_CONSTANT: f32 = 0.42 * 0.2 * 332.0;
+
+/// Energy (in kcal/mol) below which two backbone groups are hydrogen bonded.
+const MAX_HYDROGEN_BOND_ENERGY: f32 = -0.5;
+
+/// Lowest hydrogen bond energy (in kcal/mol), reached when atoms are overlapping.
+const MIN_HYDROGEN_BOND_ENERGY: f32 = -9.9;
+
+/// Secondary structure of a residue, reduced to the three states used for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SecondaryStructure {
+    /// Alpha, 3-10 and pi helices.
+    Helix,
+    /// Beta strand, part of a ladder of at least two bridges.
+    Strand,
+    #[default]
+    Coil,
+}
+
+impl SecondaryStructure {
+    /// One letter code, as used by DSSP.
+    pub fn code(&self) -> char {
+        match self {
+            Self::Helix => 'H',
+            Self::Strand => 'E',
+            Self::Coil => 'C',
+        }
+    }
+}
+
+/// Where the secondary structure of the residues is taken from.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecondaryStructureSource {
+    /// The `HELIX` and `SHEET` records, or the DSSP assignment when the file has none.
+    Auto,
+    /// Only the `HELIX` and `SHEET` records, every residue is a coil when the file has none.
+    Records,
+    /// Always assigned from the backbone hydrogen bonds, see `assign_dssp`.
+    Dssp,
+}
+
+/// A stretch of residues of a chain declared by a `HELIX` or `SHEET` record. Residues are
+/// identified by their serial number and insertion code, both ends are included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecondaryStructureRange {
+    pub kind: SecondaryStructure,
+    pub chain_id: String,
+    pub start: (isize, Option<String>),
+    pub end: (isize, Option<String>),
+}
+
+/// Backbone atoms positions of an amino acid, the amide hydrogen is placed from the previous
+/// residue carbonyl group and is missing for the first residue of a chain segment and prolines.
+struct Backbone {
+    residue: usize,
+    n: Point3<f32>,
+    ca: Point3<f32>,
+    c: Point3<f32>,
+    o: Point3<f32>,
+    h: Option<Point3<f32>>,
+    /// Whether the residue is bonded to the previous one in the list.
+    follows_previous: bool,
+}
+
+/// Backbone of every amino acid of a model, in chain order, residues missing one of the `N`,
+/// `CA`, `C` and `O` atoms are skipped. Only the first alternate location of an atom is used.
+fn backbones(structure: &Structure, model: usize) -> Vec<Backbone> {
+    let mut backbones: Vec<Backbone> = vec![];
+
+    for chain in &structure.chains[structure.models[model].chains.clone()] {
+        for index in chain.residues.clone() {
+            let residue = &structure.residues[index];
+            let find = |name: &str| {
+                structure.atoms[residue.atoms.clone()]
+                    .iter()
+                    .find(|atom| atom.name.trim() == name)
+                    .map(|atom| atom.position)
+            };
+            let (Some(n), Some(ca), Some(c), Some(o)) =
+                (find("N"), find("CA"), find("C"), find("O"))
+            else {
+                continue;
+            };
+
+            let previous = backbones.last().filter(|previous| {
+                structure.residues[previous.residue].chain == residue.chain
+                    && (n - previous.c).norm() <= MAX_PEPTIDE_BOND_LENGTH
+            });
+            let h = previous
+                .filter(|_| !residue.name.trim().eq_ignore_ascii_case("PRO"))
+                .map(|previous| n + (previous.c - previous.o).normalize());
+            let follows_previous = previous.is_some();
+
+            backbones.push(Backbone {
+                residue: index,
+                n,
+                ca,
+                c,
+                o,
+                h,
+                follows_previous,
+            });
+        }
+    }
+    backbones
+}
+
+/// DSSP electrostatic energy of the hydrogen bond between the `C=O` group of `acceptor` and the
+/// `N-H` group of `donor`, in kcal/mol.
+fn hydrogen_bond_energy(acceptor: &Backbone, donor: &Backbone) -> f32 {
+    let Some(h) = donor.h else {
+        return 0.0;
+    };
+    let distances = [
+        (acceptor.o - donor.n).norm(),
+        (acceptor.c - h).norm(),
+        (acceptor.o - h).norm(),
+        (acceptor.c - donor.n).norm(),
+    ];
+    if distances.iter().any(|&distance| distance < 0.5) {
+        return MIN_HYDROGEN_BOND_ENERGY;
+    }
+    let [on, ch, oh, cn] = distances;
+    let energy = COUPLING_CONSTANT * (1.0 / on + 1.0 / ch - 1.0 / oh - 1.0 / cn);
+    energy.max(MIN_HYDROGEN_BOND_ENERGY)
+}
+
+/// Assign the secondary structure of the amino acids of every model from their backbone hydrogen
+/// bonds, following the DSSP algorithm (Kabsch & Sander, 1983), and return it for every residue
+/// of the structure.
+///
+/// Two consecutive n-turns (a hydrogen bond from the `C=O` of residue i to the `N-H` of residue
+/// i+n) make a helix, alpha helices (n = 4) taking precedence over strands, which take precedence
+/// over 3-10 (n = 3) and pi (n = 5) helices. Two consecutive bridges of the same kind, parallel or
+/// antiparallel, make a strand. Unlike DSSP, ladders are not joined across beta bulges.
+pub fn assign_dssp(structure: &Structure) -> Vec<SecondaryStructure> {
+    let mut assignment = vec![SecondaryStructure::Coil; structure.residues.len()];
+
+    for model in 0..structure.models.len() {
+        let backbones = backbones(structure, model);
+        let count = backbones.len();
+        // Whether residues `start..=end` of the list form a continuous chain segment.
+        let is_continuous = |start: usize, end: usize| {
+            end < count && (start + 1..=end).all(|index| backbones[index].follows_previous)
+        };
+
+        let grid = SpatialGrid::new(
+            &backbones
+                .iter()
+                .map(|backbone| backbone.ca)
+                .collect::<Vec<_>>(),
+            MAX_CA_DISTANCE,
+        );
+        let mut hydrogen_bonds = HashSet::new();
+        for (acceptor, backbone) in backbones.iter().enumerate() {
+            for donor in grid.within(&backbone.ca, MAX_CA_DISTANCE) {
+                if donor != acceptor
+                    && donor != acceptor + 1
+                    && hydrogen_bond_energy(backbone, &backbones[donor]) < MAX_HYDROGEN_BOND_ENERGY
+                {
+                    hydrogen_bonds.insert((acceptor, donor));
+                }
+            }
+        }
+        let is_bonded = |acceptor: usize, donor: usize| hydrogen_bonds.contains(&(acceptor, donor));
+
+        let helix = |n: usize| {
+            let is_turn =
+                |start: usize| is_continuous(start, start + n) && is_bonded(start, start + n);
+            let mut helix = vec![false; count];
+            for start in 1..count {
+                if is_turn(start - 1) && is_turn(start) {
+                    helix[start..start + n].fill(true);
+                }
+            }
+            helix
+        };
+
+        let mut bridges = HashSet::new();
+        for (first, backbone) in backbones.iter().enumerate() {
+            for second in grid.within(&backbone.ca, MAX_CA_DISTANCE) {
+                if second < first + 3
+                    || first == 0
+                    || second == 0
+                    || !is_continuous(first - 1, first + 1)
+                    || !is_continuous(second - 1, second + 1)
+                {
+                    continue;
+                }
+                let parallel = (is_bonded(first - 1, second) && is_bonded(second, first + 1))
+                    || (is_bonded(second - 1, first) && is_bonded(first, second + 1));
+                let antiparallel = (is_bonded(first, second) && is_bonded(second, first))
+                    || (is_bonded(first - 1, second + 1) && is_bonded(second - 1, first + 1));
+                if parallel {
+                    bridges.insert((first, second, true));
+                }
+                if antiparallel {
+                    bridges.insert((first, second, false));
+                }
+            }
+        }
+        let mut strand = vec![false; count];
+        for &(first, second, parallel) in &bridges {
+            let next = if parallel {
+                Some(second + 1)
+            } else {
+                second.checked_sub(1)
+            };
+            if let Some(next) = next.filter(|&next| bridges.contains(&(first + 1, next, parallel)))
+            {
+                for index in [first, first + 1, second, next] {
+                    strand[index] = true;
+                }
+            }
+        }
+
+        let alpha = helix(4);
+        let others = [helix(3), helix(5)];
+        for (index, backbone) in backbones.iter().enumerate() {
+            assignment[backbone.residue] = combine(
+                alpha[index],
+                strand[index],
+                others.iter().any(|helix| helix[index]),
+            );
+        }
+    }
+    assignment
+}
+
+/// Secondary structure of a residue which is part of an alpha helix, a strand or a 3-10 or pi
+/// helix, alpha helices taking precedence over strands, which take precedence over the other
+/// helices.
+fn combine(alpha_helix: bool, strand: bool, other_helix: bool) -> SecondaryStructure {
+    if alpha_helix {
+        SecondaryStructure::Helix
+    } else if strand {
+        SecondaryStructure::Strand
+    } else if other_helix {
+        SecondaryStructure::Helix
+    } else {
+        SecondaryStructure::Coil
+    }
+}
+
+/// Whether the residue is an amino acid with a complete backbone (`N`, `CA` and `C` atoms).
+fn is_amino_acid(structure: &Structure, residue: usize) -> bool {
+    let atoms = &structure.atoms[structure.residues[residue].atoms.clone()];
+    ["N", "CA", "C"]
+        .iter()
+        .all(|name| atoms.iter().any(|atom| atom.name.trim() == *name))
+}
+
+/// Write the secondary structure of every amino acid as tab separated text, one residue per line
+/// with its model serial number, chain, residue serial number (followed by its insertion code),
+/// residue name and one letter secondary structure code.
+pub fn write_assignment(structure: &Structure, writer: &mut impl Write) -> anyhow::Result<()> {
+    writeln!(writer, "model\tchain\tresidue\tname\tss")?;
+    for (index, residue) in structure.residues.iter().enumerate() {
+        if !is_amino_acid(structure, index) {
+            continue;
+        }
+        let chain = &structure.chains[residue.chain];
+        writeln!(
+            writer,
+            "{}\t{}\t{}{}\t{}\t{}",
+            structure.models[chain.model].serial_number,
+            chain.id,
+            residue.serial_number,
+            residue.insertion_code.as_deref().unwrap_or_default(),
+            residue.name.trim(),
+            residue.secondary_structure.code(),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::io::LoadOptions;
+
+    /// Ideal alpha helix (chain A) and two antiparallel strands (chains B and C), without `HELIX`
+    /// and `SHEET` records.
+    fn fold() -> Structure {
+        Structure::load(
+            Path::new("resources/pdb/secondary_structure.pdb"),
+            &LoadOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn codes(structure: &Structure, assignment: &[SecondaryStructure], chain: usize) -> String {
+        structure.chains[chain]
+            .residues
+            .clone()
+            .map(|residue| assignment[residue].code())
+            .collect()
+    }
+
+    /// Backbone of a residue with only its `N-H` group.
+    fn donor(n: Point3<f32>, h: Option<Point3<f32>>) -> Backbone {
+        Backbone {
+            residue: 0,
+            n,
+            ca: n,
+            c: n,
+            o: n,
+            h,
+            follows_previous: true,
+        }
+    }
+
+    /// Backbone of a residue with only its `C=O` group, along the x axis.
+    fn acceptor(o: Point3<f32>) -> Backbone {
+        Backbone {
+            residue: 1,
+            n: o,
+            ca: o,
+            c: o + Vector3::new(1.23, 0.0, 0.0),
+            o,
+            h: None,
+            follows_previous: false,
+        }
+    }
+
+    #[test]
+    fn amide_hydrogens() {
+        let mut fold = fold();
+        let backbones = backbones(&fold, 0);
+        assert_eq!(backbones.len(), 22);
+        // First residue of every chain.
+        for index in [0, 10, 16] {
+            assert!(!backbones[index].follows_previous);
+            assert_eq!(backbones[index].h, None);
+        }
+        // 1 Å from the nitrogen, opposite to the carbonyl of the previous residue.
+        let (previous, backbone) = (&backbones[4], &backbones[5]);
+        let h = backbone.h.unwrap();
+        assert!(((h - backbone.n).norm() - 1.0).abs() < 1e-5);
+        assert!(((h - backbone.n) - (previous.c - previous.o).normalize()).norm() < 1e-5);
+
+        // Prolines have no amide hydrogen.
+        fold.residues[5].name = "PRO".to_string();
+        let backbones = super::backbones(&fold, 0);
+        assert_eq!(backbones[5].h, None);
+        assert!(backbones[5].follows_previous);
+    }
+
+    #[test]
+    fn energy_cutoff() {
+        let n = Point3::origin();
+        let h = Some(Point3::new(1.0, 0.0, 0.0));
+        let energy = |distance: f32| {
+            hydrogen_bond_energy(
+                &acceptor(Point3::new(1.0 + distance, 0.0, 0.0)),
+                &donor(n, h),
+            )
+        };
+        // Linear N-H...O=C groups, bonded up to about 4 Å between the hydrogen and the oxygen.
+        assert!(energy(2.0) < -2.0);
+        assert!(energy(3.5) < MAX_HYDROGEN_BOND_ENERGY);
+        assert!(energy(4.5) > MAX_HYDROGEN_BOND_ENERGY);
+        assert!(energy(4.5) < 0.0);
+
+        assert_eq!(energy(0.2), MIN_HYDROGEN_BOND_ENERGY);
+        let o = Point3::new(3.0, 0.0, 0.0);
+        assert_eq!(hydrogen_bond_energy(&acceptor(o), &donor(n, None)), 0.0);
+    }
+
+    #[test]
+    fn priority() {
+        use SecondaryStructure::*;
+        assert_eq!(combine(true, true, true), Helix);
+        assert_eq!(combine(false, true, true), Strand);
+        assert_eq!(combine(false, false, true), Helix);
+        assert_eq!(combine(false, false, false), Coil);
+    }
+
+    #[test]
+    fn dssp() {
+        let fold = fold();
+        let assignment = assign_dssp(&fold);
+        assert_eq!(codes(&fold, &assignment, 0), "CHHHHHHHHC");
+        assert_eq!(codes(&fold, &assignment, 1), "CEEECC");
+        assert_eq!(codes(&fold, &assignment, 2), "CEEECC");
+    }
+
+    #[test]
+    fn export() {
+        let mut fold = fold();
+        fold.assign_secondary_structure(SecondaryStructureSource::Auto);
+        let mut text = vec![];
+        write_assignment(&fold, &mut text).unwrap();
+        let mut expected = "model\tchain\tresidue\tname\tss\n".to_string();
+        for (chain, first, codes) in [
+            ("A", 1, "CHHHHHHHHC"),
+            ("B", 20, "CEEECC"),
+            ("C", 40, "CEEECC"),
+        ] {
+            for (offset, code) in codes.chars().enumerate() {
+                expected += &format!("0\t{chain}\t{}\tALA\t{code}\n", first + offset);
+            }
+        }
+        assert_eq!(String::from_utf8(text).unwrap(), expected);
+
+        // Only the records are used, the file has none.
+        fold.assign_secondary_structure(SecondaryStructureSource::Records);
+        let mut text = vec![];
+        write_assignment(&fold, &mut text).unwrap();
+        assert!(String::from_utf8(text)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .all(|line| line.ends_with("\tC")));
+    }
+}
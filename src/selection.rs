@@ -0,0 +1,789 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use pdbtbx::Element;
+
+use crate::{
+    spatial_grid::SpatialGrid,
+    structure::Structure,
+    templates::{self, ResidueKind},
+};
+
+/// A set of atoms of a structure, parsed from a selection expression and evaluated to one flag
+/// per atom (see `evaluate`).
+///
+/// Expressions combine keywords (`all`, `none`, `protein`, `nucleic`, `water`, `ligand`,
+/// `hetatm`, `hydrogen`) and properties followed by one or more values (`chain A B`,
+/// `resname HIS`, `name CA`, `element Fe`, `resi 10-50`, `serial 1-100`) with `not`, `and`,
+/// `or` and parentheses, from the tightest to the loosest binding. `within 5.0 of <selection>`
+/// and `same residue as <selection>` bind as tightly as `not`, so their selection is usually
+/// parenthesized.
+/// Residues are classified from their template (see `templates`), ligands being the residues
+/// without one. A bare list of residue codes (`MOL,HEM`), uppercase and 1 to 4 characters long,
+/// is a shorthand for `resname`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    All,
+    None,
+    Protein,
+    NucleicAcid,
+    Water,
+    Ligand,
+    /// Atoms from `HETATM` records.
+    Hetero,
+    Hydrogen,
+    /// The chains with one of these identifiers (case sensitive).
+    Chains(Vec<String>),
+    /// The residues with one of these names (case insensitive).
+    ResidueNames(Vec<String>),
+    /// The atoms with one of these names (case insensitive).
+    AtomNames(Vec<String>),
+    Elements(Vec<Element>),
+    /// The residues whose serial number is in one of these ranges.
+    ResidueSerials(Vec<RangeInclusive<isize>>),
+    /// The atoms whose serial number is in one of these ranges.
+    AtomSerials(Vec<RangeInclusive<isize>>),
+    Not(Box<Selection>),
+    And(Box<Selection>, Box<Selection>),
+    Or(Box<Selection>, Box<Selection>),
+    /// Atoms at a distance lower or equal to the given one (in Å) from an atom of the selection,
+    /// in the same model.
+    Within(f32, Box<Selection>),
+    /// Every atom of the residues holding an atom of the selection.
+    SameResidue(Box<Selection>),
+}
+
+impl Selection {
+    /// Whether each atom of the structure is selected.
+    pub fn evaluate(&self, structure: &Structure) -> Vec<bool> {
+        let atoms = &structure.atoms;
+        let per_atom = |predicate: &dyn Fn(usize) -> bool| -> Vec<bool> {
+            (0..atoms.len()).map(predicate).collect()
+        };
+        let kind = |atom: usize| {
+            templates::template(&structure.residue(atom).name).map(|template| template.kind)
+        };
+
+        match self {
+            Self::All => vec![true; atoms.len()],
+            Self::None => vec![false; atoms.len()],
+            Self::Protein => per_atom(&|atom| kind(atom) == Some(ResidueKind::AminoAcid)),
+            Self::NucleicAcid => per_atom(&|atom| kind(atom) == Some(ResidueKind::Nucleotide)),
+            Self::Water => per_atom(&|atom| kind(atom) == Some(ResidueKind::Water)),
+            Self::Ligand => per_atom(&|atom| kind(atom).is_none()),
+            Self::Hetero => per_atom(&|atom| atoms[atom].hetero),
+            Self::Hydrogen => per_atom(&|atom| atoms[atom].element == Some(Element::H)),
+            Self::Chains(ids) => per_atom(&|atom| {
+                let id = &structure.chain(atom).id;
+                ids.iter().any(|chain| chain == id.trim())
+            }),
+            Self::ResidueNames(names) => per_atom(&|atom| {
+                let residue = &structure.residue(atom).name;
+                names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(residue.trim()))
+            }),
+            Self::AtomNames(names) => per_atom(&|atom| {
+                let atom_name = atoms[atom].name.trim();
+                names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(atom_name))
+            }),
+            Self::Elements(elements) => per_atom(&|atom| {
+                atoms[atom]
+                    .element
+                    .is_some_and(|element| elements.contains(&element))
+            }),
+            Self::ResidueSerials(ranges) => per_atom(&|atom| {
+                let serial = structure.residue(atom).serial_number;
+                ranges.iter().any(|range| range.contains(&serial))
+            }),
+            Self::AtomSerials(ranges) => per_atom(&|atom| {
+                let serial = atoms[atom].serial_number as isize;
+                ranges.iter().any(|range| range.contains(&serial))
+            }),
+            Self::Not(selection) => selection
+                .evaluate(structure)
+                .into_iter()
+                .map(|selected| !selected)
+                .collect(),
+            Self::And(first, second) => first
+                .evaluate(structure)
+                .into_iter()
+                .zip(second.evaluate(structure))
+                .map(|(first, second)| first && second)
+                .collect(),
+            Self::Or(first, second) => first
+                .evaluate(structure)
+                .into_iter()
+                .zip(second.evaluate(structure))
+                .map(|(first, second)| first || second)
+                .collect(),
+            Self::Within(distance, selection) => {
+                let selected = selection.evaluate(structure);
+                let mut result = vec![false; atoms.len()];
+                for model in &structure.models {
+                    let targets = model
+                        .atoms
+                        .clone()
+                        .filter(|&atom| selected[atom])
+                        .collect::<Vec<_>>();
+                    if targets.is_empty() {
+                        continue;
+                    }
+                    let target_positions = targets
+                        .iter()
+                        .map(|&atom| atoms[atom].position)
+                        .collect::<Vec<_>>();
+                    let grid = SpatialGrid::new(&target_positions, distance.max(1.0));
+                    for atom in model.atoms.clone() {
+                        result[atom] = !grid.within(&atoms[atom].position, *distance).is_empty();
+                    }
+                }
+                result
+            }
+            Self::SameResidue(selection) => {
+                let selected = selection.evaluate(structure);
+                let mut residues = vec![false; structure.residues.len()];
+                for (atom, _) in selected
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, selected)| **selected)
+                {
+                    residues[atoms[atom].residue] = true;
+                }
+                per_atom(&|atom| residues[atoms[atom].residue])
+            }
+        }
+    }
+
+    /// Indices of the selected atoms, in `Structure::atoms`.
+    pub fn atoms(&self, structure: &Structure) -> Vec<usize> {
+        self.evaluate(structure)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(atom, selected)| selected.then_some(atom))
+            .collect()
+    }
+
+    /// Binding strength of the expression, used to parenthesize it when it is displayed.
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or(..) => 0,
+            Self::And(..) => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Open,
+    Close,
+}
+
+/// Split a selection expression into words and parentheses, along with their column (starting
+/// at 1). Commas separate values like spaces do.
+fn tokenize(s: &str) -> Vec<(usize, Token)> {
+    let mut tokens = vec![];
+    let mut word: Option<(usize, String)> = None;
+
+    for (column, c) in s.chars().enumerate().map(|(index, c)| (index + 1, c)) {
+        if c.is_whitespace() || c == ',' || c == '(' || c == ')' {
+            if let Some((start, word)) = word.take() {
+                tokens.push((start, Token::Word(word)));
+            }
+            match c {
+                '(' => tokens.push((column, Token::Open)),
+                ')' => tokens.push((column, Token::Close)),
+                _ => {}
+            }
+        } else {
+            word.get_or_insert_with(|| (column, String::new()))
+                .1
+                .push(c);
+        }
+    }
+    if let Some((start, word)) = word {
+        tokens.push((start, Token::Word(word)));
+    }
+    tokens
+}
+
+/// Keywords which can't be the value of a property.
+const OPERATORS: [&str; 2] = ["and", "or"];
+
+const KEYWORDS: &str =
+    "all, none, protein, nucleic, water, ligand, hetatm, hydrogen, chain, resname, name, \
+     element, resi, serial, not, within <distance> of, same residue as";
+
+/// Recursive descent parser of the selection expressions, see `Selection`.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Column just after the end of the expression, reported for the errors at its end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(column, _)| *column)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, message: impl fmt::Display) -> String {
+        format!("{message} (column {})", self.column())
+    }
+
+    /// Description of the current token for the error messages.
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(Token::Word(word)) => format!("`{word}`"),
+            Some(Token::Open) => "`(`".to_string(),
+            Some(Token::Close) => "`)`".to_string(),
+            None => "the end of the selection".to_string(),
+        }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str, context: &str) -> Result<(), String> {
+        if !self.peek_keyword(keyword) {
+            return Err(self.error(format!(
+                "expected `{keyword}` {context}, found {}",
+                self.found()
+            )));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Selection, String> {
+        let mut selection = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.position += 1;
+            selection = Selection::Or(Box::new(selection), Box::new(self.parse_and()?));
+        }
+        Ok(selection)
+    }
+
+    fn parse_and(&mut self) -> Result<Selection, String> {
+        let mut selection = self.parse_unary()?;
+        while self.peek_keyword("and") {
+            self.position += 1;
+            selection = Selection::And(Box::new(selection), Box::new(self.parse_unary()?));
+        }
+        Ok(selection)
+    }
+
+    fn parse_unary(&mut self) -> Result<Selection, String> {
+        let column = self.column();
+        let keyword = match self.peek() {
+            Some(Token::Word(word)) => word.to_ascii_lowercase(),
+            Some(Token::Open) => {
+                self.position += 1;
+                let selection = self.parse_or()?;
+                if self.next() != Some(Token::Close) {
+                    self.position -= 1;
+                    return Err(self.error(format!(
+                        "expected `)` to close the `(` of column {column}, found {}",
+                        self.found()
+                    )));
+                }
+                return Ok(selection);
+            }
+            _ => {
+                return Err(self.error(format!("expected a selection, found {}", self.found())));
+            }
+        };
+        self.position += 1;
+
+        let selection = match keyword.as_str() {
+            "all" => Selection::All,
+            "none" => Selection::None,
+            "protein" => Selection::Protein,
+            "nucleic" => Selection::NucleicAcid,
+            "water" => Selection::Water,
+            "ligand" => Selection::Ligand,
+            "hetatm" => Selection::Hetero,
+            "hydrogen" => Selection::Hydrogen,
+            "chain" => Selection::Chains(self.parse_values("a chain identifier")?),
+            "resname" => Selection::ResidueNames(self.parse_values("a residue name")?),
+            "name" => Selection::AtomNames(self.parse_values("an atom name")?),
+            "element" => Selection::Elements(self.parse_elements()?),
+            "resi" => Selection::ResidueSerials(self.parse_ranges("a residue number")?),
+            "serial" => Selection::AtomSerials(self.parse_ranges("an atom serial number")?),
+            "not" => Selection::Not(Box::new(self.parse_unary()?)),
+            "within" => {
+                let distance = match self.next() {
+                    Some(Token::Word(word)) => word.parse::<f32>().ok(),
+                    _ => None,
+                }
+                .filter(|distance| distance.is_finite() && *distance >= 0.0);
+                let Some(distance) = distance else {
+                    self.position -= 1;
+                    return Err(self.error(format!(
+                        "expected a distance after `within`, found {}",
+                        self.found()
+                    )));
+                };
+                self.expect_keyword("of", "after the `within` distance")?;
+                Selection::Within(distance, Box::new(self.parse_unary()?))
+            }
+            "same" => {
+                self.expect_keyword("residue", "after `same`")?;
+                self.expect_keyword("as", "after `same residue`")?;
+                Selection::SameResidue(Box::new(self.parse_unary()?))
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.error(format!(
+                    "unknown keyword {}, expected one of {KEYWORDS}",
+                    self.found()
+                )));
+            }
+        };
+        Ok(selection)
+    }
+
+    /// Values of a property, up to the next operator or parenthesis.
+    fn parse_values(&mut self, expected: &str) -> Result<Vec<String>, String> {
+        let mut values = vec![];
+        while let Some(Token::Word(word)) = self.peek() {
+            if OPERATORS
+                .iter()
+                .any(|operator| word.eq_ignore_ascii_case(operator))
+            {
+                break;
+            }
+            values.push(word.clone());
+            self.position += 1;
+        }
+        if values.is_empty() {
+            return Err(self.error(format!("expected {expected}, found {}", self.found())));
+        }
+        Ok(values)
+    }
+
+    fn parse_elements(&mut self) -> Result<Vec<Element>, String> {
+        let start = self.position;
+        let symbols = self.parse_values("an element symbol")?;
+        let mut elements = vec![];
+        for (index, symbol) in symbols.iter().enumerate() {
+            let Some(element) = Element::from_symbol(symbol) else {
+                self.position = start + index;
+                return Err(self.error(format!("unknown element `{symbol}`")));
+            };
+            elements.push(element);
+        }
+        Ok(elements)
+    }
+
+    /// Values of a numeric property, either single numbers or `start-end` inclusive ranges.
+    fn parse_ranges(&mut self, expected: &str) -> Result<Vec<RangeInclusive<isize>>, String> {
+        let start = self.position;
+        let values = self.parse_values(expected)?;
+        let mut ranges = vec![];
+        for (index, value) in values.iter().enumerate() {
+            // The first character may be the sign of the range start.
+            let range = match value.get(1..).and_then(|rest| rest.find('-')) {
+                Some(separator) => {
+                    let (first, last) = value.split_at(separator + 1);
+                    first.parse().ok().zip(last[1..].parse().ok())
+                }
+                None => value.parse().ok().map(|serial| (serial, serial)),
+            };
+            let Some((first, last)) = range else {
+                self.position = start + index;
+                return Err(self.error(format!("expected {expected} or a range, found `{value}`")));
+            };
+            ranges.push(first..=last);
+        }
+        Ok(ranges)
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s);
+        if tokens.is_empty() {
+            return Err("empty selection".to_string());
+        }
+
+        // Bare residue codes, such as `MOL` or `MOL,HEM`. Other words are misspelled keywords.
+        let is_keyword = |word: &str| {
+            let word = word.to_ascii_lowercase();
+            KEYWORDS
+                .split(',')
+                .flat_map(str::split_whitespace)
+                .any(|keyword| keyword == word)
+        };
+        let is_residue_code = |word: &str| {
+            (1..=4).contains(&word.len())
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        };
+        if !s.trim().contains(char::is_whitespace) {
+            if let Some((_, Token::Word(first))) = tokens.first() {
+                if !is_keyword(first)
+                    && tokens.iter().all(
+                        |(_, token)| matches!(token, Token::Word(word) if is_residue_code(word)),
+                    )
+                {
+                    return Ok(Self::ResidueNames(
+                        tokens
+                            .into_iter()
+                            .filter_map(|(_, token)| match token {
+                                Token::Word(word) => Some(word),
+                                _ => None,
+                            })
+                            .collect(),
+                    ));
+                }
+            }
+        }
+
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end: s.chars().count() + 1,
+        };
+        let selection = parser.parse_or()?;
+        if parser.peek().is_some() {
+            return Err(parser.error(format!(
+                "expected `and`, `or` or the end of the selection, found {}",
+                parser.found()
+            )));
+        }
+        Ok(selection)
+    }
+}
+
+/// Write the ranges as `start-end`, or a single number when they hold one.
+fn write_ranges(f: &mut fmt::Formatter<'_>, ranges: &[RangeInclusive<isize>]) -> fmt::Result {
+    let ranges = ranges
+        .iter()
+        .map(|range| match range.start() == range.end() {
+            true => range.start().to_string(),
+            false => format!("{}-{}", range.start(), range.end()),
+        })
+        .collect::<Vec<_>>();
+    write!(f, "{}", ranges.join(" "))
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Operands binding more loosely than their operator are parenthesized.
+        let operand =
+            |selection: &Selection, precedence: u8| match selection.precedence() < precedence {
+                true => format!("({selection})"),
+                false => selection.to_string(),
+            };
+        match self {
+            Self::All => write!(f, "all"),
+            Self::None => write!(f, "none"),
+            Self::Protein => write!(f, "protein"),
+            Self::NucleicAcid => write!(f, "nucleic"),
+            Self::Water => write!(f, "water"),
+            Self::Ligand => write!(f, "ligand"),
+            Self::Hetero => write!(f, "hetatm"),
+            Self::Hydrogen => write!(f, "hydrogen"),
+            Self::Chains(ids) => write!(f, "chain {}", ids.join(" ")),
+            Self::ResidueNames(names) => write!(f, "resname {}", names.join(" ")),
+            Self::AtomNames(names) => write!(f, "name {}", names.join(" ")),
+            Self::Elements(elements) => {
+                let symbols = elements.iter().map(Element::symbol).collect::<Vec<_>>();
+                write!(f, "element {}", symbols.join(" "))
+            }
+            Self::ResidueSerials(ranges) => {
+                write!(f, "resi ")?;
+                write_ranges(f, ranges)
+            }
+            Self::AtomSerials(ranges) => {
+                write!(f, "serial ")?;
+                write_ranges(f, ranges)
+            }
+            Self::Not(selection) => write!(f, "not {}", operand(selection, 2)),
+            Self::And(first, second) => {
+                write!(f, "{} and {}", operand(first, 1), operand(second, 2))
+            }
+            Self::Or(first, second) => {
+                write!(f, "{} or {}", operand(first, 0), operand(second, 1))
+            }
+            Self::Within(distance, selection) => {
+                write!(f, "within {distance} of {}", operand(selection, 2))
+            }
+            Self::SameResidue(selection) => {
+                write!(f, "same residue as {}", operand(selection, 2))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::io::LoadOptions;
+
+    fn parse(s: &str) -> Selection {
+        s.parse().unwrap()
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn precedence() {
+        let chain = |id: &str| Box::new(Selection::Chains(names(&[id])));
+        assert_eq!(
+            parse("chain A or chain B and not chain C"),
+            Selection::Or(
+                chain("A"),
+                Box::new(Selection::And(
+                    chain("B"),
+                    Box::new(Selection::Not(chain("C")))
+                ))
+            )
+        );
+        assert_eq!(
+            parse("(chain A or chain B) and chain C"),
+            Selection::And(Box::new(Selection::Or(chain("A"), chain("B"))), chain("C"))
+        );
+        assert_eq!(
+            parse("NOT protein AND water"),
+            Selection::And(
+                Box::new(Selection::Not(Box::new(Selection::Protein))),
+                Box::new(Selection::Water)
+            )
+        );
+    }
+
+    #[test]
+    fn properties() {
+        assert_eq!(
+            parse("resname HIS HID,HIE"),
+            Selection::ResidueNames(names(&["HIS", "HID", "HIE"]))
+        );
+        assert_eq!(
+            parse("element Fe zn"),
+            Selection::Elements(vec![Element::Fe, Element::Zn])
+        );
+        assert_eq!(
+            parse("resi -5--1 10 20-30"),
+            Selection::ResidueSerials(vec![-5..=-1, 10..=10, 20..=30])
+        );
+    }
+
+    #[test]
+    fn within() {
+        assert_eq!(
+            parse("within 5.0 of ligand and protein"),
+            Selection::And(
+                Box::new(Selection::Within(5.0, Box::new(Selection::Ligand))),
+                Box::new(Selection::Protein)
+            )
+        );
+        assert_eq!(
+            parse("within 3 of (chain A or water)"),
+            Selection::Within(
+                3.0,
+                Box::new(Selection::Or(
+                    Box::new(Selection::Chains(names(&["A"]))),
+                    Box::new(Selection::Water)
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn same_residue() {
+        assert_eq!(
+            parse("same residue as (within 5.0 of ligand)"),
+            Selection::SameResidue(Box::new(Selection::Within(
+                5.0,
+                Box::new(Selection::Ligand)
+            )))
+        );
+        assert_eq!(
+            parse("same residue as not hydrogen"),
+            Selection::SameResidue(Box::new(Selection::Not(Box::new(Selection::Hydrogen))))
+        );
+    }
+
+    #[test]
+    fn residue_code_shorthand() {
+        assert_eq!(parse("MOL"), Selection::ResidueNames(names(&["MOL"])));
+        assert_eq!(
+            parse("MOL,HEM,A1"),
+            Selection::ResidueNames(names(&["MOL", "HEM", "A1"]))
+        );
+        // Keywords and misspelled keywords are not residue codes.
+        assert_eq!(parse("ALL"), Selection::All);
+        assert!("protien".parse::<Selection>().is_err());
+        assert_eq!(parse("LIGAND"), Selection::Ligand);
+        assert!("LIGANDS".parse::<Selection>().is_err());
+        assert!("MOL,hem".parse::<Selection>().is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for expression in [
+            "chain A or chain B and not chain C",
+            "(chain A or chain B) and chain C",
+            "not (protein or water)",
+            "within 5.5 of (ligand or hetatm) and name CA",
+            "same residue as (within 5 of resname MOL)",
+            "resi -5--1 10 20-30",
+        ] {
+            let selection = parse(expression);
+            assert_eq!(parse(&selection.to_string()), selection);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |s: &str| s.parse::<Selection>().unwrap_err();
+        assert_eq!(error(""), "empty selection");
+        assert_eq!(
+            error("protien"),
+            format!("unknown keyword `protien`, expected one of {KEYWORDS} (column 1)")
+        );
+        assert_eq!(
+            error("chain A and"),
+            "expected a selection, found the end of the selection (column 12)"
+        );
+        assert_eq!(
+            error("(chain A or water"),
+            "expected `)` to close the `(` of column 1, found the end of the selection (column 18)"
+        );
+        assert_eq!(
+            error("chain A )"),
+            "expected `and`, `or` or the end of the selection, found `)` (column 9)"
+        );
+        assert_eq!(
+            error("within -1 of ligand"),
+            "expected a distance after `within`, found `-1` (column 8)"
+        );
+        assert_eq!(
+            error("within 5 ligand"),
+            "expected `of` after the `within` distance, found `ligand` (column 10)"
+        );
+        assert_eq!(
+            error("same as ligand"),
+            "expected `residue` after `same`, found `as` (column 6)"
+        );
+        assert_eq!(
+            error("chain and water"),
+            "expected a chain identifier, found `and` (column 7)"
+        );
+        assert_eq!(error("element C Xx"), "unknown element `Xx` (column 11)");
+        assert_eq!(
+            error("resi 1-a"),
+            "expected a residue number or a range, found `1-a` (column 6)"
+        );
+    }
+
+    /// Two models of the alanines 9, 10, 50 and 51 (`N` and `CA` atoms, chain A), a ligand (chain
+    /// B) and a water (chain C), 11 atoms each. The ligand is 4 Å from the alanine 10 in the first
+    /// model and away from the protein in the second one.
+    fn models() -> Structure {
+        Structure::load(
+            Path::new("resources/pdb/selection.pdb"),
+            &LoadOptions::default(),
+        )
+        .unwrap()
+    }
+
+    /// Selected atoms of a model, by index in the model.
+    fn selected(structure: &Structure, expression: &str, model: usize) -> Vec<usize> {
+        let atoms = structure.models[model].atoms.clone();
+        parse(expression)
+            .atoms(structure)
+            .into_iter()
+            .filter(|atom| atoms.contains(atom))
+            .map(|atom| atom - atoms.start)
+            .collect()
+    }
+
+    #[test]
+    fn evaluate_within() {
+        let models = models();
+        assert_eq!(models.models.len(), 2);
+        assert_eq!(
+            selected(&models, "within 5.0 of resname LIG", 0),
+            [2, 3, 8, 9]
+        );
+        // The alanine 10 of the second model is next to the ligand of the first one only.
+        assert_eq!(selected(&models, "within 5.0 of resname LIG", 1), [8, 9]);
+        assert_eq!(selected(&models, "within 4.0 of name C1", 0), [3, 8, 9]);
+        assert_eq!(selected(&models, "within 5.0 of none", 0), []);
+    }
+
+    #[test]
+    fn evaluate_same_residue() {
+        let models = models();
+        for model in 0..2 {
+            assert_eq!(selected(&models, "same residue as name O1", model), [8, 9]);
+            assert_eq!(
+                selected(&models, "same residue as (name CA and resi 10 51)", model),
+                [2, 3, 6, 7]
+            );
+        }
+        assert_eq!(
+            selected(&models, "same residue as (within 5.0 of ligand)", 0),
+            [2, 3, 8, 9]
+        );
+    }
+
+    #[test]
+    fn evaluate_properties() {
+        let models = models();
+        assert_eq!(selected(&models, "hetatm", 0), [8, 9, 10]);
+        assert_eq!(selected(&models, "hetatm and not water", 1), [8, 9]);
+        assert_eq!(selected(&models, "ligand", 0), [8, 9]);
+        assert_eq!(selected(&models, "resi 10-50", 0), [2, 3, 4, 5]);
+        assert_eq!(selected(&models, "resi 51 100-200", 1), [6, 7, 8, 9, 10]);
+        assert_eq!(selected(&models, "name ca and chain A", 0), [1, 3, 5, 7]);
+        assert_eq!(selected(&models, "element O", 0), [9, 10]);
+        assert_eq!(selected(&models, "serial 1-2", 1), [0, 1]);
+    }
+
+    #[test]
+    fn evaluate_operators() {
+        let models = models();
+        assert_eq!(selected(&models, "not protein", 0), [8, 9, 10]);
+        assert_eq!(
+            selected(&models, "protein and not name CA", 0),
+            [0, 2, 4, 6]
+        );
+        assert_eq!(selected(&models, "resi 9 or water", 0), [0, 1, 10]);
+        assert_eq!(
+            selected(&models, "not (resi 9 or water) and chain A", 0),
+            [2, 3, 4, 5, 6, 7]
+        );
+        assert_eq!(selected(&models, "all", 1).len(), 11);
+        assert_eq!(selected(&models, "none", 1), []);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+
+/// Folder of the shader sources, relative to the working directory.
+const SHADER_FOLDER: &str = "./resources/shaders";
+
+/// Read the shader `name` of the shader folder, with the shared GLSL `chunks` of the same folder
+/// (`clipping.glsl`, `shadow.glsl`) inserted after its `#version` line. A `#line` directive
+/// follows the chunks so that the compilation errors give the line numbers of the shader file.
+pub fn read_shader(name: &str, chunks: &[&str]) -> anyhow::Result<String> {
+    let read = |name: &str| {
+        fs::read_to_string(format!("{SHADER_FOLDER}/{name}"))
+            .map_err(|error| anyhow::format_err!("Failed to read the `{name}` shader: {error}"))
+    };
+    let shader = read(name)?;
+    if chunks.is_empty() {
+        return Ok(shader);
+    }
+    let chunks = chunks
+        .iter()
+        .map(|chunk| read(chunk))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(insert_chunks(&shader, &chunks))
+}
+
+/// Insert the `chunks` in `shader` after its first line (the `#version` directive).
+fn insert_chunks(shader: &str, chunks: &[String]) -> String {
+    let (version, body) = shader.split_once('\n').unwrap_or((shader, ""));
+    let mut source = format!("{version}\n");
+    for chunk in chunks {
+        source.push_str(chunk);
+        if !chunk.ends_with('\n') {
+            source.push('\n');
+        }
+    }
+    source.push_str("#line 2\n");
+    source.push_str(body);
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_after_version() {
+        let source = insert_chunks(
+            "#version 410 core\n\nvoid main() {}\n",
+            &["float a;".to_string(), "float b;\n".to_string()],
+        );
+        assert_eq!(
+            source,
+            "#version 410 core\nfloat a;\nfloat b;\n#line 2\n\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn shared_chunks() {
+        for name in [
+            "sphere_imposter.frag",
+            "cylinder_imposter.frag",
+            "mesh.frag",
+        ] {
+            let source = read_shader(name, &["clipping.glsl", "shadow.glsl"]).unwrap();
+            assert!(source.starts_with("#version 410 core\n"));
+            assert_eq!(source.matches("float light_visibility(").count(), 1);
+            assert_eq!(source.matches("bool is_clipped(").count(), 1);
+        }
+        let source = read_shader("line.frag", &["clipping.glsl"]).unwrap();
+        assert_eq!(source.matches("vec3 apply_fog(").count(), 1);
+        assert!(read_shader("missing.frag", &[]).is_err());
+    }
+}
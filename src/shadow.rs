@@ -0,0 +1,104 @@
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    glutin::surface::WindowSurface,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption},
+    uniforms::{
+        DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, Sampler,
+        SamplerBehavior, SamplerWrapFunction,
+    },
+};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+/// Shadows of a directional light, coming from `light_position` towards the origin.
+/// The scene is first drawn from the light with an orthographic projection into `depth`, the
+/// imposters ray casting parallel rays so that they write the depth of their actual surface. The
+/// fragment shaders then compare the depth of their point in the light view with it.
+/// `radius` is the radius (in world space units) of the sphere the light view covers, it should
+/// hold the whole molecule.
+pub struct ShadowMap {
+    pub enabled: bool,
+    pub depth: DepthTexture2d,
+    pub radius: f32,
+}
+
+impl ShadowMap {
+    pub fn new(
+        display: &glium::Display<WindowSurface>,
+        enabled: bool,
+        resolution: u32,
+        radius: f32,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            enabled,
+            depth: DepthTexture2d::empty_with_format(
+                display,
+                DepthFormat::F32,
+                MipmapsOption::NoMipmap,
+                resolution,
+                resolution,
+            )?,
+            radius,
+        })
+    }
+
+    /// Depth only framebuffer the shadow pass is drawn into.
+    pub fn framebuffer(
+        &self,
+        display: &glium::Display<WindowSurface>,
+    ) -> anyhow::Result<SimpleFrameBuffer<'_>> {
+        Ok(SimpleFrameBuffer::depth_only(display, &self.depth)?)
+    }
+
+    /// Position the light view looks from, far enough to see the whole molecule in front of it.
+    pub fn light_eye(&self, light_position: &Point3<f32>) -> Point3<f32> {
+        let direction = light_position
+            .coords
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+        Point3::from(direction * 2.0 * self.radius)
+    }
+
+    pub fn light_view(&self, light_position: &Point3<f32>) -> Matrix4<f32> {
+        let eye = self.light_eye(light_position);
+        let up = match eye.coords.normalize().y.abs() > 0.99 {
+            true => Vector3::z(),
+            false => Vector3::y(),
+        };
+        Matrix4::look_at_rh(&eye, &Point3::origin(), &up)
+    }
+
+    pub fn light_projection(&self) -> Matrix4<f32> {
+        let radius = self.radius;
+        Matrix4::new_orthographic(-radius, radius, -radius, radius, 0.5 * radius, 3.5 * radius)
+    }
+
+    /// Matrix from the world space to the clip space of the light.
+    pub fn light_space(&self, light_position: &Point3<f32>) -> Matrix4<f32> {
+        self.light_projection() * self.light_view(light_position)
+    }
+
+    /// Offset (in world space units) of the shadow lookups along the surface normal, one and a
+    /// half texel of the shadow map, avoiding the shadow acne.
+    pub fn bias(&self) -> f32 {
+        1.5 * 2.0 * self.radius / self.depth.width() as f32
+    }
+
+    /// Sampler of the shadow map comparing the depths, the linear filtering interpolates the
+    /// comparison results of the neighbouring texels.
+    pub fn sampler(&self) -> Sampler<'_, DepthTexture2d> {
+        Sampler(
+            &self.depth,
+            SamplerBehavior {
+                wrap_function: (
+                    SamplerWrapFunction::Clamp,
+                    SamplerWrapFunction::Clamp,
+                    SamplerWrapFunction::Clamp,
+                ),
+                minify_filter: MinifySamplerFilter::Linear,
+                magnify_filter: MagnifySamplerFilter::Linear,
+                depth_texture_comparison: Some(DepthTextureComparison::LessOrEqual),
+                ..Default::default()
+            },
+        )
+    }
+}
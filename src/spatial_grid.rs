@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use nalgebra::Point3;
+
+/// Uniform spatial hashing grid over a set of points.
+/// Used to answer neighbour queries (bonds, contacts, ...) without comparing every pair of atoms,
+/// which is not affordable for structures with thousands of atoms.
+///
+/// `cell_size` should be close to the typical query radius, a query visits every cell overlapping
+/// the query sphere bounding box.
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+    positions: Vec<Point3<f32>>,
+}
+
+impl SpatialGrid {
+    pub fn new(positions: &[Point3<f32>], cell_size: f32) -> Self {
+        let mut grid = Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            positions: positions.to_vec(),
+        };
+
+        for (index, position) in positions.iter().enumerate() {
+            let cell = grid.cell_of(position);
+            grid.cells.entry(cell).or_default().push(index);
+        }
+        grid
+    }
+
+    fn cell_of(&self, position: &Point3<f32>) -> (i32, i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Return the indices of every point at a distance lower or equal to `radius` from `center`.
+    pub fn within(&self, center: &Point3<f32>, radius: f32) -> Vec<usize> {
+        let mut result = Vec::new();
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let (cx, cy, cz) = self.cell_of(center);
+        let radius_squared = radius * radius;
+
+        for x in cx - reach..=cx + reach {
+            for y in cy - reach..=cy + reach {
+                for z in cz - reach..=cz + reach {
+                    let Some(cell) = self.cells.get(&(x, y, z)) else {
+                        continue;
+                    };
+                    result.extend(cell.iter().copied().filter(|&index| {
+                        (self.positions[index] - center).norm_squared() <= radius_squared
+                    }));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn positions(&self) -> &[Point3<f32>] {
+        &self.positions
+    }
+}
@@ -6,7 +6,10 @@ use glium::{
 };
 use nalgebra::{Point3, Point4};
 
-use crate::geometry::quad::{Quad, QuadVertex};
+use crate::{
+    geometry::quad::{Quad, QuadVertex},
+    shaders,
+};
 
 /// This struct hold a instancing imposter sphere batch informations.
 /// `vertex_buffer` hold the quad geometry for the sphere imposter, and `index_buffer` contains the
@@ -82,7 +85,8 @@ impl SphereBatch {
     /// Build the sphere imposter GLSL Program and return it.
     pub fn build_program(display: &glium::Display<WindowSurface>) -> anyhow::Result<Program> {
         let vertex_shader = fs::read_to_string("./resources/shaders/sphere_imposter.vert")?;
-        let fragment_shader = fs::read_to_string("./resources/shaders/sphere_imposter.frag")?;
+        let fragment_shader =
+            shaders::read_shader("sphere_imposter.frag", &["clipping.glsl", "shadow.glsl"])?;
         if vertex_shader.is_empty() || fragment_shader.is_empty() {
             return Err(anyhow::format_err!(
                 "Fragment or Vertex shader file are empty"
@@ -0,0 +1,224 @@
+use std::fs;
+
+use glium::{
+    framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer},
+    glutin::surface::WindowSurface,
+    implement_vertex,
+    index::{NoIndices, PrimitiveType},
+    program,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::{
+        MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction,
+    },
+    Program, Surface, VertexBuffer,
+};
+use nalgebra::Matrix4;
+
+/// Vertex of the quad covering the viewport, in normalized device coordinates.
+#[derive(Copy, Clone, Debug)]
+pub struct ScreenVertex {
+    pub position: [f32; 2],
+}
+
+implement_vertex!(ScreenVertex, position);
+
+/// `strength` scales the darkening, from 0 (none) to 1, `radius` is the radius (in world space
+/// units) of the hemisphere sampled around each pixel and `samples` the number of samples per
+/// pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoParameters {
+    pub strength: f32,
+    pub radius: f32,
+    pub samples: u32,
+}
+
+/// Offscreen targets of the scene when the ambient occlusion is enabled: the lit colors, the view
+/// space normals and the depth written by the imposters, all of the window size.
+pub struct GeometryBuffer {
+    pub color: Texture2d,
+    pub normal: Texture2d,
+    pub depth: DepthTexture2d,
+    pub occlusion: Texture2d,
+}
+
+impl GeometryBuffer {
+    pub fn new(
+        display: &glium::Display<WindowSurface>,
+        (width, height): (u32, u32),
+    ) -> anyhow::Result<Self> {
+        let color_texture = |format| {
+            Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+        };
+        Ok(Self {
+            color: color_texture(UncompressedFloatFormat::U8U8U8U8)?,
+            normal: color_texture(UncompressedFloatFormat::F16F16F16F16)?,
+            depth: DepthTexture2d::empty_with_format(
+                display,
+                DepthFormat::F32,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )?,
+            occlusion: color_texture(UncompressedFloatFormat::U8)?,
+        })
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.color.dimensions()
+    }
+
+    /// Framebuffer the scene is drawn into, its programs must write both `frag_color` and
+    /// `frag_normal`.
+    pub fn scene_framebuffer(
+        &self,
+        display: &glium::Display<WindowSurface>,
+    ) -> anyhow::Result<MultiOutputFrameBuffer<'_>> {
+        Ok(MultiOutputFrameBuffer::with_depth_buffer(
+            display,
+            [("frag_color", &self.color), ("frag_normal", &self.normal)],
+            &self.depth,
+        )?)
+    }
+}
+
+/// Screen space ambient occlusion, drawn as a post process of the scene rendered in a
+/// `GeometryBuffer`.
+/// The occlusion of each pixel is the fraction of the samples of the hemisphere around its normal
+/// hidden by the depth buffer, the composite pass then blurs it and darkens the scene colors with
+/// it. Working on the depth buffer, it handles the imposters `gl_FragDepth` the same way as the
+/// meshes.
+pub struct SsaoPass {
+    pub enabled: bool,
+    pub parameters: SsaoParameters,
+    geometry: Option<GeometryBuffer>,
+    quad: VertexBuffer<ScreenVertex>,
+    occlusion_program: Program,
+    composite_program: Program,
+}
+
+impl SsaoPass {
+    pub fn new(
+        display: &glium::Display<WindowSurface>,
+        enabled: bool,
+        parameters: SsaoParameters,
+    ) -> anyhow::Result<Self> {
+        let quad = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]]
+            .map(|position| ScreenVertex { position });
+        Ok(Self {
+            enabled,
+            parameters,
+            geometry: None,
+            quad: VertexBuffer::new(display, &quad)?,
+            occlusion_program: Self::build_program(display, "ssao.frag")?,
+            composite_program: Self::build_program(display, "ssao_composite.frag")?,
+        })
+    }
+
+    /// Recreate the geometry buffer when the window has been resized, should be called before
+    /// drawing the scene.
+    pub fn resize(
+        &mut self,
+        display: &glium::Display<WindowSurface>,
+        dimensions: (u32, u32),
+    ) -> anyhow::Result<()> {
+        let outdated = self
+            .geometry
+            .as_ref()
+            .is_none_or(|geometry| geometry.dimensions() != dimensions);
+        if outdated {
+            self.geometry = Some(GeometryBuffer::new(display, dimensions)?);
+        }
+        Ok(())
+    }
+
+    /// Framebuffer of the geometry buffer the scene is drawn into, see `resize`.
+    pub fn scene_framebuffer(
+        &self,
+        display: &glium::Display<WindowSurface>,
+    ) -> anyhow::Result<MultiOutputFrameBuffer<'_>> {
+        self.geometry
+            .as_ref()
+            .ok_or_else(|| anyhow::format_err!("The geometry buffer has not been created"))?
+            .scene_framebuffer(display)
+    }
+
+    /// Compute the occlusion of the scene drawn in the geometry buffer, and draw the occluded
+    /// scene to `target`. `projection` is the projection the scene was drawn with.
+    pub fn draw(
+        &self,
+        display: &glium::Display<WindowSurface>,
+        target: &mut impl Surface,
+        projection: &Matrix4<f32>,
+    ) -> anyhow::Result<()> {
+        let Some(geometry) = &self.geometry else {
+            return Ok(());
+        };
+        // Texels are read one by one, without filtering or wrapping.
+        let nearest = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+        let inverse_projection = projection
+            .try_inverse()
+            .ok_or_else(|| anyhow::format_err!("The projection matrix is not invertible"))?;
+        let projection: [[f32; 4]; 4] = (*projection).into();
+        let inverse_projection: [[f32; 4]; 4] = inverse_projection.into();
+
+        let mut occlusion = SimpleFrameBuffer::new(display, &geometry.occlusion)?;
+        occlusion.draw(
+            &self.quad,
+            NoIndices(PrimitiveType::TriangleStrip),
+            &self.occlusion_program,
+            &uniform! {
+                depth_texture: Sampler(&geometry.depth, nearest),
+                normal_texture: Sampler(&geometry.normal, nearest),
+                projection: projection,
+                inverse_projection: inverse_projection,
+                radius: self.parameters.radius,
+                samples: self.parameters.samples as i32,
+            },
+            &Default::default(),
+        )?;
+
+        target.draw(
+            &self.quad,
+            NoIndices(PrimitiveType::TriangleStrip),
+            &self.composite_program,
+            &uniform! {
+                color_texture: Sampler(&geometry.color, nearest),
+                occlusion_texture: Sampler(&geometry.occlusion, nearest),
+                strength: self.parameters.strength,
+            },
+            &Default::default(),
+        )?;
+        Ok(())
+    }
+
+    /// Build a program drawing the screen quad with the given fragment shader.
+    fn build_program(
+        display: &glium::Display<WindowSurface>,
+        fragment_shader: &str,
+    ) -> anyhow::Result<Program> {
+        let vertex_shader = fs::read_to_string("./resources/shaders/screen_quad.vert")?;
+        let fragment_shader = fs::read_to_string(format!("./resources/shaders/{fragment_shader}"))?;
+        if vertex_shader.is_empty() || fragment_shader.is_empty() {
+            return Err(anyhow::format_err!(
+                "Fragment or Vertex shader file are empty"
+            ));
+        }
+        let program = program!(display,
+            410 => {
+                vertex: &vertex_shader,
+                fragment: &fragment_shader,
+            },
+        )?;
+        Ok(program)
+    }
+}
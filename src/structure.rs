@@ -0,0 +1,733 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Range,
+    path::Path,
+};
+
+use nalgebra::Point3;
+use pdbtbx::Element;
+
+use crate::{
+    bonding::{self, Bond, BondOrder, BondOrderSource, BondSource},
+    io::{self, LoadOptions, LoadedStructure, StructureFormat},
+    secondary_structure::{
+        self, SecondaryStructure, SecondaryStructureRange, SecondaryStructureSource,
+    },
+    templates,
+};
+
+/// Maximum distance (in Å) between a hydrogen of a standard residue and the heavy atom it is
+/// bonded to.
+const MAX_HYDROGEN_DISTANCE: f32 = 1.5;
+
+/// Maximum length (in Å) of a peptide or phosphodiester bond, longer ones are chain breaks.
+const MAX_LINK_LENGTH: f32 = 2.0;
+
+/// A molecular structure, free of any GPU related data.
+/// Atoms of every model are stored in a single flat list, ordered by model, chain and residue.
+/// Residues, chains and models reference their atoms (and children) with index ranges, and atoms
+/// reference their residue by index, so the hierarchy can be walked both ways.
+/// Bonds reference the atoms by their index in `atoms`.
+#[derive(Debug, Clone, Default)]
+pub struct Structure {
+    /// Identifier of the structure, the PDB ID or the molecule name when the file has one.
+    pub identifier: Option<String>,
+    /// Format of the file the structure was read from, `None` for structures built in memory.
+    pub format: Option<StructureFormat>,
+    pub atoms: Vec<Atom>,
+    pub residues: Vec<Residue>,
+    pub chains: Vec<Chain>,
+    pub models: Vec<Model>,
+    pub bonds: Vec<Bond>,
+    /// Bonds between residues declared by the `SSBOND` and `LINK` records, they are kept apart
+    /// from `bonds` so that they survive `assign_bonds`.
+    pub links: Vec<Bond>,
+    /// Helices and strands declared by the `HELIX` and `SHEET` records, see
+    /// `assign_secondary_structure`.
+    pub secondary_structure_ranges: Vec<SecondaryStructureRange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atom {
+    pub serial_number: usize,
+    pub name: String,
+    pub element: Option<Element>,
+    /// Position in Å.
+    pub position: Point3<f32>,
+    pub b_factor: f32,
+    pub occupancy: f32,
+    /// Formal charge, in elementary charge unit.
+    pub formal_charge: isize,
+    /// Partial charge, in elementary charge unit, when the file has some (MOL2).
+    pub partial_charge: Option<f32>,
+    /// Mass number, when the atom is not the natural isotope mixture.
+    pub isotope: Option<u16>,
+    /// Force field atom type (Sybyl type for MOL2 files).
+    pub atom_type: Option<String>,
+    /// Whether the atom comes from a `HETATM` record.
+    pub hetero: bool,
+    /// Alternate location indicator of the conformer holding the atom.
+    pub alternate_location: Option<String>,
+    /// Index of the residue holding the atom, in `Structure::residues`.
+    pub residue: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Residue {
+    pub name: String,
+    pub serial_number: isize,
+    pub insertion_code: Option<String>,
+    /// Index of the chain holding the residue, in `Structure::chains`.
+    pub chain: usize,
+    pub atoms: Range<usize>,
+    /// Coil until `Structure::assign_secondary_structure` is called.
+    pub secondary_structure: SecondaryStructure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chain {
+    pub id: String,
+    /// Index of the model holding the chain, in `Structure::models`.
+    pub model: usize,
+    pub residues: Range<usize>,
+    pub atoms: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model {
+    pub serial_number: usize,
+    pub chains: Range<usize>,
+    pub atoms: Range<usize>,
+    /// Free form key/value properties of the model (extended XYZ comment line, SDF data items).
+    pub properties: BTreeMap<String, String>,
+}
+
+impl Structure {
+    /// Read a structure file, the bonds are the ones written in the file, see `assign_bonds` to
+    /// infer them.
+    pub fn load(path: &Path, options: &LoadOptions) -> anyhow::Result<Self> {
+        Ok(io::load_structure(path, options)?.into())
+    }
+
+    pub fn atom_count(&self) -> usize {
+        self.atoms.len()
+    }
+
+    pub fn residue(&self, atom: usize) -> &Residue {
+        &self.residues[self.atoms[atom].residue]
+    }
+
+    pub fn chain(&self, atom: usize) -> &Chain {
+        &self.chains[self.residue(atom).chain]
+    }
+
+    pub fn model(&self, atom: usize) -> &Model {
+        &self.models[self.chain(atom).model]
+    }
+
+    /// First atom of a residue named `name`, so the first alternate location when the atom has
+    /// several. Pre-remediation names of the nucleotides (`O3*`) match the current ones (`O3'`).
+    pub fn find_atom(&self, residue: usize, name: &str) -> Option<usize> {
+        self.residues[residue]
+            .atoms
+            .clone()
+            .find(|&atom| self.atoms[atom].name.trim().replace('*', "'") == name)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Point3<f32>> + '_ {
+        self.atoms.iter().map(|atom| atom.position)
+    }
+
+    /// Mean position of the atoms, the origin for an empty structure.
+    pub fn center(&self) -> Point3<f32> {
+        if self.atoms.is_empty() {
+            return Point3::origin();
+        }
+        let sum = self
+            .positions()
+            .fold(Point3::origin().coords, |sum, position| {
+                sum + position.coords
+            });
+        Point3::from(sum / self.atoms.len() as f32)
+    }
+
+    /// Minimum and maximum corners of the axis aligned box containing every atom.
+    pub fn bounding_box(&self) -> (Point3<f32>, Point3<f32>) {
+        let mut positions = self.positions();
+        let Some(first) = positions.next() else {
+            return (Point3::origin(), Point3::origin());
+        };
+        positions.fold((first, first), |(min, max), position| {
+            (min.inf(&position), max.sup(&position))
+        })
+    }
+
+    /// Replace the bonds according to `source`, the bonds read from the file, the bonds perceived
+    /// from the covalent radii (see `bonding::perceive_bonds`), the bonds of the standard residue
+    /// templates (see `template_bonds`) or both.
+    /// Structures whose format has no connectivity never use the file bonds, and the merged bonds
+    /// of the formats listing every bond (SDF, MOL2) are the file bonds alone.
+    pub fn assign_bonds(&mut self, source: BondSource, tolerance: f32) {
+        let has_connectivity = self.format.is_some_and(|format| format.has_connectivity());
+        let has_complete_connectivity = self
+            .format
+            .is_some_and(|format| format.has_complete_connectivity());
+        let source = match source {
+            BondSource::Conect if !has_connectivity => BondSource::Inferred,
+            BondSource::Merged if !has_connectivity => BondSource::Templates,
+            BondSource::Merged if has_complete_connectivity => BondSource::Conect,
+            source => source,
+        };
+
+        let explicit = bonding::merge_bonds(&self.bonds, &self.links);
+        self.bonds = match source {
+            BondSource::Conect => explicit,
+            BondSource::Inferred => self.infer_bonds(tolerance),
+            BondSource::Templates => {
+                bonding::merge_bonds(&self.links, &self.template_bonds(tolerance))
+            }
+            BondSource::Merged => bonding::merge_bonds(&explicit, &self.template_bonds(tolerance)),
+        };
+    }
+
+    /// Bonds of the standard residues (amino acids, nucleotides, water) built from their
+    /// templates (see `templates`), with the peptide and phosphodiester bonds between consecutive
+    /// residues of a chain. Hydrogens of the standard residues are bonded to their closest heavy
+    /// atom. The other residues get their bonds inferred from the atoms coordinates, only the
+    /// inferred bonds between two standard residues are dropped.
+    pub fn template_bonds(&self, tolerance: f32) -> Vec<Bond> {
+        let residue_templates = self
+            .residues
+            .iter()
+            .map(|residue| templates::template(&residue.name))
+            .collect::<Vec<_>>();
+        let mut bonds = vec![];
+        let mut previous_names = HashMap::new();
+
+        for (index, residue) in self.residues.iter().enumerate() {
+            let names = self.atom_names(residue);
+            let Some(template) = residue_templates[index] else {
+                previous_names = names;
+                continue;
+            };
+
+            for &(first, second, order) in template.bonds() {
+                bonds.extend(self.bond_named_atoms(&names, first, &names, second, order));
+            }
+            bonds.extend(self.hydrogen_bonds(residue));
+
+            let is_linked = index.checked_sub(1).is_some_and(|previous| {
+                self.residues[previous].chain == residue.chain
+                    && residue_templates[previous]
+                        .is_some_and(|previous| previous.kind == template.kind)
+            });
+            if let Some((first, second)) = template.kind.link().filter(|_| is_linked) {
+                bonds.extend(
+                    self.bond_named_atoms(
+                        &previous_names,
+                        first,
+                        &names,
+                        second,
+                        BondOrder::Single,
+                    )
+                    .filter(|bond| self.bond_length(bond) < MAX_LINK_LENGTH),
+                );
+            }
+            previous_names = names;
+        }
+
+        let is_standard = |atom: usize| residue_templates[self.atoms[atom].residue].is_some();
+        let inferred = self
+            .infer_bonds(tolerance)
+            .into_iter()
+            .filter(|bond| !is_standard(bond.first) || !is_standard(bond.second))
+            .collect::<Vec<_>>();
+        bonding::merge_bonds(&bonds, &inferred)
+    }
+
+    /// Distance between the atoms of a bond, in Å.
+    pub fn bond_length(&self, bond: &Bond) -> f32 {
+        (self.atoms[bond.second].position - self.atoms[bond.first].position).norm()
+    }
+
+    /// Atoms of a residue by name, a name being shared by the alternate locations of an atom.
+    /// Pre-remediation names of the nucleotides (`O3*`) are converted to the current ones (`O3'`).
+    fn atom_names(&self, residue: &Residue) -> HashMap<String, Vec<usize>> {
+        let mut names = HashMap::<_, Vec<_>>::new();
+        for atom in residue.atoms.clone() {
+            names
+                .entry(self.atoms[atom].name.trim().replace('*', "'"))
+                .or_default()
+                .push(atom);
+        }
+        names
+    }
+
+    /// Bond the atoms named `first` and `second`, every pair of alternate locations which can
+    /// coexist is bonded.
+    fn bond_named_atoms<'a>(
+        &'a self,
+        first_names: &'a HashMap<String, Vec<usize>>,
+        first: &str,
+        second_names: &'a HashMap<String, Vec<usize>>,
+        second: &str,
+        order: BondOrder,
+    ) -> impl Iterator<Item = Bond> + 'a {
+        let first_atoms = first_names
+            .get(first)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let second_atoms = second_names
+            .get(second)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        first_atoms.iter().flat_map(move |&first| {
+            second_atoms
+                .iter()
+                .filter(move |&&second| self.can_coexist(first, second))
+                .map(move |&second| Bond::with_order(first, second, order))
+        })
+    }
+
+    /// Bond every hydrogen of the residue to its closest heavy atom.
+    fn hydrogen_bonds(&self, residue: &Residue) -> Vec<Bond> {
+        let is_hydrogen = |atom: usize| self.atoms[atom].element == Some(Element::H);
+
+        residue
+            .atoms
+            .clone()
+            .filter(|&atom| is_hydrogen(atom))
+            .filter_map(|hydrogen| {
+                residue
+                    .atoms
+                    .clone()
+                    .filter(|&atom| !is_hydrogen(atom) && self.can_coexist(hydrogen, atom))
+                    .map(|atom| Bond::new(hydrogen, atom))
+                    .map(|bond| (bond, self.bond_length(&bond)))
+                    .filter(|(_, length)| *length < MAX_HYDROGEN_DISTANCE)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(bond, _)| bond)
+            })
+            .collect()
+    }
+
+    /// Whether two atoms belong to the same conformation, atoms without alternate location being
+    /// part of every conformation.
+    fn can_coexist(&self, first: usize, second: usize) -> bool {
+        match (
+            &self.atoms[first].alternate_location,
+            &self.atoms[second].alternate_location,
+        ) {
+            (Some(first), Some(second)) => first == second,
+            _ => true,
+        }
+    }
+
+    /// Replace the bond orders according to `source`. In `Auto` mode, the orders are perceived
+    /// from the geometry (see `bonding::perceive_bond_orders`) unless the file format has orders,
+    /// and only for the residues which do not already have some (from the residue templates or
+    /// repeated `CONECT` entries).
+    pub fn assign_bond_orders(&mut self, source: BondOrderSource) {
+        let has_orders = self.format.is_some_and(|format| format.has_bond_orders());
+
+        match source {
+            BondOrderSource::Auto if has_orders => {}
+            BondOrderSource::Auto | BondOrderSource::Perceive => {
+                let ordered_residues = match source {
+                    BondOrderSource::Auto => self
+                        .bonds
+                        .iter()
+                        .filter(|bond| bond.order != BondOrder::Single)
+                        .flat_map(|bond| [bond.first, bond.second])
+                        .map(|atom| self.atoms[atom].residue)
+                        .collect(),
+                    _ => HashSet::new(),
+                };
+
+                let positions = self.positions().collect::<Vec<_>>();
+                let elements = self
+                    .atoms
+                    .iter()
+                    .map(|atom| atom.element.as_ref())
+                    .collect::<Vec<_>>();
+                let perceived = bonding::perceive_bond_orders(&positions, &elements, &self.bonds);
+
+                for (bond, perceived) in self.bonds.iter_mut().zip(perceived) {
+                    let is_ordered = [bond.first, bond.second]
+                        .iter()
+                        .any(|&atom| ordered_residues.contains(&self.atoms[atom].residue));
+                    if !is_ordered {
+                        bond.order = perceived.order;
+                    }
+                }
+            }
+            BondOrderSource::Ignore => {
+                for bond in &mut self.bonds {
+                    bond.order = BondOrder::Single;
+                }
+            }
+        }
+    }
+
+    /// Assign the secondary structure of the residues according to `source`, from the `HELIX`
+    /// and `SHEET` records (applied to every model) or from the backbone geometry (see
+    /// `secondary_structure::assign_dssp`). In `Auto` mode the records are used when the file has
+    /// some.
+    pub fn assign_secondary_structure(&mut self, source: SecondaryStructureSource) {
+        let assignment = match source {
+            SecondaryStructureSource::Auto if !self.secondary_structure_ranges.is_empty() => {
+                self.secondary_structure_from_records()
+            }
+            SecondaryStructureSource::Records => self.secondary_structure_from_records(),
+            SecondaryStructureSource::Auto | SecondaryStructureSource::Dssp => {
+                secondary_structure::assign_dssp(self)
+            }
+        };
+        for (residue, secondary_structure) in self.residues.iter_mut().zip(assignment) {
+            residue.secondary_structure = secondary_structure;
+        }
+    }
+
+    /// Secondary structure of every residue declared by the `HELIX` and `SHEET` records, ranges
+    /// whose ends can't be found in a chain are ignored.
+    fn secondary_structure_from_records(&self) -> Vec<SecondaryStructure> {
+        let mut assignment = vec![SecondaryStructure::Coil; self.residues.len()];
+
+        for chain in &self.chains {
+            let position = |(serial_number, insertion_code): &(isize, Option<String>)| {
+                chain.residues.clone().find(|&index| {
+                    let residue = &self.residues[index];
+                    residue.serial_number == *serial_number
+                        && residue.insertion_code == *insertion_code
+                })
+            };
+            for range in self
+                .secondary_structure_ranges
+                .iter()
+                .filter(|range| range.chain_id == chain.id)
+            {
+                if let (Some(start), Some(end)) = (position(&range.start), position(&range.end)) {
+                    if start <= end {
+                        assignment[start..=end].fill(range.kind);
+                    }
+                }
+            }
+        }
+        assignment
+    }
+
+    /// Infer the bonds from the atoms coordinates, each model is handled separately so that
+    /// overlapping models are never bonded together.
+    pub fn infer_bonds(&self, tolerance: f32) -> Vec<Bond> {
+        let mut bonds = vec![];
+
+        for model in &self.models {
+            let atoms = &self.atoms[model.atoms.clone()];
+            let positions = atoms.iter().map(|atom| atom.position).collect::<Vec<_>>();
+            let elements = atoms
+                .iter()
+                .map(|atom| atom.element.as_ref())
+                .collect::<Vec<_>>();
+
+            let offset = model.atoms.start;
+            bonds.extend(
+                bonding::perceive_bonds(&positions, &elements, tolerance)
+                    .into_iter()
+                    .map(|bond| Bond {
+                        first: bond.first + offset,
+                        second: bond.second + offset,
+                        ..bond
+                    }),
+            );
+        }
+        bonds
+    }
+}
+
+impl From<LoadedStructure> for Structure {
+    fn from(loaded: LoadedStructure) -> Self {
+        let mut structure = Structure {
+            identifier: loaded.pdb.identifier.clone().or_else(|| {
+                loaded
+                    .properties
+                    .first()
+                    .and_then(|properties| properties.get("name").cloned())
+            }),
+            format: Some(loaded.format),
+            bonds: loaded.bonds,
+            links: loaded.links,
+            secondary_structure_ranges: loaded.secondary_structure,
+            ..Default::default()
+        };
+
+        for (model_index, model) in loaded.pdb.models().enumerate() {
+            let model_chains_start = structure.chains.len();
+            let model_atoms_start = structure.atoms.len();
+
+            for chain in model.chains() {
+                let chain_index = structure.chains.len();
+                let chain_residues_start = structure.residues.len();
+                let chain_atoms_start = structure.atoms.len();
+
+                for residue in chain.residues() {
+                    let residue_index = structure.residues.len();
+                    let residue_atoms_start = structure.atoms.len();
+
+                    for conformer in residue.conformers() {
+                        for atom in conformer.atoms() {
+                            let properties = loaded
+                                .atom_properties
+                                .get(structure.atoms.len())
+                                .cloned()
+                                .unwrap_or_default();
+
+                            structure.atoms.push(Atom {
+                                serial_number: atom.serial_number(),
+                                name: atom.name().to_string(),
+                                element: atom.element().copied(),
+                                position: Point3::new(
+                                    atom.x() as f32,
+                                    atom.y() as f32,
+                                    atom.z() as f32,
+                                ),
+                                b_factor: atom.b_factor() as f32,
+                                occupancy: atom.occupancy() as f32,
+                                formal_charge: atom.charge(),
+                                partial_charge: properties.partial_charge,
+                                isotope: properties.isotope,
+                                atom_type: properties.atom_type,
+                                hetero: atom.hetero(),
+                                alternate_location: conformer
+                                    .alternative_location()
+                                    .map(str::to_string),
+                                residue: residue_index,
+                            });
+                        }
+                    }
+
+                    structure.residues.push(Residue {
+                        name: residue.name().unwrap_or_default().to_string(),
+                        serial_number: residue.serial_number(),
+                        insertion_code: residue.insertion_code().map(str::to_string),
+                        chain: chain_index,
+                        atoms: residue_atoms_start..structure.atoms.len(),
+                        secondary_structure: SecondaryStructure::Coil,
+                    });
+                }
+
+                structure.chains.push(Chain {
+                    id: chain.id().to_string(),
+                    model: model_index,
+                    residues: chain_residues_start..structure.residues.len(),
+                    atoms: chain_atoms_start..structure.atoms.len(),
+                });
+            }
+
+            structure.models.push(Model {
+                serial_number: model.serial_number(),
+                chains: model_chains_start..structure.chains.len(),
+                atoms: model_atoms_start..structure.atoms.len(),
+                properties: loaded
+                    .properties
+                    .get(model_index)
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+        }
+
+        structure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::RecordSelector;
+
+    /// Default `--bond-tolerance`.
+    const TOLERANCE: f32 = 0.45;
+
+    fn load(path: &str) -> Structure {
+        Structure::load(Path::new(path), &LoadOptions::default()).unwrap()
+    }
+
+    fn count_orders(structure: &Structure, order: BondOrder) -> usize {
+        structure
+            .bonds
+            .iter()
+            .filter(|bond| bond.order == order)
+            .count()
+    }
+
+    #[test]
+    fn hierarchy() {
+        let complex = load("resources/pdb/complex.pdb");
+        assert_eq!(complex.format, Some(StructureFormat::Pdb));
+        assert_eq!(complex.models.len(), 1);
+        assert_eq!(complex.models[0].atoms, 0..complex.atom_count());
+        let chains = complex
+            .chains
+            .iter()
+            .map(|chain| chain.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(chains, ["X", "A", "B"]);
+
+        for (index, atom) in complex.atoms.iter().enumerate() {
+            assert!(complex.residues[atom.residue].atoms.contains(&index));
+            assert!(complex.chain(index).atoms.contains(&index));
+        }
+        for (index, residue) in complex.residues.iter().enumerate() {
+            assert!(complex.chains[residue.chain].residues.contains(&index));
+        }
+
+        let ligand = &complex.residues[0];
+        assert_eq!(ligand.name, "MOL");
+        assert_eq!(complex.find_atom(0, "C2"), Some(1));
+        assert_eq!(complex.find_atom(0, "CA"), None);
+    }
+
+    #[test]
+    fn atom_properties() {
+        let ethanol = load("resources/molecules/ethanol.mol2");
+        assert_eq!(ethanol.identifier.as_deref(), Some("ethanol"));
+        assert_eq!(ethanol.atoms[0].atom_type.as_deref(), Some("C.3"));
+        assert_eq!(ethanol.atoms[9].partial_charge, Some(-0.834));
+        assert_eq!(ethanol.residue(9).name, "HOH");
+        assert_eq!(ethanol.chain(9).id, "B");
+
+        let ethylene = load("resources/molecules/ligands.sdf");
+        assert_eq!(ethylene.identifier.as_deref(), Some("ethylene"));
+        assert_eq!(ethylene.atoms[0].isotope, Some(13));
+        assert_eq!(ethylene.models[0].properties["FORMULA"], "C2H4");
+
+        let water = load("resources/molecules/water.xyz");
+        assert_eq!(water.models.len(), 2);
+        assert_eq!(water.models[1].atoms, 3..6);
+    }
+
+    #[test]
+    fn infer_bonds() {
+        for (path, bond_count) in [
+            ("resources/pdb/methane.pdb", 4),
+            ("resources/pdb/ethylene.pdb", 5),
+            ("resources/pdb/propane.pdb", 10),
+            ("resources/pdb/benzene.pdb", 12),
+        ] {
+            let structure = load(path);
+            let bonds = structure.infer_bonds(TOLERANCE);
+            assert_eq!(bonds.len(), bond_count, "{path}");
+            assert!(bonds.iter().all(|bond| bond.first < bond.second));
+        }
+
+        // Frames of an XYZ file are never bonded together.
+        let water = load("resources/molecules/water.xyz");
+        let bonds = water.infer_bonds(TOLERANCE);
+        assert_eq!(bonds.len(), 4);
+        assert!(bonds
+            .iter()
+            .all(|bond| water.model(bond.first) == water.model(bond.second)));
+    }
+
+    #[test]
+    fn file_bonds() {
+        let mut ethylene = load("resources/pdb/ethylene.pdb");
+        assert_eq!(ethylene.bonds.len(), 5);
+        ethylene.assign_bonds(BondSource::Conect, TOLERANCE);
+        ethylene.assign_bond_orders(BondOrderSource::Auto);
+        assert_eq!(count_orders(&ethylene, BondOrder::Double), 1);
+
+        ethylene.assign_bond_orders(BondOrderSource::Ignore);
+        assert_eq!(count_orders(&ethylene, BondOrder::Single), 5);
+
+        // XYZ files have no connectivity, their bonds are always inferred.
+        let mut water = load("resources/molecules/water.xyz");
+        water.assign_bonds(BondSource::Conect, TOLERANCE);
+        assert_eq!(water.bonds.len(), 4);
+    }
+
+    #[test]
+    fn complete_connectivity() {
+        // The sodium is close enough to an oxygen to be inferred as bonded to it.
+        let options = LoadOptions {
+            record: Some(RecordSelector::Name("sodium acetate".to_string())),
+        };
+        let mut acetate =
+            Structure::load(Path::new("resources/molecules/ligands.sdf"), &options).unwrap();
+        assert_eq!(acetate.bonds.len(), 6);
+        acetate.assign_bonds(BondSource::Merged, TOLERANCE);
+        assert_eq!(acetate.bonds.len(), 6);
+        acetate.assign_bonds(BondSource::Inferred, TOLERANCE);
+        assert_eq!(acetate.bonds.len(), 7);
+
+        let mut ethanol = load("resources/molecules/ethanol.mol2");
+        ethanol.assign_bonds(BondSource::Merged, TOLERANCE);
+        assert_eq!(ethanol.bonds.len(), 10);
+    }
+
+    #[test]
+    fn perceive_bond_orders() {
+        let mut ethylene = load("resources/pdb/ethylene.pdb");
+        ethylene.assign_bonds(BondSource::Inferred, TOLERANCE);
+        ethylene.assign_bond_orders(BondOrderSource::Perceive);
+        assert_eq!(count_orders(&ethylene, BondOrder::Double), 1);
+        assert_eq!(count_orders(&ethylene, BondOrder::Single), 4);
+        let double = ethylene
+            .bonds
+            .iter()
+            .find(|bond| bond.order == BondOrder::Double)
+            .unwrap();
+        assert_eq!((double.first, double.second), (0, 1));
+
+        let mut benzene = load("resources/pdb/benzene.pdb");
+        benzene.assign_bonds(BondSource::Merged, TOLERANCE);
+        benzene.assign_bond_orders(BondOrderSource::Auto);
+        assert_eq!(count_orders(&benzene, BondOrder::Aromatic), 6);
+        assert_eq!(count_orders(&benzene, BondOrder::Single), 6);
+        assert!(benzene
+            .bonds
+            .iter()
+            .filter(|bond| bond.order == BondOrder::Aromatic)
+            .all(|bond| bond.second < 6));
+
+        let mut methane = load("resources/pdb/methane.pdb");
+        methane.assign_bonds(BondSource::Inferred, TOLERANCE);
+        methane.assign_bond_orders(BondOrderSource::Perceive);
+        assert_eq!(count_orders(&methane, BondOrder::Single), 4);
+    }
+
+    #[test]
+    fn template_bonds() {
+        let mut complex = load("resources/pdb/complex.pdb");
+        complex.assign_bonds(BondSource::Templates, TOLERANCE);
+        assert!(complex
+            .bonds
+            .iter()
+            .all(|bond| complex.bond_length(bond) < 2.0));
+
+        // Every atom of the standard residues is bonded.
+        let mut bonded = vec![false; complex.atom_count()];
+        for bond in &complex.bonds {
+            bonded[bond.first] = true;
+            bonded[bond.second] = true;
+        }
+        let standard_atoms = complex
+            .atoms
+            .iter()
+            .enumerate()
+            .filter(|(_, atom)| templates::template(&complex.residues[atom.residue].name).is_some())
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert!(!standard_atoms.is_empty());
+        assert!(standard_atoms.iter().all(|&atom| bonded[atom]));
+
+        // Peptide bonds link the consecutive residues of a chain.
+        let chain = &complex.chains[2];
+        let (first, second) = (chain.residues.start, chain.residues.start + 1);
+        let carbon = complex.find_atom(first, "C").unwrap();
+        let nitrogen = complex.find_atom(second, "N").unwrap();
+        assert!(complex
+            .bonds
+            .iter()
+            .any(|bond| (bond.first, bond.second) == (carbon, nitrogen)));
+    }
+}
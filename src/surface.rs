@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use nalgebra::{Point3, Point4, Vector3};
+
+use crate::{
+    mesh_batch::{Mesh, MeshVertex},
+    spatial_grid::SpatialGrid,
+};
+
+/// Above this number of grid points, the grid spacing is increased to keep the memory and the
+/// build time reasonable.
+const MAX_GRID_POINTS: usize = 32_000_000;
+
+/// Corners of a grid cell, as offsets from its lowest corner.
+const CELL_CORNERS: [[usize; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [1, 1, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [1, 1, 1],
+    [0, 1, 1],
+];
+
+/// Decomposition of a grid cell in six tetrahedra sharing its `0`-`6` diagonal, so that the
+/// tetrahedra of neighbouring cells share their faces.
+const CELL_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 5, 1, 6],
+    [0, 1, 2, 6],
+    [0, 2, 3, 6],
+    [0, 3, 7, 6],
+    [0, 7, 4, 6],
+    [0, 4, 5, 6],
+];
+
+/// Kind of molecular surface.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceKind {
+    /// Solvent accessible surface, traced by the center of the probe rolling on the atoms.
+    Sas,
+    /// Solvent excluded (Connolly) surface, the part of the space the probe can't reach.
+    Ses,
+}
+
+impl SurfaceKind {
+    /// Next surface, `None` being no surface, used to cycle through the surfaces at runtime.
+    pub fn next(surface: Option<Self>) -> Option<Self> {
+        match surface {
+            None => Some(Self::Sas),
+            Some(Self::Sas) => Some(Self::Ses),
+            Some(Self::Ses) => None,
+        }
+    }
+}
+
+/// `probe_radius` is the radius of the solvent molecule (in Å, 1.4 for water), `resolution` the
+/// spacing of the grid the surface is extracted from (in Å) and `opacity` the alpha of the
+/// surface color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceParameters {
+    pub probe_radius: f32,
+    pub resolution: f32,
+    pub opacity: f32,
+}
+
+/// Regular grid of scalar values, positive inside the surface.
+struct ScalarGrid {
+    origin: Point3<f32>,
+    spacing: f32,
+    dimensions: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl ScalarGrid {
+    fn new(origin: Point3<f32>, spacing: f32, dimensions: [usize; 3], value: f32) -> Self {
+        Self {
+            origin,
+            spacing,
+            dimensions,
+            values: vec![value; dimensions.iter().product()],
+        }
+    }
+
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.dimensions[1] + y) * self.dimensions[0] + x
+    }
+
+    fn point(&self, [x, y, z]: [usize; 3]) -> Point3<f32> {
+        self.origin + Vector3::new(x as f32, y as f32, z as f32) * self.spacing
+    }
+
+    /// Grid points closer than `radius` from `center`, along with their distance to it.
+    fn points_within(
+        &self,
+        center: &Point3<f32>,
+        radius: f32,
+    ) -> impl Iterator<Item = ([usize; 3], f32)> + '_ {
+        let cell = (center - self.origin) / self.spacing;
+        let reach = radius / self.spacing;
+        let range = |axis: usize| {
+            let start = (cell[axis] - reach).floor().max(0.0) as usize;
+            let end =
+                ((cell[axis] + reach).ceil().max(0.0) as usize).min(self.dimensions[axis] - 1);
+            start..=end
+        };
+        let (xs, ys, zs) = (range(0), range(1), range(2));
+        let center = *center;
+        zs.flat_map(move |z| ys.clone().map(move |y| (y, z)))
+            .flat_map(move |(y, z)| xs.clone().map(move |x| [x, y, z]))
+            .filter_map(move |point| {
+                let distance = (self.point(point) - center).norm();
+                (distance <= radius).then_some((point, distance))
+            })
+    }
+
+    /// Gradient of the values at a grid point, by central differences.
+    fn gradient(&self, point: [usize; 3]) -> Vector3<f32> {
+        let mut gradient = Vector3::zeros();
+        for axis in 0..3 {
+            let mut before = point;
+            let mut after = point;
+            before[axis] = point[axis].saturating_sub(1);
+            after[axis] = (point[axis] + 1).min(self.dimensions[axis] - 1);
+            let steps = (after[axis] - before[axis]).max(1) as f32;
+            gradient[axis] = (self.values[self.index(after)] - self.values[self.index(before)])
+                / (steps * self.spacing);
+        }
+        gradient
+    }
+}
+
+/// Build the surface of the given atoms (`positions` and van der Waals `radii`), each vertex
+/// taking the color of its nearest atom.
+/// The solvent accessible surface is the isosurface of the distance to the atoms inflated by the
+/// probe radius. The solvent excluded surface is the set of points at the probe radius from the
+/// probe centers, that is the grid points outside of the solvent accessible surface.
+/// Both are extracted with marching tetrahedra, their normals are the gradient of the field.
+pub fn build_surface(
+    kind: SurfaceKind,
+    positions: &[Point3<f32>],
+    radii: &[f32],
+    colors: &[Point4<f32>],
+    parameters: &SurfaceParameters,
+) -> Mesh {
+    if positions.is_empty() {
+        return Mesh::default();
+    }
+    let probe_radius = parameters.probe_radius.max(0.0);
+    // Without probe, the solvent excluded surface is the van der Waals surface.
+    let kind = match probe_radius <= f32::EPSILON {
+        true => SurfaceKind::Sas,
+        false => kind,
+    };
+
+    let max_radius = radii.iter().copied().fold(0.0, f32::max) + probe_radius;
+    let (min, max) = positions
+        .iter()
+        .fold((positions[0], positions[0]), |(min, max), position| {
+            (min.inf(position), max.sup(position))
+        });
+    let mut spacing = parameters.resolution.max(0.1);
+    let margin = max_radius + 3.0 * spacing;
+    let extent = max - min + Vector3::repeat(2.0 * margin);
+    let dimensions = |spacing: f32| extent.map(|extent| (extent / spacing).ceil() as usize + 1);
+    while dimensions(spacing).iter().product::<usize>() > MAX_GRID_POINTS {
+        spacing *= 1.25;
+    }
+    let dimensions = dimensions(spacing);
+    let dimensions = [dimensions.x, dimensions.y, dimensions.z];
+    let origin = min - Vector3::repeat(margin);
+
+    // Solvent accessible field, the distance to the closest inflated atom sphere.
+    let outside = -2.0 * spacing;
+    let mut field = ScalarGrid::new(origin, spacing, dimensions, outside);
+    for (position, radius) in positions.iter().zip(radii) {
+        let radius = radius + probe_radius;
+        let points = field
+            .points_within(position, radius + 2.0 * spacing)
+            .collect::<Vec<_>>();
+        for (point, distance) in points {
+            let index = field.index(point);
+            field.values[index] = field.values[index].max(radius - distance);
+        }
+    }
+
+    if kind == SurfaceKind::Ses {
+        field = solvent_excluded_field(&field, probe_radius);
+    }
+
+    let mut mesh = march_tetrahedra(&field);
+    color_vertices(
+        &mut mesh,
+        positions,
+        colors,
+        max_radius + spacing,
+        parameters.opacity,
+    );
+    mesh
+}
+
+/// Solvent excluded field from the solvent accessible one: the distance to the closest probe
+/// center minus the probe radius, probe centers being the grid points outside of the solvent
+/// accessible surface next to a point inside of it.
+fn solvent_excluded_field(accessible: &ScalarGrid, probe_radius: f32) -> ScalarGrid {
+    let [width, height, depth] = accessible.dimensions;
+    let is_inside = |point: [usize; 3]| accessible.values[accessible.index(point)] > 0.0;
+    let reach = probe_radius + 2.0 * accessible.spacing;
+    let mut distances = ScalarGrid::new(
+        accessible.origin,
+        accessible.spacing,
+        accessible.dimensions,
+        reach,
+    );
+
+    for z in 0..depth {
+        for y in 0..height {
+            for x in 0..width {
+                let point = [x, y, z];
+                if is_inside(point) {
+                    continue;
+                }
+                let is_probe_center = (0..3).any(|axis| {
+                    [point[axis].wrapping_sub(1), point[axis] + 1]
+                        .into_iter()
+                        .filter(|&coordinate| coordinate < accessible.dimensions[axis])
+                        .any(|coordinate| {
+                            let mut neighbour = point;
+                            neighbour[axis] = coordinate;
+                            is_inside(neighbour)
+                        })
+                });
+                if !is_probe_center {
+                    continue;
+                }
+                let center = accessible.point(point);
+                let points = distances.points_within(&center, reach).collect::<Vec<_>>();
+                for (point, distance) in points {
+                    let index = distances.index(point);
+                    distances.values[index] = distances.values[index].min(distance);
+                }
+            }
+        }
+    }
+
+    for (index, distance) in distances.values.iter_mut().enumerate() {
+        *distance = match accessible.values[index] > 0.0 {
+            true => *distance - probe_radius,
+            false => -probe_radius,
+        };
+    }
+    distances
+}
+
+/// Extract the zero isosurface of the field, each cell being split in six tetrahedra. Vertices
+/// are shared between the triangles of neighbouring tetrahedra.
+fn march_tetrahedra(field: &ScalarGrid) -> Mesh {
+    let mut mesh = Mesh::default();
+    let mut edge_vertices: HashMap<(usize, usize), u32> = HashMap::new();
+    let [width, height, depth] = field.dimensions;
+
+    let mut edge_vertex = |mesh: &mut Mesh, first: [usize; 3], second: [usize; 3]| {
+        let (first_index, second_index) = (field.index(first), field.index(second));
+        let key = (first_index.min(second_index), first_index.max(second_index));
+        *edge_vertices.entry(key).or_insert_with(|| {
+            let (first_value, second_value) =
+                (field.values[first_index], field.values[second_index]);
+            let t = first_value / (first_value - second_value);
+            let position = field.point(first) + (field.point(second) - field.point(first)) * t;
+            let gradient = field.gradient(first).lerp(&field.gradient(second), t);
+            let normal = (-gradient)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::z);
+            mesh.push_vertex(MeshVertex::new(
+                position,
+                normal,
+                Point4::new(1.0, 1.0, 1.0, 1.0),
+            ))
+        })
+    };
+
+    for z in 0..depth.saturating_sub(1) {
+        for y in 0..height.saturating_sub(1) {
+            for x in 0..width.saturating_sub(1) {
+                let corners = CELL_CORNERS.map(|[dx, dy, dz]| [x + dx, y + dy, z + dz]);
+                let inside = corners.map(|corner| field.values[field.index(corner)] > 0.0);
+                if inside.iter().all(|&inside| inside) || inside.iter().all(|&inside| !inside) {
+                    continue;
+                }
+
+                for tetrahedron in CELL_TETRAHEDRA {
+                    let (ins, outs): (Vec<usize>, Vec<usize>) =
+                        tetrahedron.iter().partition(|&&corner| inside[corner]);
+                    let mut edge =
+                        |a: usize, b: usize| edge_vertex(&mut mesh, corners[a], corners[b]);
+                    let triangles = match (ins.as_slice(), outs.as_slice()) {
+                        ([inside], [a, b, c]) | ([a, b, c], [inside]) => {
+                            vec![[edge(*inside, *a), edge(*inside, *b), edge(*inside, *c)]]
+                        }
+                        ([a, b], [c, d]) => {
+                            let (ac, ad, bc, bd) =
+                                (edge(*a, *c), edge(*a, *d), edge(*b, *c), edge(*b, *d));
+                            vec![[ac, ad, bd], [ac, bd, bc]]
+                        }
+                        _ => vec![],
+                    };
+                    for triangle in triangles {
+                        push_oriented_triangle(&mut mesh, triangle);
+                    }
+                }
+            }
+        }
+    }
+    mesh
+}
+
+/// Add a triangle facing the same side as the normals of its vertices.
+fn push_oriented_triangle(mesh: &mut Mesh, [first, second, third]: [u32; 3]) {
+    if first == second || second == third || first == third {
+        return;
+    }
+    let vertex = |index: u32| mesh.vertices[index as usize];
+    let position = |index: u32| Point3::from(vertex(index).position);
+    let normal = |index: u32| Vector3::from(vertex(index).normal);
+    let face_normal =
+        (position(second) - position(first)).cross(&(position(third) - position(first)));
+    if face_normal.dot(&(normal(first) + normal(second) + normal(third))) < 0.0 {
+        mesh.push_triangle(first, third, second);
+    } else {
+        mesh.push_triangle(first, second, third);
+    }
+}
+
+/// Give each vertex the color of the closest atom, and the surface opacity.
+fn color_vertices(
+    mesh: &mut Mesh,
+    positions: &[Point3<f32>],
+    colors: &[Point4<f32>],
+    search_radius: f32,
+    opacity: f32,
+) {
+    let grid = SpatialGrid::new(positions, search_radius);
+    for vertex in &mut mesh.vertices {
+        let position = Point3::from(vertex.position);
+        let closest =
+            grid.within(&position, search_radius)
+                .into_iter()
+                .min_by(|&first, &second| {
+                    let distance = |atom: usize| (positions[atom] - position).norm_squared();
+                    distance(first).total_cmp(&distance(second))
+                });
+        let mut color = closest.map_or(Point4::new(1.0, 1.0, 1.0, 1.0), |atom| colors[atom]);
+        color.w = opacity;
+        vertex.color = color.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Point4<f32> = Point4::new(1.0, 0.0, 0.0, 1.0);
+    const BLUE: Point4<f32> = Point4::new(0.0, 0.0, 1.0, 1.0);
+
+    fn parameters(probe_radius: f32) -> SurfaceParameters {
+        SurfaceParameters {
+            probe_radius,
+            resolution: 0.25,
+            opacity: 0.5,
+        }
+    }
+
+    /// Two atoms of radius 1.5 Å, 2 Å apart, the first one red and the second one blue.
+    fn build_pair(kind: SurfaceKind, probe_radius: f32) -> (Vec<Point3<f32>>, Mesh) {
+        let positions = vec![Point3::origin(), Point3::new(2.0, 0.0, 0.0)];
+        let mesh = build_surface(
+            kind,
+            &positions,
+            &[1.5, 1.5],
+            &[RED, BLUE],
+            &parameters(probe_radius),
+        );
+        (positions, mesh)
+    }
+
+    /// Whether every edge of the mesh is shared by two triangles, walked in opposite directions.
+    fn is_closed(mesh: &Mesh) -> bool {
+        let mut edges: HashMap<(u32, u32), i32> = HashMap::new();
+        for triangle in mesh.indices.chunks(3) {
+            for (first, second) in [(0, 1), (1, 2), (2, 0)] {
+                let (first, second) = (triangle[first], triangle[second]);
+                let key = (first.min(second), first.max(second));
+                *edges.entry(key).or_default() += if first < second { 1 } else { -1 };
+            }
+        }
+        !edges.is_empty() && edges.values().all(|&count| count == 0)
+    }
+
+    #[test]
+    fn accessible_surface_of_an_atom() {
+        let center = Point3::new(1.0, -2.0, 0.5);
+        let mesh = build_surface(
+            SurfaceKind::Sas,
+            &[center],
+            &[1.7],
+            &[RED],
+            &parameters(1.4),
+        );
+        assert!(!mesh.is_empty());
+        for vertex in &mesh.vertices {
+            let offset = Point3::from(vertex.position) - center;
+            assert!((offset.norm() - 3.1).abs() < 0.02, "{}", offset.norm());
+            // Normals point outwards.
+            assert!(Vector3::from(vertex.normal).dot(&offset.normalize()) > 0.99);
+        }
+        assert!(is_closed(&mesh));
+    }
+
+    #[test]
+    fn excluded_surface_of_two_atoms() {
+        let (positions, mesh) = build_pair(SurfaceKind::Ses, 1.4);
+        assert!(is_closed(&mesh));
+        // A single closed surface, of Euler characteristic 2.
+        let edges = mesh.indices.len() / 2;
+        let faces = mesh.indices.len() / 3;
+        assert_eq!(mesh.vertices.len() + faces - edges, 2);
+        // Outside of the van der Waals spheres, filling the crevice between them.
+        let distance = |vertex: &MeshVertex| {
+            positions
+                .iter()
+                .map(|position| (Point3::from(vertex.position) - position).norm())
+                .fold(f32::MAX, f32::min)
+        };
+        assert!(mesh.vertices.iter().all(|vertex| distance(vertex) > 1.45));
+        assert!(mesh.vertices.iter().any(|vertex| distance(vertex) > 1.6));
+        assert!(mesh.vertices.iter().all(|vertex| distance(vertex) < 2.9));
+    }
+
+    #[test]
+    fn nearest_atom_color() {
+        let (positions, mesh) = build_pair(SurfaceKind::Sas, 1.4);
+        for vertex in &mesh.vertices {
+            let position = Point3::from(vertex.position);
+            let expected = match (position - positions[0]).norm() < (position - positions[1]).norm()
+            {
+                true => RED,
+                false => BLUE,
+            };
+            assert_eq!(vertex.color, [expected.x, expected.y, expected.z, 0.5]);
+        }
+    }
+}
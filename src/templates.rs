@@ -0,0 +1,318 @@
+use crate::bonding::BondOrder;
+
+const S: BondOrder = BondOrder::Single;
+const D: BondOrder = BondOrder::Double;
+const AR: BondOrder = BondOrder::Aromatic;
+
+/// A bond between two atoms of a residue, referenced by their PDB atom name.
+type TemplateBond = (&'static str, &'static str, BondOrder);
+
+/// Family of a residue template, residues of the same family are linked together along a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResidueKind {
+    AminoAcid,
+    Nucleotide,
+    Water,
+}
+
+impl ResidueKind {
+    /// Bonds shared by every residue of the family.
+    fn backbone(&self) -> &'static [TemplateBond] {
+        match self {
+            Self::AminoAcid => AMINO_ACID_BACKBONE,
+            Self::Nucleotide => NUCLEOTIDE_BACKBONE,
+            Self::Water => &[],
+        }
+    }
+
+    /// Atom of a residue bonded to the atom of the next residue in the chain: the peptide bond of
+    /// the amino acids, and the phosphodiester bond of the nucleotides.
+    pub fn link(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::AminoAcid => Some(("C", "N")),
+            Self::Nucleotide => Some(("O3'", "P")),
+            Self::Water => None,
+        }
+    }
+}
+
+/// Connectivity of a standard residue, as found in the chemical component dictionary.
+/// Only the heavy atoms are described, hydrogens are named too inconsistently across the force
+/// fields and are bonded to their closest heavy atom instead.
+pub struct ResidueTemplate {
+    pub kind: ResidueKind,
+    side_chain: &'static [TemplateBond],
+}
+
+impl ResidueTemplate {
+    /// Every bond of the residue, including the ones of its family backbone.
+    pub fn bonds(&self) -> impl Iterator<Item = &TemplateBond> {
+        self.kind.backbone().iter().chain(self.side_chain)
+    }
+}
+
+/// Find the template of a residue, from its name. Protonation state variants (`HID`, `HIE`,
+/// `CYX`, ...) share the template of their standard residue.
+pub fn template(residue_name: &str) -> Option<&'static ResidueTemplate> {
+    let template = match residue_name.trim().to_ascii_uppercase().as_str() {
+        "ALA" => &ALA,
+        "ARG" | "ARN" => &ARG,
+        "ASN" => &ASN,
+        "ASP" | "ASH" => &ASP,
+        "CYS" | "CYX" | "CYM" => &CYS,
+        "GLN" => &GLN,
+        "GLU" | "GLH" => &GLU,
+        "GLY" => &GLY,
+        "HIS" | "HID" | "HIE" | "HIP" | "HSD" | "HSE" | "HSP" => &HIS,
+        "ILE" => &ILE,
+        "LEU" => &LEU,
+        "LYS" | "LYN" => &LYS,
+        "MET" => &MET,
+        "PHE" => &PHE,
+        "PRO" => &PRO,
+        "SER" => &SER,
+        "THR" => &THR,
+        "TRP" => &TRP,
+        "TYR" => &TYR,
+        "VAL" => &VAL,
+        "A" | "DA" | "RA" => &ADENINE,
+        "C" | "DC" | "RC" => &CYTOSINE,
+        "G" | "DG" | "RG" => &GUANINE,
+        "T" | "DT" => &THYMINE,
+        "U" | "DU" | "RU" => &URACIL,
+        "HOH" | "WAT" | "H2O" | "DOD" | "SOL" | "TIP3" => &WATER,
+        _ => return None,
+    };
+    Some(template)
+}
+
+const AMINO_ACID_BACKBONE: &[TemplateBond] = &[
+    ("N", "CA", S),
+    ("CA", "C", S),
+    ("C", "O", D),
+    ("C", "OXT", S),
+];
+
+/// Sugar phosphate backbone of both DNA and RNA (`O2'` only exists in RNA), with the current and
+/// the pre-remediation names of the phosphate oxygens.
+const NUCLEOTIDE_BACKBONE: &[TemplateBond] = &[
+    ("OP3", "P", S),
+    ("P", "OP1", D),
+    ("P", "OP2", S),
+    ("P", "O1P", D),
+    ("P", "O2P", S),
+    ("P", "O5'", S),
+    ("O5'", "C5'", S),
+    ("C5'", "C4'", S),
+    ("C4'", "O4'", S),
+    ("C4'", "C3'", S),
+    ("C3'", "O3'", S),
+    ("C3'", "C2'", S),
+    ("C2'", "O2'", S),
+    ("C2'", "C1'", S),
+    ("C1'", "O4'", S),
+];
+
+const fn amino_acid(side_chain: &'static [TemplateBond]) -> ResidueTemplate {
+    ResidueTemplate {
+        kind: ResidueKind::AminoAcid,
+        side_chain,
+    }
+}
+
+const fn nucleotide(base: &'static [TemplateBond]) -> ResidueTemplate {
+    ResidueTemplate {
+        kind: ResidueKind::Nucleotide,
+        side_chain: base,
+    }
+}
+
+const ALA: ResidueTemplate = amino_acid(&[("CA", "CB", S)]);
+const ARG: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD", S),
+    ("CD", "NE", S),
+    ("NE", "CZ", S),
+    ("CZ", "NH1", D),
+    ("CZ", "NH2", S),
+]);
+const ASN: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "OD1", D),
+    ("CG", "ND2", S),
+]);
+const ASP: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "OD1", D),
+    ("CG", "OD2", S),
+]);
+const CYS: ResidueTemplate = amino_acid(&[("CA", "CB", S), ("CB", "SG", S)]);
+const GLN: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD", S),
+    ("CD", "OE1", D),
+    ("CD", "NE2", S),
+]);
+const GLU: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD", S),
+    ("CD", "OE1", D),
+    ("CD", "OE2", S),
+]);
+const GLY: ResidueTemplate = amino_acid(&[]);
+const HIS: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "ND1", AR),
+    ("CG", "CD2", AR),
+    ("ND1", "CE1", AR),
+    ("CE1", "NE2", AR),
+    ("CD2", "NE2", AR),
+]);
+const ILE: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG1", S),
+    ("CB", "CG2", S),
+    ("CG1", "CD1", S),
+    // Older files and some force fields name it CD.
+    ("CG1", "CD", S),
+]);
+const LEU: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD1", S),
+    ("CG", "CD2", S),
+]);
+const LYS: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD", S),
+    ("CD", "CE", S),
+    ("CE", "NZ", S),
+]);
+const MET: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "SD", S),
+    ("SD", "CE", S),
+]);
+const PHE: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD1", AR),
+    ("CG", "CD2", AR),
+    ("CD1", "CE1", AR),
+    ("CD2", "CE2", AR),
+    ("CE1", "CZ", AR),
+    ("CE2", "CZ", AR),
+]);
+const PRO: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD", S),
+    ("CD", "N", S),
+]);
+const SER: ResidueTemplate = amino_acid(&[("CA", "CB", S), ("CB", "OG", S)]);
+const THR: ResidueTemplate = amino_acid(&[("CA", "CB", S), ("CB", "OG1", S), ("CB", "CG2", S)]);
+const TRP: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD1", AR),
+    ("CG", "CD2", AR),
+    ("CD1", "NE1", AR),
+    ("NE1", "CE2", AR),
+    ("CD2", "CE2", AR),
+    ("CD2", "CE3", AR),
+    ("CE2", "CZ2", AR),
+    ("CE3", "CZ3", AR),
+    ("CZ2", "CH2", AR),
+    ("CZ3", "CH2", AR),
+]);
+const TYR: ResidueTemplate = amino_acid(&[
+    ("CA", "CB", S),
+    ("CB", "CG", S),
+    ("CG", "CD1", AR),
+    ("CG", "CD2", AR),
+    ("CD1", "CE1", AR),
+    ("CD2", "CE2", AR),
+    ("CE1", "CZ", AR),
+    ("CE2", "CZ", AR),
+    ("CZ", "OH", S),
+]);
+const VAL: ResidueTemplate = amino_acid(&[("CA", "CB", S), ("CB", "CG1", S), ("CB", "CG2", S)]);
+
+const ADENINE: ResidueTemplate = nucleotide(&[
+    ("C1'", "N9", S),
+    ("N9", "C8", AR),
+    ("C8", "N7", AR),
+    ("N7", "C5", AR),
+    ("C5", "C4", AR),
+    ("C4", "N9", AR),
+    ("C5", "C6", AR),
+    ("C6", "N1", AR),
+    ("N1", "C2", AR),
+    ("C2", "N3", AR),
+    ("N3", "C4", AR),
+    ("C6", "N6", S),
+]);
+const GUANINE: ResidueTemplate = nucleotide(&[
+    ("C1'", "N9", S),
+    ("N9", "C8", AR),
+    ("C8", "N7", AR),
+    ("N7", "C5", AR),
+    ("C5", "C4", AR),
+    ("C4", "N9", AR),
+    ("C5", "C6", S),
+    ("C6", "N1", S),
+    ("N1", "C2", S),
+    ("C2", "N3", D),
+    ("N3", "C4", S),
+    ("C6", "O6", D),
+    ("C2", "N2", S),
+]);
+const CYTOSINE: ResidueTemplate = nucleotide(&[
+    ("C1'", "N1", S),
+    ("N1", "C2", S),
+    ("C2", "N3", S),
+    ("N3", "C4", D),
+    ("C4", "C5", S),
+    ("C5", "C6", D),
+    ("C6", "N1", S),
+    ("C2", "O2", D),
+    ("C4", "N4", S),
+]);
+const THYMINE: ResidueTemplate = nucleotide(&[
+    ("C1'", "N1", S),
+    ("N1", "C2", S),
+    ("C2", "N3", S),
+    ("N3", "C4", S),
+    ("C4", "C5", S),
+    ("C5", "C6", D),
+    ("C6", "N1", S),
+    ("C2", "O2", D),
+    ("C4", "O4", D),
+    ("C5", "C7", S),
+    // Pre-remediation name of the methyl carbon.
+    ("C5", "C5M", S),
+]);
+const URACIL: ResidueTemplate = nucleotide(&[
+    ("C1'", "N1", S),
+    ("N1", "C2", S),
+    ("C2", "N3", S),
+    ("N3", "C4", S),
+    ("C4", "C5", S),
+    ("C5", "C6", D),
+    ("C6", "N1", S),
+    ("C2", "O2", D),
+    ("C4", "O4", D),
+]);
+
+const WATER: ResidueTemplate = ResidueTemplate {
+    kind: ResidueKind::Water,
+    side_chain: &[],
+};